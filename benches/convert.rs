@@ -0,0 +1,46 @@
+//! Measures converting 1080p frames to RGBA.
+//!
+//! NV12 takes the SIMD conversion where the CPU supports it while I420 is
+//! converted a pixel at a time by the scalar path, so comparing the two shows
+//! the speedup.  Building with `--no-default-features` disables the SIMD
+//! kernels to measure the scalar NV12 conversion instead.
+//!
+//!     cargo bench --bench convert
+
+use std::time::{Duration, Instant};
+use videostream::frame::Frame;
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const ITERATIONS: u32 = 30;
+
+fn bench(fourcc: &str) -> Duration {
+    let mut src = Frame::new(WIDTH, HEIGHT, 0, fourcc).unwrap();
+    src.alloc(None).unwrap();
+    for (i, byte) in src.mmap_mut().unwrap().iter_mut().enumerate() {
+        *byte = (i * 7) as u8;
+    }
+    let mut dst = Frame::new(WIDTH, HEIGHT, 0, "RGBA").unwrap();
+    dst.alloc(None).unwrap();
+
+    src.convert_to(&mut dst).unwrap();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        src.convert_to(&mut dst).unwrap();
+    }
+    return start.elapsed() / ITERATIONS;
+}
+
+fn main() {
+    let nv12 = bench("NV12");
+    let i420 = bench("I420");
+    println!(
+        "NV12 to RGBA: {:.2} ms per frame",
+        nv12.as_secs_f64() * 1000.0
+    );
+    println!(
+        "I420 to RGBA: {:.2} ms per frame",
+        i420.as_secs_f64() * 1000.0
+    );
+    println!("speedup: {:.1}x", i420.as_secs_f64() / nv12.as_secs_f64());
+}
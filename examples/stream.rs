@@ -0,0 +1,27 @@
+//! Receives five frames from a host as an async stream.
+//!
+//! cargo run --example stream --features async -- /tmp/camera.vsl
+
+use futures::{executor::block_on, StreamExt};
+use videostream::client::Client;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "/tmp/camera.vsl".to_string());
+    let client = Client::new(&path, true).unwrap();
+
+    let frames: Vec<_> = block_on(client.into_stream().take(5).collect());
+    for frame in frames {
+        match frame {
+            Ok(frame) => println!(
+                "frame {} {}x{} pts {}",
+                frame.serial(),
+                frame.width(),
+                frame.height(),
+                frame.pts()
+            ),
+            Err(err) => eprintln!("stream ended: {}", err),
+        }
+    }
+}
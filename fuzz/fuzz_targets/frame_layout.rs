@@ -0,0 +1,22 @@
+//! Feeds arbitrary frame descriptions, as a host would advertise them, through
+//! the layout validation used by the client.
+//!
+//! cargo +nightly fuzz run frame_layout
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use videostream::format::{format_info, layout_fits};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 24 {
+        return;
+    }
+    let field = |i: usize| u32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+    let fourcc = field(0);
+    let (width, height, stride) = (field(1), field(2), field(3));
+    let size = u64::from_le_bytes(data[16..24].try_into().unwrap()) as usize;
+
+    let _ = format_info(fourcc);
+    let _ = layout_fits(fourcc, width, height, stride, size);
+});
@@ -0,0 +1,158 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Bytes of an acknowledgement: the client id, the frame's serial, the
+/// processing time in nanoseconds and the frames the client has dropped.
+const ACK_LEN: usize = 8 * 4;
+
+/// Bytes of the acknowledgements of older clients, which do not report the
+/// frames they dropped.
+const LEGACY_ACK_LEN: usize = 8 * 3;
+
+/// Numbers the clients of this process sending acknowledgements.
+static NEXT_CLIENT: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the path of the acknowledgement socket beside the host's socket.
+fn ack_path(path: &Path) -> PathBuf {
+    let mut ack = OsString::from(path.as_os_str());
+    ack.push(".ack");
+    return PathBuf::from(ack);
+}
+
+/// The AckListener receives the acknowledgements sent by clients as they drop
+/// their frames.
+///
+/// The library's protocol has no message from clients to the host, so the
+/// acknowledgements are datagrams sent to a second socket beside the host's,
+/// named with an ".ack" suffix.  Each carries an id chosen by the client, as
+/// datagrams from unbound sockets cannot be told apart.
+pub(crate) struct AckListener {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl AckListener {
+    pub fn new(host_path: &Path) -> io::Result<Self> {
+        let path = ack_path(host_path);
+        // A socket left behind by a previous host would prevent binding.
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path)?;
+        socket.set_nonblocking(true)?;
+        return Ok(AckListener { socket, path });
+    }
+
+    /// Passes the client id, serial and processing time of each received
+    /// acknowledgement to `acked`, along with the frames the client has
+    /// dropped when it reports them.
+    pub fn service(
+        &self,
+        mut acked: impl FnMut(u64, i64, Duration, Option<u64>),
+    ) -> io::Result<()> {
+        let mut buf = [0u8; ACK_LEN];
+        loop {
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            // Any process may send to the socket, malformed datagrams are
+            // ignored.
+            if len != ACK_LEN && len != LEGACY_ACK_LEN {
+                continue;
+            }
+            let field = |index: usize| {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&buf[index * 8..index * 8 + 8]);
+                return u64::from_le_bytes(bytes);
+            };
+            let dropped = if len == ACK_LEN { Some(field(3)) } else { None };
+            acked(
+                field(0),
+                field(1) as i64,
+                Duration::from_nanos(field(2)),
+                dropped,
+            );
+        }
+    }
+}
+
+impl Drop for AckListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Sends a client's acknowledgements to the host at `path`.
+pub(crate) struct AckSender {
+    socket: UnixDatagram,
+    path: PathBuf,
+    id: u64,
+}
+
+impl AckSender {
+    pub fn new(host_path: &Path) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        // A host slow to read its acknowledgements must not stall the client.
+        socket.set_nonblocking(true)?;
+        let id = (process::id() as u64) << 32 | NEXT_CLIENT.fetch_add(1, Ordering::Relaxed) as u64;
+        return Ok(AckSender {
+            socket,
+            path: ack_path(host_path),
+            id,
+        });
+    }
+
+    /// Returns the id identifying the client to the host.
+    pub fn id(&self) -> u64 {
+        return self.id;
+    }
+
+    fn send(&self, serial: i64, processing: Duration, dropped: u64) {
+        let mut buf = [0u8; ACK_LEN];
+        buf[..8].copy_from_slice(&self.id.to_le_bytes());
+        buf[8..16].copy_from_slice(&serial.to_le_bytes());
+        buf[16..24].copy_from_slice(&(processing.as_nanos() as u64).to_le_bytes());
+        buf[24..].copy_from_slice(&dropped.to_le_bytes());
+        // Acknowledgements are best effort, they are lost when the host does
+        // not listen for them or its socket is full.
+        let _ = self.socket.send_to(&buf, &self.path);
+    }
+}
+
+/// The acknowledgement of a frame returned to the application, sent with the
+/// time it was held once dropped along with its frame, and the frames the
+/// client had dropped when it was returned.
+pub(crate) struct PendingAck {
+    sender: Arc<AckSender>,
+    serial: i64,
+    returned: Instant,
+    dropped: u64,
+}
+
+impl PendingAck {
+    pub fn new(sender: Arc<AckSender>, serial: i64, dropped: u64) -> Self {
+        return PendingAck {
+            sender,
+            serial,
+            returned: Instant::now(),
+            dropped,
+        };
+    }
+}
+
+impl Drop for PendingAck {
+    fn drop(&mut self) {
+        self.sender
+            .send(self.serial, self.returned.elapsed(), self.dropped);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::client::ClientError;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    io::{self, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The longest token accepted, so a client cannot make the host allocate an
+/// arbitrary amount.
+const MAX_TOKEN_LEN: usize = 4096;
+
+/// How long the host waits for an authenticating client to send its token
+/// before dropping it.
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long the client waits for the host's answer, which is sent the next
+/// time the host processes its connections.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long after authenticating a process may connect its clients.
+const AUTHORIZED_FOR: Duration = Duration::from_secs(10);
+
+/// Returns the path of the authentication socket beside the host's socket.
+fn auth_path(path: &Path) -> PathBuf {
+    let mut auth = OsString::from(path.as_os_str());
+    auth.push(".auth");
+    return PathBuf::from(auth);
+}
+
+/// Compares the tokens in time independent of where they differ.
+fn tokens_match(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    return a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0;
+}
+
+/// Returns the process id of the peer of a UNIX socket.
+fn peer_pid(fd: RawFd) -> io::Result<libc::pid_t> {
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    return Ok(cred.pid);
+}
+
+/// The Authenticator admits only the clients of processes which presented the
+/// host's token.
+///
+/// The library's handshake cannot carry a token, so clients first present it
+/// over a second socket beside the host's, named with an ".auth" suffix.  The
+/// process of each client the library accepts is then checked against those
+/// which authenticated, through the socket's peer credentials, and clients of
+/// other processes are shut down so the library drops them.
+pub(crate) struct Authenticator {
+    listener: UnixListener,
+    path: PathBuf,
+    token: Vec<u8>,
+    authorized: Mutex<HashMap<libc::pid_t, Instant>>,
+    checked: Mutex<HashSet<RawFd>>,
+    pending: Mutex<Vec<Pending>>,
+}
+
+/// A client which has connected to authenticate and has not been answered.
+struct Pending {
+    stream: UnixStream,
+    /// The bytes of the token's length and the token received so far.
+    received: Vec<u8>,
+    since: Instant,
+}
+
+impl Authenticator {
+    pub fn new(host_path: &Path, token: Vec<u8>) -> io::Result<Self> {
+        let path = auth_path(host_path);
+        // A socket left behind by a previous host would prevent binding.
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        return Ok(Authenticator {
+            listener,
+            path,
+            token,
+            authorized: Mutex::new(HashMap::new()),
+            checked: Mutex::new(HashSet::new()),
+            pending: Mutex::new(Vec::new()),
+        });
+    }
+
+    /// Answers the clients waiting to authenticate.  Tokens are read as they
+    /// arrive, so a client which is slow to send its token does not block the
+    /// host.
+    pub fn service(&self) -> io::Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            };
+            // A client which fails the exchange is refused, not the host.
+            if stream.set_nonblocking(true).is_ok() {
+                pending.push(Pending {
+                    stream,
+                    received: Vec::new(),
+                    since: Instant::now(),
+                });
+            }
+        }
+        pending.retain_mut(|client| return self.receive(client));
+        return Ok(());
+    }
+
+    /// Reads what the client has sent of its token, answering it once the
+    /// token is complete.  Returns false once the client is answered, has
+    /// disconnected or took too long, so it is dropped.
+    fn receive(&self, client: &mut Pending) -> bool {
+        let mut buf = [0u8; 512];
+        loop {
+            match client.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => client.received.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+            if let Some(answer) = self.answer(client) {
+                let _ = client.stream.write_all(&[answer]);
+                return false;
+            }
+        }
+        return client.since.elapsed() < TOKEN_TIMEOUT;
+    }
+
+    /// Returns the answer to the client, 1 when it presented the token and the
+    /// process is authorized, or None while its token is incomplete.
+    fn answer(&self, client: &Pending) -> Option<u8> {
+        let len = <[u8; 4]>::try_from(client.received.get(..4)?).ok()?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_TOKEN_LEN {
+            return Some(0);
+        }
+        let token = client.received.get(4..4 + len)?;
+        if !tokens_match(token, &self.token) {
+            return Some(0);
+        }
+        let pid = match peer_pid(client.stream.as_raw_fd()) {
+            Ok(pid) => pid,
+            Err(_) => return Some(0),
+        };
+        let mut authorized = self.authorized.lock().unwrap();
+        authorized.retain(|_, since| since.elapsed() < AUTHORIZED_FOR);
+        authorized.insert(pid, Instant::now());
+        return Some(1);
+    }
+
+    /// Shuts down the newly accepted client sockets of processes which have not
+    /// authenticated.
+    pub fn enforce(&self, clients: &[RawFd]) {
+        let authorized = self.authorized.lock().unwrap();
+        let mut checked = self.checked.lock().unwrap();
+        // Descriptors of disconnected clients may be reused by new ones.
+        checked.retain(|fd| clients.contains(fd));
+        for &fd in clients {
+            if !checked.insert(fd) {
+                continue;
+            }
+            let admitted = match peer_pid(fd) {
+                Ok(pid) => authorized
+                    .get(&pid)
+                    .is_some_and(|since| since.elapsed() < AUTHORIZED_FOR),
+                Err(_) => false,
+            };
+            if !admitted {
+                unsafe { libc::shutdown(fd, libc::SHUT_RDWR) };
+            }
+        }
+    }
+}
+
+impl Drop for Authenticator {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Presents the token to the host at `path`.  Hosts which do not require a
+/// token have no authentication socket, so the client connects as usual.
+pub(crate) fn authenticate(path: &Path, token: &[u8]) -> Result<(), ClientError> {
+    let mut stream = match UnixStream::connect(auth_path(path)) {
+        Ok(stream) => stream,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(ClientError::Io(err)),
+    };
+    stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+    stream.write_all(&(token.len() as u32).to_le_bytes())?;
+    stream.write_all(token)?;
+
+    let mut answer = [0u8; 1];
+    match stream.read_exact(&mut answer) {
+        Ok(()) if answer[0] == 1 => return Ok(()),
+        Ok(()) => return Err(ClientError::AuthFailed),
+        // The host closes the connection on tokens it cannot read.
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+            return Err(ClientError::AuthFailed)
+        }
+        Err(err) => return Err(err.into()),
+    }
+}
@@ -1,76 +1,1430 @@
-use crate::frame::Frame;
+#[cfg(feature = "async")]
+use crate::stream::FrameStream;
+use crate::{
+    ack::{AckSender, PendingAck},
+    auth, encoder,
+    format::{self, FourCC},
+    frame::{Frame, FrameError, FrameFormat},
+    metadata::Metadata,
+    partial,
+    prefetch::{Prefetched, Prefetcher},
+    subscription::{self, Subscription},
+    tee::{self, FrameReceiver},
+    util::{JitterMeter, JitterStats, ThreadConfig},
+    Endpoint, VslError,
+};
 use std::{
+    collections::VecDeque,
     error::Error,
     ffi::{c_void, CStr, CString},
-    io,
+    fmt, io,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 use videostream_sys as ffi;
 
-use crate::NullStringError;
+/// The socket timeout in seconds of a newly created client, matching the
+/// library's default.
+const DEFAULT_TIMEOUT: f32 = 1.0;
+
+/// The socket timeout in seconds used by [`Client::recv`] to only take frames
+/// which have already arrived.
+const DRAIN_TIMEOUT: f32 = 0.001;
+
+/// The interval between connection attempts by [`ClientOptions::connect_wait`].
+const CONNECT_RETRY: Duration = Duration::from_millis(50);
+
+/// The remaining time below which a wait which returned early is not retried,
+/// the resolution of the library's timeout.
+const WAIT_GRANULARITY: Duration = Duration::from_millis(1);
+
+/// Number of recent frames over which the received framerate is measured.
+const FRAME_RATE_WINDOW: usize = 30;
 
 pub struct Client {
     ptr: *mut ffi::VSLClient,
+    handle: Arc<ClientHandle>,
+    closed: bool,
+    timeout: Mutex<f32>,
+    /// The library's current socket timeout, which the receiving methods
+    /// override temporarily.
+    socket_timeout: Mutex<f32>,
+    prefetch: Option<Prefetcher>,
+    decimate: u32,
+    reorder_window: usize,
+    preferred: Vec<FourCC>,
+    deliver_as: Option<FourCC>,
+    assemble_partial: bool,
+    thread_config: ThreadConfig,
+    acks: Option<Arc<AckSender>>,
+    state: Mutex<ClientState>,
+}
+
+/// Owns the library client.  Received frames are unlocked through the client
+/// when released so each holds a reference, releasing the client only once
+/// the Client and all of its frames have been dropped.
+pub(crate) struct ClientHandle(pub(crate) *mut ffi::VSLClient);
+
+unsafe impl Send for ClientHandle {}
+unsafe impl Sync for ClientHandle {}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        unsafe { ffi::vsl_client_release(self.0) }
+    }
+}
+
+/// The best of the preferred formats the host is posting, see
+/// [`ClientOptions::prefer_formats`].
+struct BestFormat {
+    /// The position of the format in the preferred formats.
+    rank: usize,
+    /// The pts of the last frame of the format.
+    pts: i64,
+    /// The pts of the first frame of another format following the last frame
+    /// of this format.
+    missed: Option<i64>,
+}
+
+/// Reception state shared by the receiving methods.
+struct ClientState {
+    received: u64,
+    /// Frames returned and the bytes of their buffers, see [`Client::stats`].
+    returned: u64,
+    bytes: u64,
+    /// Frames skipped by decimation or format preference.
+    skipped: u64,
+    last_signal: Option<Instant>,
+    /// Measures the frames returned, see [`Client::measured_fps`].
+    rate: JitterMeter,
+    /// The framerate sent by hosts built with
+    /// [`crate::host::HostBuilder::frame_rate`].
+    target_fps: Option<f64>,
+    reorder: VecDeque<Frame>,
+    /// The format of the last frame returned by [`Client::next_event`].
+    format: Option<FrameFormat>,
+    /// A frame held back by [`Client::next_event`] while it reports the
+    /// frame's new format, returned by the next receive.
+    pending: Option<Frame>,
+    /// The best of the preferred formats the host is posting.
+    best_format: Option<BestFormat>,
+    /// Measures frame arrivals when enabled by
+    /// [`ClientOptions::measure_jitter`].
+    jitter: Option<JitterMeter>,
+    /// The serial and frame partial updates are merged into when enabled by
+    /// [`ClientOptions::assemble_partial`].
+    base: Option<(i64, Frame)>,
+    numbering: FrameNumbering,
+    /// The frames numbered under [`FrameNumbering::ClientMonotonic`].
+    numbered: u64,
+    /// Whether the host said goodbye through
+    /// [`crate::host::Host::shutdown`] as it terminated.
+    terminated: bool,
+    /// The metadata of the frames received, sent by hosts built with
+    /// [`crate::host::HostBuilder::control_frames`].
+    metadata: Metadata,
+    /// An error which ended a drain of [`Client::recv`] or a batch of
+    /// [`Client::get_frames`], returned by the next receive.
+    deferred: Option<VslError>,
+}
+
+/// The ClientStats is a snapshot of a client's reception, returned by
+/// [`Client::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientStats {
+    /// Frames returned by the receiving methods.
+    pub frames_received: u64,
+    /// Bytes of the buffers of the frames returned.
+    pub bytes_received: u64,
+    /// Frames which arrived but were skipped by [`ClientOptions::decimate`]
+    /// or [`ClientOptions::prefer_formats`], or dropped by
+    /// [`ClientOptions::prefetch`] as its queue was full.
+    pub frames_skipped: u64,
+}
+
+/// The FrameMeta describes a frame received by [`Client::get_frame_into`],
+/// whose pixels were copied to the caller's buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameMeta {
+    pub width: u32,
+    pub height: u32,
+    pub fourcc: u32,
+    /// Bytes copied to the buffer, the frame's planes without row padding.
+    pub len: usize,
+    pub serial: i64,
+    pub timestamp: i64,
+    pub duration: i64,
+    pub pts: i64,
+    pub dts: i64,
+}
+
+/// An event received by [`Client::next_event`].
+pub enum ClientEvent {
+    /// A frame was received, as returned by [`Client::recv`].
+    Frame(Frame),
+    /// The host signalled it is alive without posting a frame, see
+    /// [`crate::host::Host::send_heartbeat`].
+    Heartbeat,
+    /// The host announced the format of the frames it intends to post through
+    /// [`crate::host::Host::post_probe`].  The frame is a probe, see
+    /// [`Frame::probe`], describing the format without a buffer.
+    Probe(Frame),
+    /// The frames which follow have a different format to the previous frame,
+    /// such as when the host's source changes resolution.  This is also the
+    /// first event, describing the initial format.
+    FormatChanged(FrameFormat),
+    /// The connection to the host was lost.  Clients created with
+    /// [`ClientOptions::reconnect`] may receive further events once the host
+    /// is back, others should be dropped.
+    Disconnected,
+}
+
+/// How [`Client::recv`] waits for a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvMode {
+    /// Waits up to the given time for the next frame, returning the oldest
+    /// frame not yet received.
+    Blocking(Duration),
+    /// Returns the oldest frame which has already arrived without waiting
+    /// for one.
+    NonBlocking,
+    /// Returns the newest frame which has already arrived, releasing the
+    /// older ones unseen, for consumers which only want the current frame
+    /// such as a display falling behind the host.
+    Latest,
+}
+
+/// How [`Client::get_numbered_frame`] numbers the frames it returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameNumbering {
+    /// Frames are numbered by their serial, which the host assigns as it
+    /// posts them.  Serials start again when the host restarts and skip the
+    /// frames this client missed or skipped.
+    #[default]
+    HostSerial,
+    /// Frames are numbered by the client from 0, incrementing by one for each
+    /// frame returned, so the numbers are unique and dense for the client's
+    /// lifetime whatever the host's serials.
+    ClientMonotonic,
+}
+
+/// A frame returned by [`Client::get_numbered_frame`] with its number.
+pub struct NumberedFrame {
+    /// The frame's number, as configured by [`Client::set_frame_numbering`].
+    pub number: u64,
+    pub frame: Frame,
+}
+
+/// A signal from the host seen while waiting for frames.
+enum Signal {
+    Frame(Frame),
+    Heartbeat,
+    Probe(Frame),
+}
+
+/// The ClientOptions configures optional Client behaviour before connecting to
+/// the host.
+#[derive(Clone)]
+pub struct ClientOptions {
+    reconnect: bool,
+    decimate: u32,
+    reorder_window: usize,
+    preferred: Vec<FourCC>,
+    auth_token: Option<Vec<u8>>,
+    jitter_window: Option<usize>,
+    prefetch: usize,
+    deliver_as: Option<FourCC>,
+    assemble_partial: bool,
+    thread_config: ThreadConfig,
+    send_acks: bool,
+}
+
+/// The ClientError describes why a frame could not be received.
+#[derive(Debug)]
+pub enum ClientError {
+    /// No frame arrived within the client's timeout.
+    Timeout,
+    /// The wait for a frame failed, typically because the host went away.
+    Io(io::Error),
+    /// The host described a frame whose layout does not fit its buffer.
+    Malformed,
+    /// Frames received from the client were still held when it was closed,
+    /// the client is released once they have been dropped.
+    FramesOutstanding(usize),
+    /// The host refused the client's authentication token.
+    AuthFailed,
+    /// The buffer given to [`Client::get_frame_into`] is smaller than the
+    /// received frame.
+    BufferTooSmall { required: usize, len: usize },
+    /// The received frame could not be converted to the format requested by
+    /// [`ClientOptions::deliver_as`], such as an encoded frame.  The frame is
+    /// skipped and later frames are still received.
+    Convert(FrameError),
+    /// The host said goodbye through [`crate::host::Host::shutdown`].  When
+    /// `reconnectable` the host is restarting and clients created with
+    /// [`ClientOptions::reconnect`] receive its frames once it is back.
+    /// Otherwise the host terminated and every later receive fails the same
+    /// way without waiting.
+    HostShutdown { reconnectable: bool },
+    /// A [`FrameReader`] received a frame of the fourcc, which is not an
+    /// H.264 or H.265 bitstream.
+    UnsupportedCodec(u32),
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClientError::Io(err) => return Some(err),
+            ClientError::Convert(err) => return Some(err),
+            _ => return None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Timeout => write!(f, "timed out waiting for a frame"),
+            ClientError::Io(err) => write!(f, "failed to receive a frame: {}", err),
+            ClientError::Malformed => write!(f, "received a malformed frame"),
+            ClientError::FramesOutstanding(count) => {
+                write!(f, "{} received frames are still held", count)
+            }
+            ClientError::AuthFailed => write!(f, "the host refused the authentication token"),
+            ClientError::BufferTooSmall { required, len } => write!(
+                f,
+                "buffer of {} bytes is too small, frame requires {} bytes",
+                len, required
+            ),
+            ClientError::Convert(err) => write!(f, "failed to convert a frame: {}", err),
+            ClientError::HostShutdown { reconnectable } => {
+                if *reconnectable {
+                    write!(f, "the host is restarting")
+                } else {
+                    write!(f, "the host has terminated")
+                }
+            }
+            ClientError::UnsupportedCodec(fourcc) => write!(
+                f,
+                "cannot read {} frames as a bitstream",
+                String::from_utf8_lossy(&fourcc.to_le_bytes())
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => return ClientError::Timeout,
+            _ => return ClientError::Io(err),
+        }
+    }
 }
 
 unsafe impl Send for Client {}
 unsafe impl Sync for Client {}
 
-impl Client {
-    pub fn new(path: &str, reconnect: bool) -> Result<Self, Box<dyn Error>> {
+impl Default for ClientOptions {
+    fn default() -> Self {
+        return ClientOptions::new();
+    }
+}
+
+impl ClientOptions {
+    pub fn new() -> Self {
+        return ClientOptions {
+            reconnect: false,
+            decimate: 1,
+            reorder_window: 1,
+            preferred: Vec::new(),
+            auth_token: None,
+            jitter_window: None,
+            prefetch: 0,
+            deliver_as: None,
+            assemble_partial: false,
+            thread_config: ThreadConfig::default(),
+            send_acks: false,
+        };
+    }
+
+    /// Whether the client should reconnect to the host when disconnected.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        return self;
+    }
+
+    /// Only return every nth frame from [`Client::recv`], the other
+    /// frames are released as soon as they are received without being mapped.
+    /// This suits previews which only need a fraction of the host's framerate.
+    ///
+    /// The host is not told to skip frames so each one is still signalled to
+    /// the client.  A value of 0 or 1 returns every frame.
+    pub fn decimate(mut self, n: u32) -> Self {
+        self.decimate = n.max(1);
+        return self;
+    }
+
+    /// Buffers up to `n` received frames and returns them from
+    /// [`Client::recv`] in presentation order, for hosts which interleave
+    /// sources or post frames in decode order.  Frames with equal pts are
+    /// returned in the order they arrived.
+    ///
+    /// Each frame is held until the window is full, adding a latency of `n`
+    /// frames.  When no further frame arrives the held frames are returned in
+    /// order before the error is reported.  A value of 0 or 1 disables
+    /// reordering.
+    pub fn reorder_window(mut self, n: usize) -> Self {
+        self.reorder_window = n.max(1);
+        return self;
+    }
+
+    /// Only return frames in the most preferred of `fourccs`, listed from most
+    /// to least preferred, for hosts which post several representations of
+    /// each frame, such as raw NV12 alongside encoded H.264.
+    ///
+    /// The protocol has no handshake through which the client could tell the
+    /// host its preference, so hosts cannot pick a representation per client.
+    /// Every representation is still signalled to the client and those not
+    /// chosen are released as they are received without being mapped.  Frames
+    /// of every format are returned until the host posts one of the preferred
+    /// formats, so hosts offering none of them fall back to their own format.
+    /// The client switches to a more preferred format as soon as the host
+    /// posts it, and falls back once the host posts an instant, the frames
+    /// sharing a pts, without the chosen format.
+    pub fn prefer_formats(mut self, fourccs: &[FourCC]) -> Self {
+        self.preferred = fourccs.to_vec();
+        return self;
+    }
+
+    /// Measures the jitter of frame arrivals over the last `window` frames,
+    /// reported by [`Client::jitter`].  Every frame which arrives is measured,
+    /// including those skipped by decimation, while heartbeats are not.
+    pub fn measure_jitter(mut self, window: usize) -> Self {
+        self.jitter_window = Some(window);
+        return self;
+    }
+
+    /// Presents the token to hosts created with
+    /// [`crate::host::HostBuilder::auth_token`] when connecting, which refuse
+    /// clients without the same token with [`ClientError::AuthFailed`].
+    pub fn auth_token(mut self, token: &[u8]) -> Self {
+        self.auth_token = Some(token.to_vec());
+        return self;
+    }
+
+    /// Receives up to `depth` frames ahead of the consumer on a dedicated
+    /// thread, so [`Client::recv`] returns a frame which has already
+    /// arrived while the next ones are received.  This hides the socket's
+    /// latency from consumers doing heavy work on each frame.
+    ///
+    /// When the queue is full the oldest frame is dropped, counted as skipped
+    /// by [`Client::stats`], so the queue holds the latest frames.  Queued
+    /// frames stay locked, holding back the host's buffers, so the depth
+    /// should be small.  A depth of 0 disables prefetching, the default.
+    pub fn prefetch(mut self, depth: usize) -> Self {
+        self.prefetch = depth;
+        return self;
+    }
+
+    /// Converts every received frame to `fourcc` before returning it, for
+    /// consumers such as viewers which want RGB whatever the host posts.  The
+    /// fourcc must be one of the packed RGB formats or GREY, otherwise
+    /// connecting fails with [`FrameError::UnsupportedFourcc`].
+    ///
+    /// Frames are converted in software, as [`Frame::convert_to`], into a
+    /// newly allocated frame, and the host's buffer is released once
+    /// converted.  Frames already in the format are returned as received.
+    /// The library has no decoder, so encoded frames, and others which
+    /// cannot be converted, fail with [`ClientError::Convert`] and are
+    /// skipped.
+    pub fn deliver_as(mut self, fourcc: FourCC) -> Self {
+        self.deliver_as = Some(fourcc);
+        return self;
+    }
+
+    /// Reconstructs full frames from the partial updates sent by hosts built
+    /// with [`crate::host::HostBuilder::partial_updates`], merging the dirty
+    /// regions of each update into a copy of the last frame received.  Without
+    /// it partial updates are skipped and only full frames are received.
+    ///
+    /// Frames are assembled into buffers allocated by the client, so each full
+    /// frame is copied as it arrives and each update into a copy of the last.
+    /// The frames returned share the buffer the next is assembled from and
+    /// should not be written.  Updates which do not follow the last frame,
+    /// such as after missing one, are skipped until the host posts a frame in
+    /// full.
+    pub fn assemble_partial(mut self, assemble: bool) -> Self {
+        self.assemble_partial = assemble;
+        return self;
+    }
+
+    /// Configures the threads spawned for the client, by
+    /// [`ClientOptions::prefetch`], [`Client::tee`] and the async stream,
+    /// such as to run them at a real-time priority on dedicated cores.  By
+    /// default they inherit the settings of the thread spawning them.
+    pub fn thread_config(mut self, config: ThreadConfig) -> Self {
+        self.thread_config = config;
+        return self;
+    }
+
+    /// Sends the host an acknowledgement as each returned frame is dropped,
+    /// carrying how long the frame was held, which hosts receive through
+    /// [`crate::host::Host::on_frame_acked`] such as to lower the quality for
+    /// consistently slow clients.
+    ///
+    /// The library's protocol has no message from clients to the host, so
+    /// the acknowledgements are sent over a second socket, the host's path
+    /// with an ".ack" suffix, and identify the client by
+    /// [`Client::ack_id`].  They are best effort: acknowledgements are lost
+    /// while the host is not listening for them or is slow to read them.
+    pub fn send_acks(mut self, send: bool) -> Self {
+        self.send_acks = send;
+        return self;
+    }
+
+    /// Creates the Client and connects to the host at the provided path.
+    pub fn connect(self, path: &str) -> Result<Client, VslError> {
+        crate::check_version_compatibility()?;
+        if let Some(fourcc) = self.deliver_as {
+            if format::channels(fourcc).is_none() && !matches!(fourcc, format::GREY | format::Y8) {
+                return Err(VslError::from(FrameError::UnsupportedFourcc(fourcc)));
+            }
+        }
+        if let Some(token) = &self.auth_token {
+            auth::authenticate(Path::new(path), token)?;
+        }
         let path_str_c = CString::new(path)?;
         let ptr = unsafe {
             ffi::vsl_client_init(
                 path_str_c.as_ptr(),
                 std::ptr::null_mut() as *mut c_void,
-                reconnect,
+                self.reconnect,
             )
         };
         if ptr.is_null() {
             let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+            return Err(VslError::from(err));
         }
 
-        return Ok(Client { ptr });
+        let acks = if self.send_acks {
+            Some(Arc::new(AckSender::new(Path::new(path))?))
+        } else {
+            None
+        };
+        let handle = Arc::new(ClientHandle(ptr));
+        let prefetch = match self.prefetch {
+            0 => None,
+            depth => Some(Prefetcher::spawn(
+                handle.clone(),
+                depth,
+                self.reconnect,
+                &self.thread_config,
+            )?),
+        };
+        return Ok(Client {
+            ptr,
+            handle,
+            closed: false,
+            timeout: Mutex::new(DEFAULT_TIMEOUT),
+            socket_timeout: Mutex::new(DEFAULT_TIMEOUT),
+            prefetch,
+            decimate: self.decimate,
+            reorder_window: self.reorder_window,
+            preferred: self.preferred,
+            deliver_as: self.deliver_as,
+            assemble_partial: self.assemble_partial,
+            thread_config: self.thread_config,
+            acks,
+            state: Mutex::new(ClientState {
+                received: 0,
+                returned: 0,
+                bytes: 0,
+                skipped: 0,
+                last_signal: None,
+                rate: JitterMeter::new(FRAME_RATE_WINDOW - 1),
+                target_fps: None,
+                reorder: VecDeque::with_capacity(self.reorder_window),
+                format: None,
+                pending: None,
+                best_format: None,
+                jitter: self.jitter_window.map(JitterMeter::new),
+                base: None,
+                numbering: FrameNumbering::HostSerial,
+                numbered: 0,
+                terminated: false,
+                metadata: Metadata::default(),
+                deferred: None,
+            }),
+        });
+    }
+
+    /// Connects as [`ClientOptions::connect`] to the host listening on the
+    /// endpoint.
+    pub fn connect_endpoint(self, endpoint: &Endpoint) -> Result<Client, VslError> {
+        let path = match endpoint.path().to_str() {
+            Some(path) => path,
+            None => return Err(io::Error::from(io::ErrorKind::InvalidInput).into()),
+        };
+        return self.connect(path);
     }
 
+    /// Connects as [`ClientOptions::connect`], retrying until the host has
+    /// created its socket and accepts the connection or `timeout` has passed,
+    /// so the client can be started before the host.
+    pub fn connect_wait(self, path: &Path, timeout: Duration) -> Result<Client, VslError> {
+        let path = match path.to_str() {
+            Some(path) => path,
+            None => {
+                return Err(VslError::from(ClientError::Io(
+                    io::ErrorKind::InvalidInput.into(),
+                )))
+            }
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            let err = match self.clone().connect(path) {
+                Ok(client) => return Ok(client),
+                Err(err) => err,
+            };
+            // The socket is missing or not yet listening until the host starts.
+            match &err {
+                VslError::Os(os)
+                    if matches!(
+                        os.kind(),
+                        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+                    ) => {}
+                _ => return Err(err),
+            }
+            if Instant::now() >= deadline {
+                return Err(VslError::from(ClientError::Timeout));
+            }
+            thread::sleep(CONNECT_RETRY);
+        }
+    }
+
+    /// Connects as [`ClientOptions::connect_wait`] and invokes `on_frame`
+    /// with every frame received, on a dedicated thread, until the returned
+    /// [`Subscription`] is dropped.
+    ///
+    /// The subscription survives the loss of the host: the thread connects
+    /// again with the same options and resumes invoking `on_frame` once the
+    /// host is back, notifying [`Subscription::on_reconnect`].  Frames posted
+    /// while disconnected are missed.  A host which terminated through
+    /// [`crate::host::Host::shutdown`] ends the subscription instead.
+    pub fn subscribe<F>(
+        self,
+        path: &Path,
+        timeout: Duration,
+        on_frame: F,
+    ) -> Result<Subscription, VslError>
+    where
+        F: FnMut(Frame) + Send + 'static,
+    {
+        let client = self.clone().connect_wait(path, timeout)?;
+        return subscription::spawn(self, path.to_path_buf(), client, on_frame);
+    }
+}
+
+impl Client {
+    pub fn new(path: &str, reconnect: bool) -> Result<Self, VslError> {
+        return ClientOptions::new().reconnect(reconnect).connect(path);
+    }
+
+    /// Connects to the host at the provided path, waiting up to `timeout` for
+    /// the host to become available, see [`ClientOptions::connect_wait`].
+    pub fn connect_wait(path: &Path, timeout: Duration) -> Result<Self, VslError> {
+        return ClientOptions::new().connect_wait(path, timeout);
+    }
+
+    /// Disconnects from the host, the client is released when dropped.
+    #[deprecated = "the client is released when dropped, use Client::close"]
     pub fn release(&self) {
-        unsafe { ffi::vsl_client_release(self.ptr) }
+        self.disconnect();
     }
 
     pub fn disconnect(&self) {
         unsafe { ffi::vsl_client_disconnect(self.ptr) }
     }
 
+    /// Disconnects from the host and releases the frames held by the client.
+    /// Unlike dropping the client, this reports frames received through the
+    /// client which the caller still holds.  Those frames remain valid and the
+    /// client is released once the last of them is dropped.
+    pub fn close(mut self) -> Result<(), VslError> {
+        return self.shutdown();
+    }
+
+    fn shutdown(&mut self) -> Result<(), VslError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        let state = self.state.get_mut().unwrap();
+        state.reorder.clear();
+        state.pending = None;
+        if let Some(prefetch) = &self.prefetch {
+            prefetch.close();
+        }
+        self.disconnect();
+
+        let outstanding = self.frames_outstanding();
+        if outstanding > 0 {
+            return Err(VslError::from(ClientError::FramesOutstanding(outstanding)));
+        }
+        return Ok(());
+    }
+
     pub fn userptr() {
         panic!("CURRENTLY NOT USED");
     }
 
-    pub fn path(&self) -> Result<&str, ()> {
+    pub fn path(&self) -> Result<&str, VslError> {
         let path_ptr = unsafe { ffi::vsl_client_path(self.ptr) };
-        if !path_ptr.is_null() {
-            let p_cstr = unsafe { CStr::from_ptr(path_ptr) };
-            let path_ref = p_cstr.to_str().unwrap();
-            return Ok(path_ref);
+        if path_ptr.is_null() {
+            return Err(VslError::NullPointer);
         }
-        return Err(());
+        let p_cstr = unsafe { CStr::from_ptr(path_ptr) };
+        return Ok(p_cstr.to_str()?);
     }
 
     pub fn set_timeout(&self, timeout: f32) {
+        *self.timeout.lock().unwrap() = timeout;
+        self.set_socket_timeout(timeout);
+    }
+
+    fn set_socket_timeout(&self, timeout: f32) {
+        *self.socket_timeout.lock().unwrap() = timeout;
         unsafe { ffi::vsl_client_set_timeout(self.ptr, timeout) };
     }
 
-    pub fn get_frame(&self, until: i64) -> Result<Frame, Box<dyn Error>> {
-        let frame = unsafe { ffi::vsl_frame_wait(self.ptr, until) };
-        if frame.is_null() {
-            return Err(Box::new(NullStringError {}));
+    /// Receives up to `max` frames in order, waiting up to `timeout` for the
+    /// first as [`RecvMode::Blocking`] then collecting those which have
+    /// already arrived as [`RecvMode::NonBlocking`].  Fewer than `max` frames
+    /// are returned when no more have arrived, and a timeout is only reported
+    /// when no frame arrived at all.  Any other error after the first frame
+    /// ends the batch and is returned by the next receive.
+    #[deprecated = "use Client::recv, with RecvMode::NonBlocking for the frames which follow"]
+    pub fn get_frames(&self, max: usize, timeout: Duration) -> Result<Vec<Frame>, VslError> {
+        let mut frames = Vec::new();
+        if max == 0 {
+            return Ok(frames);
+        }
+        frames.push(self.recv(RecvMode::Blocking(timeout))?);
+        while frames.len() < max {
+            match self.recv(RecvMode::NonBlocking) {
+                Ok(frame) => frames.push(frame),
+                Err(VslError::Client(ClientError::Timeout)) => break,
+                Err(err) => {
+                    self.defer(err);
+                    break;
+                }
+            }
+        }
+        return Ok(frames);
+    }
+
+    /// Receives a frame as selected by the mode, returning
+    /// [`ClientError::Timeout`] when none arrived in time.  Each mode takes
+    /// frames after decimation, format preference and reordering.
+    ///
+    /// The library has no non-blocking wait, so [`RecvMode::NonBlocking`] and
+    /// [`RecvMode::Latest`] wait a millisecond for a frame, the resolution of
+    /// the library's timeout, rather than polling the socket.  Either way the
+    /// timeout set by [`Client::set_timeout`] is restored after.  An error
+    /// other than a timeout while draining [`RecvMode::Latest`] is returned
+    /// by the next call, after the latest frame received before it.
+    pub fn recv(&self, mode: RecvMode) -> Result<Frame, VslError> {
+        let drain = Duration::from_secs_f32(DRAIN_TIMEOUT);
+        match mode {
+            RecvMode::Blocking(timeout) => {
+                return self.with_timeout(timeout, || self.receive(0));
+            }
+            RecvMode::NonBlocking => return self.with_timeout(drain, || self.receive(0)),
+            RecvMode::Latest => {
+                return self.with_timeout(drain, || {
+                    let mut latest = self.receive(0)?;
+                    loop {
+                        match self.receive(0) {
+                            Ok(frame) => latest = frame,
+                            Err(VslError::Client(ClientError::Timeout)) => return Ok(latest),
+                            // A failure ends the drain, it is reported by the
+                            // next call once the frames before it were
+                            // returned.
+                            Err(err) => {
+                                self.defer(err);
+                                return Ok(latest);
+                            }
+                        }
+                    }
+                });
+            }
         }
-        return Ok(Frame::wrap(frame).unwrap());
+    }
+
+    /// Returns an iterator receiving the client's frames, for consumers which
+    /// process every frame in a loop.  Each call to `next` waits up to the
+    /// timeout set by [`Client::set_timeout`], retrying timeouts, and any other
+    /// error is yielded once before the iterator ends.
+    ///
+    /// The iterator keeps no reference to the frames it yields, so each frame
+    /// is released as soon as the consumer drops it.  A consumer dropping each
+    /// frame before asking for the next holds a single frame of the host at a
+    /// time, leaving the rest free for the host to reuse.
+    pub fn frames(&self) -> Frames<'_> {
+        return Frames {
+            client: self,
+            done: false,
+        };
+    }
+
+    /// Converts the client into a [`FrameReader`] of the bitstream of its
+    /// encoded frames, for piping an H.264 or H.265 stream into a file or
+    /// another process such as with [`io::copy`].
+    pub fn into_reader(self) -> FrameReader {
+        return FrameReader {
+            client: self,
+            pending: Vec::new(),
+            position: 0,
+            done: false,
+        };
+    }
+
+    /// Returns the number of frames received through the client which have
+    /// not been dropped, including those waiting in its reorder window or
+    /// prefetch queue, each keeping a buffer of the host locked.  A count
+    /// which keeps growing shows frames being held longer than intended.
+    pub fn frames_outstanding(&self) -> usize {
+        // The prefetcher holds a reference until its thread has exited.
+        let held = 1 + self.prefetch.is_some() as usize;
+        return Arc::strong_count(&self.handle).saturating_sub(held);
+    }
+
+    /// Waits for the next frame, up to the timeout set by
+    /// [`Client::set_timeout`], skipping frames with a timestamp older than
+    /// `until` when it is not zero.
+    #[deprecated = "use Client::recv with RecvMode::Blocking"]
+    pub fn get_frame(&self, until: i64) -> Result<Frame, VslError> {
+        return self.receive(until);
+    }
+
+    /// Selects how [`Client::get_numbered_frame`] numbers frames, by default
+    /// [`FrameNumbering::HostSerial`].  Recorders which need a unique and
+    /// increasing index, even across hosts which reset their serials, should
+    /// use [`FrameNumbering::ClientMonotonic`].  A client switching back and
+    /// forth continues its own numbering where it left off.
+    pub fn set_frame_numbering(&self, numbering: FrameNumbering) {
+        self.state.lock().unwrap().numbering = numbering;
+    }
+
+    /// Waits for the next frame, up to the timeout set by
+    /// [`Client::set_timeout`] and skipping frames with a timestamp older than
+    /// `until` when it is not zero, returning it along with its number as
+    /// selected by [`Client::set_frame_numbering`].  Frames are numbered by the
+    /// client as this returns them, frames returned by the other receiving
+    /// methods are not counted.
+    pub fn get_numbered_frame(&self, until: i64) -> Result<NumberedFrame, VslError> {
+        let frame = self.receive(until)?;
+        let mut state = self.state.lock().unwrap();
+        let number = match state.numbering {
+            FrameNumbering::HostSerial => frame.serial().max(0) as u64,
+            FrameNumbering::ClientMonotonic => {
+                state.numbered += 1;
+                state.numbered - 1
+            }
+        };
+        return Ok(NumberedFrame { number, frame });
+    }
+
+    /// Waits up to `timeout` for the next frame and copies its pixels into
+    /// `dst`, such as a slot of the caller's own ring buffer, returning only
+    /// its description.  The planes are copied one after another without row
+    /// padding, as [`Frame::to_vec`], and the host's buffer is released as
+    /// soon as the copy is made.
+    ///
+    /// Returns [`ClientError::BufferTooSmall`] when `dst` cannot hold the
+    /// frame, which is then skipped.
+    #[deprecated = "use Client::recv with RecvMode::Blocking and Frame::copy_to_slice"]
+    pub fn get_frame_into(&self, dst: &mut [u8], timeout: Duration) -> Result<FrameMeta, VslError> {
+        let frame = self.recv(RecvMode::Blocking(timeout))?;
+        let len = match frame.copy_to_slice(dst) {
+            Ok(len) => len,
+            Err(VslError::Frame(FrameError::BufferTooSmall { required, len })) => {
+                return Err(VslError::from(ClientError::BufferTooSmall {
+                    required,
+                    len,
+                }))
+            }
+            Err(VslError::Frame(FrameError::Io(err))) => {
+                return Err(VslError::from(ClientError::Io(err)))
+            }
+            Err(_) => return Err(VslError::from(ClientError::Malformed)),
+        };
+        let format = frame.format();
+        return Ok(FrameMeta {
+            width: format.width,
+            height: format.height,
+            fourcc: format.fourcc,
+            len,
+            serial: frame.serial(),
+            timestamp: frame.timestamp(),
+            duration: frame.duration(),
+            pts: frame.pts(),
+            dts: frame.dts(),
+        });
+    }
+
+    /// Runs `f` with the socket timeout set to `timeout`, restoring the
+    /// timeout configured by [`Client::set_timeout`] after.
+    fn with_timeout<T>(&self, timeout: Duration, f: impl FnOnce() -> T) -> T {
+        let configured = *self.timeout.lock().unwrap();
+        self.set_socket_timeout(timeout.as_secs_f32());
+        let result = f();
+        self.set_socket_timeout(configured);
+        return result;
+    }
+
+    /// Converts the client into a [`FrameStream`] of received frames, for use
+    /// from async code.
+    #[cfg(feature = "async")]
+    pub fn into_stream(self) -> FrameStream {
+        return FrameStream::new(self);
+    }
+
+    /// Converts the client into `count` [`FrameReceiver`]s which each receive
+    /// every frame, for consumers such as a recorder and a display which run
+    /// at their own pace without each connecting to the host.
+    ///
+    /// The frames are received once, on a dedicated thread which owns the
+    /// client, and each branch gets its own frame sharing the received
+    /// frame's buffer, so none is copied.  How a branch handles frames its
+    /// consumer has yet to receive is set with [`FrameReceiver::set_policy`].
+    pub fn tee(self, count: usize) -> Vec<FrameReceiver> {
+        return tee::spawn(self, count);
+    }
+
+    /// Returns the configuration of the client's threads, see
+    /// [`ClientOptions::thread_config`].
+    pub fn thread_config(&self) -> &ThreadConfig {
+        return &self.thread_config;
+    }
+
+    /// Returns the id identifying the client's acknowledgements to the host,
+    /// or None unless enabled by [`ClientOptions::send_acks`].
+    pub fn ack_id(&self) -> Option<u64> {
+        return self.acks.as_ref().map(|acks| acks.id());
+    }
+
+    /// Waits up to `timeout` for the next event on the stream, so a single
+    /// loop can handle frames, heartbeats, format changes and the loss of the
+    /// host.  Frames are received as [`Client::recv`], which remains the
+    /// simpler choice for consumers only interested in frames.
+    ///
+    /// A [`ClientEvent::FormatChanged`] is reported ahead of a frame whose
+    /// format differs from the previous frame.  The library describes the
+    /// geometry and fourcc of each frame as it is posted, so the frame
+    /// following the event has the new format and renderers can resize their
+    /// buffers before drawing it.  Returns [`ClientError::Timeout`] when
+    /// nothing arrived within `timeout`.
+    pub fn next_event(&self, timeout: Duration) -> Result<ClientEvent, VslError> {
+        let frame = match self.with_timeout(timeout, || self.recv_signal(0, true)) {
+            Ok(Signal::Frame(frame)) => frame,
+            Ok(Signal::Heartbeat) => return Ok(ClientEvent::Heartbeat),
+            Ok(Signal::Probe(probe)) => return Ok(ClientEvent::Probe(probe)),
+            Err(VslError::Client(ClientError::Io(err))) if is_disconnect(&err) => {
+                return Ok(ClientEvent::Disconnected)
+            }
+            Err(err) => return Err(err),
+        };
+        let format = frame.format();
+        let mut state = self.state.lock().unwrap();
+        if state.format != Some(format) {
+            state.format = Some(format);
+            state.pending = Some(frame);
+            return Ok(ClientEvent::FormatChanged(format));
+        }
+        return Ok(ClientEvent::Frame(frame));
+    }
+
+    /// Keeps an error which ended a batch or drain, so the next receive
+    /// returns it once the frames received before it were returned.
+    fn defer(&self, err: VslError) {
+        self.state.lock().unwrap().deferred = Some(err);
+    }
+
+    pub(crate) fn receive(&self, until: i64) -> Result<Frame, VslError> {
+        if let Some(err) = self.state.lock().unwrap().deferred.take() {
+            return Err(err);
+        }
+        loop {
+            if let Signal::Frame(frame) = self.recv_signal(until, false)? {
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Receives the next frame, through the reorder window, or a heartbeat or
+    /// probe when `signals` is set.
+    fn recv_signal(&self, until: i64, signals: bool) -> Result<Signal, VslError> {
+        if let Some(frame) = self.state.lock().unwrap().pending.take() {
+            return Ok(Signal::Frame(frame));
+        }
+        loop {
+            if self.state.lock().unwrap().reorder.len() >= self.reorder_window {
+                break;
+            }
+            let result = self.wait(until, signals);
+            let mut state = self.state.lock().unwrap();
+            match result {
+                Ok(Signal::Frame(frame)) => state.reorder.push_back(frame),
+                Ok(signal) => return Ok(signal),
+                Err(_) if !state.reorder.is_empty() => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // min_by_key keeps the first of equal frames, preserving arrival order.
+        let (index, _) = state
+            .reorder
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, frame)| frame.pts())
+            .unwrap();
+        let frame = state.reorder.remove(index).unwrap();
+
+        state.rate.record(frame.timestamp());
+        state.returned += 1;
+        state.bytes += frame.buffer_size() as u64;
+        let skipped = self.frames_skipped(&state);
+        drop(state);
+        let mut frame = match self.deliver_as {
+            Some(fourcc) if frame.fourcc() != fourcc => match frame.convert_into(fourcc) {
+                Ok(converted) => converted,
+                Err(VslError::Frame(err)) => return Err(VslError::from(ClientError::Convert(err))),
+                Err(err) => return Err(err),
+            },
+            _ => frame,
+        };
+        if let Some(acks) = &self.acks {
+            let ack = PendingAck::new(acks.clone(), frame.serial(), skipped);
+            frame.set_ack(ack);
+        }
+        return Ok(Signal::Frame(frame));
+    }
+
+    /// Waits for the next frame which is not skipped by decimation, or the
+    /// next heartbeat or probe when `signals` is set.
+    fn wait(&self, until: i64, signals: bool) -> Result<Signal, VslError> {
+        loop {
+            // A terminated host will not come back, so its socket is not
+            // waited on again.
+            if self.state.lock().unwrap().terminated {
+                return Err(VslError::from(ClientError::HostShutdown {
+                    reconnectable: false,
+                }));
+            }
+            let Prefetched {
+                frame,
+                timestamp,
+                instant,
+            } = self.next_frame(until).map_err(ClientError::from)?;
+            let mut state = self.state.lock().unwrap();
+            state.last_signal = Some(instant);
+            if frame.fourcc() == crate::HEARTBEAT_FOURCC {
+                if signals {
+                    return Ok(Signal::Heartbeat);
+                }
+                continue;
+            }
+            if frame.fourcc() == crate::GOODBYE_FOURCC {
+                let reconnectable = match frame.mmap() {
+                    Ok(mem) => mem.first() != Some(&crate::GOODBYE_TERMINATE),
+                    Err(_) => return Err(VslError::from(ClientError::Malformed)),
+                };
+                state.terminated = !reconnectable;
+                return Err(VslError::from(ClientError::HostShutdown { reconnectable }));
+            }
+            if frame.fourcc() == crate::RATE_FOURCC {
+                state.target_fps = Some(decode_rate(&frame)?);
+                continue;
+            }
+            if frame.fourcc() == crate::METADATA_FOURCC {
+                state.metadata = decode_metadata(&frame)?;
+                continue;
+            }
+            if frame.fourcc() == crate::PROBE_FOURCC {
+                if signals {
+                    return Ok(Signal::Probe(decode_probe(&frame)?));
+                }
+                continue;
+            }
+            let mut frame = if frame.fourcc() == crate::PARTIAL_FOURCC {
+                match self.assemble(&mut state, &frame) {
+                    Some(assembled) => assembled,
+                    None => {
+                        state.skipped += 1;
+                        continue;
+                    }
+                }
+            } else {
+                if !frame_fits(&frame) {
+                    return Err(VslError::from(ClientError::Malformed));
+                }
+                if self.assemble_partial {
+                    let serial = frame.serial();
+                    state.base = frame
+                        .alloc_copy(None)
+                        .ok()
+                        .map(|base| return (serial, base));
+                }
+                frame
+            };
+            frame.set_metadata(&state.metadata);
+            if !self.accepts_format(&mut state, frame.fourcc(), frame.pts()) {
+                state.skipped += 1;
+                continue;
+            }
+            if let Some(jitter) = &mut state.jitter {
+                jitter.record(timestamp);
+            }
+
+            state.received += 1;
+            if state.received.is_multiple_of(self.decimate as u64) {
+                return Ok(Signal::Frame(frame));
+            }
+            state.skipped += 1;
+        }
+    }
+
+    /// Merges a partial update into the last frame, returning the assembled
+    /// frame, or None when the update cannot be assembled.  An update of
+    /// another frame than the last, which was missed, cannot be assembled nor
+    /// can the updates which follow until a frame is posted in full.
+    fn assemble(&self, state: &mut ClientState, update: &Frame) -> Option<Frame> {
+        let (serial, base) = state.base.take()?;
+        if !self.assemble_partial || partial::base_serial(update) != Some(serial) {
+            return None;
+        }
+        let assembled = partial::apply(&base, update).ok()?;
+        state.base = assembled
+            .share()
+            .ok()
+            .map(|share| return (update.serial(), share));
+        return Some(assembled);
+    }
+
+    /// Waits for the next frame from the library, or from the prefetched
+    /// frames when enabled by [`ClientOptions::prefetch`].
+    ///
+    /// The library's wait may return before its timeout without a frame, such
+    /// as when interrupted by a signal or woken by a message which did not
+    /// complete a frame, reported as EINTR and EAGAIN.  Those waits are
+    /// retried for the time remaining until the deadline of the first, so
+    /// such wakeups neither end the wait early nor extend it.  A timeout
+    /// reported by the library is genuine and ends the wait, even before the
+    /// deadline.
+    fn next_frame(&self, until: i64) -> io::Result<Prefetched> {
+        let configured = *self.socket_timeout.lock().unwrap();
+        let timeout = Duration::from_secs_f32(configured.max(0.0));
+        if let Some(prefetch) = &self.prefetch {
+            return prefetch.pop(until, timeout);
+        }
+        let deadline = Instant::now() + timeout;
+        let mut retried = false;
+        let result = loop {
+            let frame = unsafe { ffi::vsl_frame_wait(self.ptr, until) };
+            if !frame.is_null() {
+                break Ok(frame);
+            }
+            let err = io::Error::last_os_error();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let spurious = matches!(
+                err.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+            );
+            if !spurious || remaining < WAIT_GRANULARITY {
+                break Err(err);
+            }
+            unsafe { ffi::vsl_client_set_timeout(self.ptr, remaining.as_secs_f32()) };
+            retried = true;
+        };
+        if retried {
+            unsafe { ffi::vsl_client_set_timeout(self.ptr, configured) };
+        }
+        return Ok(Prefetched {
+            frame: Frame::from_client(result?, self.handle.clone()),
+            timestamp: crate::timestamp(),
+            instant: Instant::now(),
+        });
+    }
+
+    /// Returns true if frames of the format should be returned, being the best
+    /// of the preferred formats the host is posting, or any format while the
+    /// host posts none of them.  The best format is forgotten once the host
+    /// posts a whole instant without it, an instant being the frames sharing a
+    /// pts.
+    fn accepts_format(&self, state: &mut ClientState, fourcc: FourCC, pts: i64) -> bool {
+        if self.preferred.is_empty() {
+            return true;
+        }
+        let rank = self
+            .preferred
+            .iter()
+            .position(|&preferred| preferred == fourcc);
+        if let Some(best) = &mut state.best_format {
+            if rank == Some(best.rank) {
+                best.pts = pts;
+                best.missed = None;
+                return true;
+            }
+            if rank.is_none_or(|rank| rank > best.rank) {
+                if pts <= best.pts {
+                    return false;
+                }
+                // Frames of a later instant than the first without the best
+                // format show it is no longer posted.
+                let missed = *best.missed.get_or_insert(pts);
+                if pts <= missed {
+                    return false;
+                }
+            }
+        }
+        state.best_format = rank.map(|rank| {
+            return BestFormat {
+                rank,
+                pts,
+                missed: None,
+            };
+        });
+        return true;
+    }
+
+    /// Returns the jitter of frame arrivals, when enabled by
+    /// [`ClientOptions::measure_jitter`], once two frames have arrived.
+    pub fn jitter(&self) -> Option<JitterStats> {
+        let state = self.state.lock().unwrap();
+        return state.jitter.as_ref()?.stats();
+    }
+
+    /// Returns a snapshot of the client's reception.  Frames the library
+    /// discards before returning them, such as those already expired, are not
+    /// seen by the client and so are not counted.
+    pub fn stats(&self) -> ClientStats {
+        let state = self.state.lock().unwrap();
+        return ClientStats {
+            frames_received: state.returned,
+            bytes_received: state.bytes,
+            frames_skipped: self.frames_skipped(&state),
+        };
+    }
+
+    /// Returns the frames skipped by the client or dropped by its prefetch
+    /// queue, see [`ClientStats::frames_skipped`].
+    fn frames_skipped(&self, state: &ClientState) -> u64 {
+        let dropped = self
+            .prefetch
+            .as_ref()
+            .map_or(0, |prefetch| prefetch.dropped());
+        return state.skipped + dropped;
+    }
+
+    /// Returns the time since the host last signalled the client with either a
+    /// frame or a heartbeat, see [`crate::host::Host::send_heartbeat`].
+    /// Signals are only seen while waiting for frames and None is returned
+    /// until the first one.
+    pub fn time_since_last_signal(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        return state.last_signal.map(|last| last.elapsed());
+    }
+
+    /// Returns the framerate of the frames returned by [`Client::recv`],
+    /// after decimation, measured from their timestamps over the most recent
+    /// frames.  Returns None until at least two frames have been received.
+    /// A rate falling below [`Client::target_fps`] shows the client is not
+    /// keeping up with the host, or is decimating its frames.
+    pub fn measured_fps(&self) -> Option<f64> {
+        let stats = self.state.lock().unwrap().rate.stats()?;
+        if stats.mean_interval.is_zero() {
+            return None;
+        }
+        return Some(1.0 / stats.mean_interval.as_secs_f64());
+    }
+
+    #[deprecated = "renamed to Client::measured_fps"]
+    pub fn frame_rate(&self) -> Option<f64> {
+        return self.measured_fps();
+    }
+
+    /// Returns the framerate the host intends to post at, set by
+    /// [`crate::host::HostBuilder::frame_rate`].  The host sends it as the
+    /// client connects, and like other signals it is seen while waiting for
+    /// frames, so None is returned until the first receive or when the host
+    /// did not set one.
+    pub fn target_fps(&self) -> Option<f64> {
+        return self.state.lock().unwrap().target_fps;
+    }
+}
+
+/// Returns true if the error reports the connection to the host was lost.
+fn is_disconnect(err: &io::Error) -> bool {
+    return matches!(
+        err.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::UnexpectedEof
+    );
+}
+
+/// Decodes the framerate carried by a frame of [`crate::RATE_FOURCC`].
+fn decode_rate(frame: &Frame) -> Result<f64, VslError> {
+    let mem = frame.mmap().map_err(|_| ClientError::Malformed)?;
+    let size = frame.buffer_size().min(mem.len());
+    if size < crate::RATE_SIZE {
+        return Err(VslError::from(ClientError::Malformed));
+    }
+    let fps = f64::from_le_bytes(mem[..crate::RATE_SIZE].try_into().unwrap());
+    if !(fps > 0.0 && fps.is_finite()) {
+        return Err(VslError::from(ClientError::Malformed));
+    }
+    return Ok(fps);
+}
+
+/// Decodes the metadata carried by a frame of [`crate::METADATA_FOURCC`].
+fn decode_metadata(frame: &Frame) -> Result<Metadata, VslError> {
+    let mem = frame.mmap().map_err(|_| ClientError::Malformed)?;
+    let size = frame.buffer_size().min(mem.len());
+    return Metadata::decode(&mem[..size]).ok_or(VslError::from(ClientError::Malformed));
+}
+
+/// Decodes the probe carried by a frame of [`crate::PROBE_FOURCC`].
+fn decode_probe(frame: &Frame) -> Result<Frame, VslError> {
+    let mem = frame.mmap().map_err(|_| ClientError::Malformed)?;
+    let size = frame.buffer_size().min(mem.len());
+    if size < crate::PROBE_SIZE {
+        return Err(VslError::from(ClientError::Malformed));
+    }
+    let field = |index: usize| {
+        let bytes = mem[index * 4..index * 4 + 4].try_into().unwrap();
+        return u32::from_le_bytes(bytes);
+    };
+    match Frame::init_probe(field(0), field(1), field(2)) {
+        Ok(probe) => return Ok(probe),
+        Err(_) => return Err(VslError::from(ClientError::Malformed)),
+    }
+}
+
+/// Checks that the layout the host described for a received frame fits within
+/// its buffer, so the frame helpers never read beyond the mapping.
+fn frame_fits(frame: &Frame) -> bool {
+    return format::layout_fits(
+        frame.fourcc(),
+        frame.width().max(0) as u32,
+        frame.height().max(0) as u32,
+        frame.stride(),
+        frame.buffer_size(),
+    );
+}
+
+/// The iterator of a client's frames, see [`Client::frames`].
+pub struct Frames<'a> {
+    client: &'a Client,
+    done: bool,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<Frame, VslError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.client.receive(0) {
+                Ok(frame) => return Some(Ok(frame)),
+                Err(VslError::Client(ClientError::Timeout)) => continue,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        return None;
+    }
+}
+
+/// The FrameReader reads the frames received by a [`Client`] as a single
+/// Annex B elementary stream, created by [`Client::into_reader`].  Each NAL
+/// unit of each frame is preceded by a four byte start code and the padding
+/// of the frame's buffer is dropped.
+///
+/// Timeouts are not reported, the reader keeps waiting for the next frame.
+/// The stream ends once the host goes away, and a frame which is not H.264
+/// or H.265, including raw frames, fails the read with
+/// [`io::ErrorKind::InvalidData`] holding [`ClientError::UnsupportedCodec`].
+pub struct FrameReader {
+    client: Client,
+    pending: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl FrameReader {
+    /// Receives the next frame into the pending bytes, returning false once
+    /// the stream has ended.
+    fn fill(&mut self) -> io::Result<bool> {
+        loop {
+            let frame = match self.client.receive(0) {
+                Ok(frame) => frame,
+                Err(VslError::Client(ClientError::Timeout)) => continue,
+                Err(VslError::Client(ClientError::Io(err))) if is_disconnect(&err) => {
+                    return Ok(false)
+                }
+                Err(VslError::Client(ClientError::HostShutdown { .. })) => return Ok(false),
+                Err(VslError::Client(ClientError::Io(err))) => return Err(err),
+                Err(err) => return Err(io::Error::other(err)),
+            };
+            let fourcc = frame.fourcc();
+            if !matches!(&fourcc.to_le_bytes(), b"H264" | b"H265" | b"HEVC") {
+                let err = ClientError::UnsupportedCodec(fourcc);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            let size = frame.buffer_size();
+            let mem = frame.mmap().map_err(io::Error::other)?;
+            self.pending.clear();
+            self.position = 0;
+            for nal in encoder::nal_units(mem.get(..size).unwrap_or(mem)) {
+                self.pending.extend_from_slice(&encoder::START_CODE);
+                self.pending.extend_from_slice(nal);
+            }
+            if !self.pending.is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl io::Read for FrameReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.position == self.pending.len() {
+            if self.done || !self.fill()? {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.pending.len() - self.position);
+        buf[..len].copy_from_slice(&self.pending[self.position..self.position + len]);
+        self.position += len;
+        return Ok(len);
     }
 }
 
 impl Drop for Client {
     fn drop(&mut self) {
-        self.release();
-        self.disconnect();
+        let _ = self.shutdown();
     }
 }
@@ -0,0 +1,228 @@
+/// An opaque color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        return Rgb { r, g, b };
+    }
+}
+
+/// A color with straight, not premultiplied, alpha where 255 is opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        return Rgba { r, g, b, a };
+    }
+}
+
+impl From<Rgb> for Rgba {
+    fn from(color: Rgb) -> Self {
+        return Rgba::new(color.r, color.g, color.b, 255);
+    }
+}
+
+/// The YUV color encoding, which determines the matrix converting between YUV
+/// and RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// ITU-R BT.601, used by standard definition video.
+    Bt601,
+    /// ITU-R BT.709, used by high definition video.
+    Bt709,
+    /// ITU-R BT.2020, used by ultra high definition and HDR video.
+    Bt2020,
+}
+
+impl ColorSpace {
+    /// Returns the red and blue luma coefficients, Kr and Kb.
+    pub fn coefficients(&self) -> (f32, f32) {
+        match self {
+            ColorSpace::Bt601 => return (0.299, 0.114),
+            ColorSpace::Bt709 => return (0.2126, 0.0722),
+            ColorSpace::Bt2020 => return (0.2627, 0.0593),
+        }
+    }
+}
+
+/// The range of the YUV values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorRange {
+    /// Luma spans 16 to 235 and chroma 16 to 240, as used by most video.
+    Limited,
+    /// Luma and chroma span the full 0 to 255, as used by JPEG.
+    Full,
+}
+
+/// The color volume of the display an HDR video was mastered on, as defined by
+/// SMPTE ST 2086.  Chromaticities are in units of 0.00002 and luminances in
+/// units of 0.0001 candelas per square metre, as carried by the HEVC and AVC
+/// mastering display colour volume SEI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    /// The x and y chromaticities of the red, green and blue primaries.
+    pub primaries: [(u16, u16); 3],
+    /// The x and y chromaticity of the white point.
+    pub white_point: (u16, u16),
+    pub max_luminance: u32,
+    pub min_luminance: u32,
+}
+
+/// Bytes of the mastering display colour volume SEI payload.
+const MASTERING_DISPLAY_LEN: usize = 24;
+
+/// Bytes of the content light level information SEI payload.
+const CONTENT_LIGHT_LEN: usize = 4;
+
+/// The static HDR metadata of a frame, needed for tone mapping HDR10 content
+/// at the display, set by [`crate::frame::Frame::set_hdr_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HdrMetadata {
+    pub mastering_display: MasteringDisplay,
+    /// The maximum content light level, MaxCLL, in candelas per square metre.
+    pub max_cll: u16,
+    /// The maximum frame average light level, MaxFALL, in candelas per
+    /// square metre.
+    pub max_fall: u16,
+}
+
+impl HdrMetadata {
+    /// Returns the mastering display colour volume SEI payload, big endian
+    /// with the primaries ordered green, blue then red as the SEI has them.
+    pub fn mastering_display_sei(&self) -> [u8; MASTERING_DISPLAY_LEN] {
+        let display = &self.mastering_display;
+        let mut sei = [0u8; MASTERING_DISPLAY_LEN];
+        for (index, &primary) in [1, 2, 0].iter().enumerate() {
+            let (x, y) = display.primaries[primary];
+            sei[index * 4..index * 4 + 2].copy_from_slice(&x.to_be_bytes());
+            sei[index * 4 + 2..index * 4 + 4].copy_from_slice(&y.to_be_bytes());
+        }
+        sei[12..14].copy_from_slice(&display.white_point.0.to_be_bytes());
+        sei[14..16].copy_from_slice(&display.white_point.1.to_be_bytes());
+        sei[16..20].copy_from_slice(&display.max_luminance.to_be_bytes());
+        sei[20..24].copy_from_slice(&display.min_luminance.to_be_bytes());
+        return sei;
+    }
+
+    /// Returns the content light level information SEI payload.
+    pub fn content_light_level_sei(&self) -> [u8; CONTENT_LIGHT_LEN] {
+        let mut sei = [0u8; CONTENT_LIGHT_LEN];
+        sei[..2].copy_from_slice(&self.max_cll.to_be_bytes());
+        sei[2..].copy_from_slice(&self.max_fall.to_be_bytes());
+        return sei;
+    }
+
+    /// Returns the compact serialization of the metadata, the mastering
+    /// display SEI payload followed by the content light level payload.
+    pub fn to_bytes(&self) -> [u8; MASTERING_DISPLAY_LEN + CONTENT_LIGHT_LEN] {
+        let mut bytes = [0u8; MASTERING_DISPLAY_LEN + CONTENT_LIGHT_LEN];
+        bytes[..MASTERING_DISPLAY_LEN].copy_from_slice(&self.mastering_display_sei());
+        bytes[MASTERING_DISPLAY_LEN..].copy_from_slice(&self.content_light_level_sei());
+        return bytes;
+    }
+
+    /// Parses the serialization of [`HdrMetadata::to_bytes`], returning None
+    /// if `bytes` has another length.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != MASTERING_DISPLAY_LEN + CONTENT_LIGHT_LEN {
+            return None;
+        }
+        let u16_at = |offset: usize| {
+            return u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+        };
+        let u32_at = |offset: usize| {
+            return u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]);
+        };
+        let mut primaries = [(0, 0); 3];
+        for (index, &primary) in [1, 2, 0].iter().enumerate() {
+            primaries[primary] = (u16_at(index * 4), u16_at(index * 4 + 2));
+        }
+        return Some(HdrMetadata {
+            mastering_display: MasteringDisplay {
+                primaries,
+                white_point: (u16_at(12), u16_at(14)),
+                max_luminance: u32_at(16),
+                min_luminance: u32_at(20),
+            },
+            max_cll: u16_at(24),
+            max_fall: u16_at(26),
+        });
+    }
+}
+
+/// Returns the luma of the color in the color space, over the full range.
+pub(crate) fn luma(space: ColorSpace, color: Rgba) -> u8 {
+    let (kr, kb) = space.coefficients();
+    let kg = 1.0 - kr - kb;
+    let y = kr * color.r as f32 + kg * color.g as f32 + kb * color.b as f32;
+    return y.round().clamp(0.0, 255.0) as u8;
+}
+
+/// Fractional bits of the fixed point [`YuvMatrix`] coefficients.
+pub(crate) const YUV_SHIFT: i32 = 14;
+
+/// The matrix converting YUV samples of a color space and range to RGB, in
+/// fixed point so the scalar and SIMD conversions produce identical results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct YuvMatrix {
+    /// The luma value of black.
+    pub y_offset: i32,
+    /// The scale of luma above black.
+    pub y: i32,
+    /// The contribution of Cr to red.
+    pub rv: i32,
+    /// The contributions of Cb and Cr subtracted from green.
+    pub gu: i32,
+    pub gv: i32,
+    /// The contribution of Cb to blue.
+    pub bu: i32,
+}
+
+impl YuvMatrix {
+    pub fn new(space: ColorSpace, range: ColorRange) -> Self {
+        let (y_offset, y_scale, c_scale) = match range {
+            ColorRange::Limited => (16, 255.0 / 219.0, 255.0 / 224.0),
+            ColorRange::Full => (0, 1.0, 1.0),
+        };
+        let (kr, kb) = space.coefficients();
+        let kg = 1.0 - kr - kb;
+        let fixed = |c: f32| (c * (1 << YUV_SHIFT) as f32).round() as i32;
+        return YuvMatrix {
+            y_offset,
+            y: fixed(y_scale),
+            rv: fixed(c_scale * 2.0 * (1.0 - kr)),
+            gu: fixed(c_scale * 2.0 * kb * (1.0 - kb) / kg),
+            gv: fixed(c_scale * 2.0 * kr * (1.0 - kr) / kg),
+            bu: fixed(c_scale * 2.0 * (1.0 - kb)),
+        };
+    }
+
+    /// Converts a YUV sample to RGB.
+    pub fn convert(&self, y: u8, u: u8, v: u8) -> Rgba {
+        let luma = (y as i32 - self.y_offset) * self.y + (1 << (YUV_SHIFT - 1));
+        let (u, v) = (u as i32 - 128, v as i32 - 128);
+        let channel = |c: i32| (c >> YUV_SHIFT).clamp(0, 255) as u8;
+        return Rgba::new(
+            channel(luma + self.rv * v),
+            channel(luma - self.gu * u - self.gv * v),
+            channel(luma + self.bu * u),
+            255,
+        );
+    }
+}
@@ -1,15 +1,209 @@
-use crate::{frame, NullStringError};
-use std::{error::Error, os::raw::c_int};
+use crate::{color::HdrMetadata, frame, VslError};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt, io,
+    os::raw::c_int,
+};
 use videostream_sys as ffi;
 
+/// Errors reported by the encoder.
+#[derive(Debug)]
+pub enum EncoderError {
+    /// The encoder could not be created, usually because no hardware encoder
+    /// is available on this platform.
+    Unavailable,
+    /// The underlying library reported an OS error.
+    Io(io::Error),
+    /// The crop region is empty or extends beyond the source frame, whose
+    /// width and height are given.
+    InvalidCrop {
+        rect: VSLRect,
+        source_size: (c_int, c_int),
+    },
+    /// No frame carrying the codec's parameter sets has been encoded yet, see
+    /// [`Encoder::codec_config`].
+    NoCodecConfig,
+    /// The encoder cannot drain the frames it holds, see [`Encoder::flush`].
+    FlushUnsupported,
+}
+
+impl Error for EncoderError {}
+
+impl fmt::Display for EncoderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncoderError::Unavailable => write!(f, "encoder is unavailable"),
+            EncoderError::Io(err) => write!(f, "encoder error: {}", err),
+            EncoderError::InvalidCrop { rect, source_size } => write!(
+                f,
+                "crop {}x{} at {},{} does not fit the {}x{} source",
+                rect.get_width(),
+                rect.get_height(),
+                rect.get_x(),
+                rect.get_y(),
+                source_size.0,
+                source_size.1
+            ),
+            EncoderError::NoCodecConfig => {
+                write!(f, "no codec configuration has been encoded yet")
+            }
+            EncoderError::FlushUnsupported => write!(f, "the encoder cannot be flushed"),
+        }
+    }
+}
+
+impl From<io::Error> for EncoderError {
+    fn from(err: io::Error) -> Self {
+        return EncoderError::Io(err);
+    }
+}
+
 pub struct Encoder {
-    ptr: *mut ffi::VSLEncoder,
+    ptr: Cell<*mut ffi::VSLEncoder>,
+    profile: u32,
+    output_fourcc: u32,
+    fps: c_int,
+    keyframe_requested: Cell<bool>,
+    thumbnail: Option<(c_int, c_int)>,
+    codec_config: RefCell<Option<Vec<u8>>>,
+}
+
+/// The start code preceding each NAL unit of an Annex B bitstream.
+pub(crate) const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Returns the parameter sets within the Annex B `bitstream` of the fourcc,
+/// the SPS and PPS of H.264 or the VPS, SPS and PPS of H.265, each preceded
+/// by a four byte start code in the order they appear.  Other codecs and
+/// bitstreams without parameter sets return an empty list.
+pub fn extract_parameter_sets(fourcc: u32, bitstream: &[u8]) -> Vec<u8> {
+    let is_parameter_set = match &fourcc.to_le_bytes() {
+        b"H264" => |nal: &[u8]| return matches!(nal[0] & 0x1f, 7 | 8),
+        b"H265" | b"HEVC" => |nal: &[u8]| return matches!((nal[0] >> 1) & 0x3f, 32..=34),
+        _ => return Vec::new(),
+    };
+
+    let mut sets = Vec::new();
+    for nal in nal_units(bitstream) {
+        if is_parameter_set(nal) {
+            sets.extend_from_slice(&START_CODE);
+            sets.extend_from_slice(nal);
+        }
+    }
+    return sets;
+}
+
+/// The SEI payload types of the mastering display colour volume and the
+/// content light level information, shared by H.264 and H.265.
+const MASTERING_DISPLAY_SEI: u8 = 137;
+const CONTENT_LIGHT_LEVEL_SEI: u8 = 144;
+
+/// Returns the Annex B `bitstream` of the fourcc with an SEI NAL unit holding
+/// the mastering display colour volume and content light level of `meta`
+/// inserted ahead of its first slice, as HDR10 expects with each keyframe.
+/// Only H.264 and H.265 are supported, other bitstreams and those without a
+/// slice are returned unchanged.
+pub fn insert_hdr_sei(fourcc: u32, bitstream: &[u8], meta: &HdrMetadata) -> Vec<u8> {
+    let is_slice = match &fourcc.to_le_bytes() {
+        b"H264" => |nal: &[u8]| return matches!(nal[0] & 0x1f, 1..=5),
+        b"H265" | b"HEVC" => |nal: &[u8]| return (nal[0] >> 1) & 0x3f < 32,
+        _ => return bitstream.to_vec(),
+    };
+    // The NAL unit header of an H.264 SEI or an H.265 prefix SEI.
+    let header: &[u8] = match &fourcc.to_le_bytes() {
+        b"H264" => &[0x06],
+        _ => &[39 << 1, 1],
+    };
+    let slice = match nal_units(bitstream).into_iter().find(|nal| is_slice(nal)) {
+        Some(slice) => slice,
+        None => return bitstream.to_vec(),
+    };
+
+    // The SEI goes before the slice's start code, including the leading zero
+    // of its four byte form.
+    let mut start = slice.as_ptr() as usize - bitstream.as_ptr() as usize - 3;
+    while start > 0 && bitstream[start - 1] == 0 {
+        start -= 1;
+    }
+
+    let mut rbsp = vec![MASTERING_DISPLAY_SEI, 24];
+    rbsp.extend_from_slice(&meta.mastering_display_sei());
+    rbsp.extend_from_slice(&[CONTENT_LIGHT_LEVEL_SEI, 4]);
+    rbsp.extend_from_slice(&meta.content_light_level_sei());
+    rbsp.push(0x80);
+
+    let mut output = Vec::with_capacity(bitstream.len() + rbsp.len() + 16);
+    output.extend_from_slice(&bitstream[..start]);
+    output.extend_from_slice(&START_CODE);
+    output.extend_from_slice(header);
+    // Emulation prevention keeps the payload from forming a start code.
+    let mut zeros = 0;
+    for byte in rbsp {
+        if zeros >= 2 && byte <= 3 {
+            output.push(3);
+            zeros = 0;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        output.push(byte);
+    }
+    output.extend_from_slice(&bitstream[start..]);
+    return output;
+}
+
+/// Returns the NAL units of the Annex B `bitstream`, without their start
+/// codes and trailing zeros.
+pub(crate) fn nal_units(bitstream: &[u8]) -> Vec<&[u8]> {
+    // Each NAL unit follows a three byte start code, the leading zero of the
+    // four byte form being trailing zeros of the previous unit.
+    let mut starts = Vec::new();
+    let mut index = 0;
+    while index + 3 <= bitstream.len() {
+        if bitstream[index..index + 3] == [0, 0, 1] {
+            starts.push(index + 3);
+            index += 3;
+        } else {
+            index += 1;
+        }
+    }
+
+    let mut units = Vec::new();
+    for (position, &start) in starts.iter().enumerate() {
+        let end = match starts.get(position + 1) {
+            Some(next) => next - 3,
+            None => bitstream.len(),
+        };
+        let mut nal = &bitstream[start..end];
+        while let [rest @ .., 0] = nal {
+            nal = rest;
+        }
+        if !nal.is_empty() {
+            units.push(nal);
+        }
+    }
+    return units;
+}
+
+/// The EncodeOutput describes a frame encoded by [`Encoder::encode`] into the
+/// caller's destination frame.
+pub struct EncodeOutput {
+    /// Whether the encoded frame is a keyframe.
+    pub keyframe: bool,
+    /// The RGB thumbnail of the encoded region, when enabled by
+    /// [`Encoder::with_thumbnail`].
+    pub thumbnail: Option<frame::Frame>,
+    /// The source frame's [`frame::Frame::hdr_metadata`].  The library's
+    /// encoder cannot insert SEI messages and fixes the size of the
+    /// destination, so the metadata is not in its bitstream.  Callers add the
+    /// SEI to a copy of the bitstream through [`insert_hdr_sei`], as
+    /// [`crate::mux::Mp4Recorder`] does for keyframes.
+    pub hdr_metadata: Option<HdrMetadata>,
 }
 
 pub struct VSLEncoderProfile {
     profile: ffi::VSLEncoderProfile,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct VSLRect {
     rect: ffi::vsl_rect,
 }
@@ -42,15 +236,82 @@ impl VSLRect {
     pub fn get_y(&self) -> c_int {
         return (self.rect).y;
     }
+
+    /// Returns true if the rectangle is not empty and lies within a frame of
+    /// the given size.
+    pub(crate) fn fits(&self, width: c_int, height: c_int) -> bool {
+        let r = &self.rect;
+        return r.x >= 0
+            && r.y >= 0
+            && r.width > 0
+            && r.height > 0
+            && r.x as i64 + r.width as i64 <= width as i64
+            && r.y as i64 + r.height as i64 <= height as i64;
+    }
+
+    /// Returns the part of the rectangle within a frame of the given size.
+    pub(crate) fn clamp(&self, width: c_int, height: c_int) -> Self {
+        let r = &self.rect;
+        let x = r.x.clamp(0, width.max(0));
+        let y = r.y.clamp(0, height.max(0));
+        let right = (r.x as i64 + r.width as i64).clamp(x as i64, width.max(0) as i64);
+        let bottom = (r.y as i64 + r.height as i64).clamp(y as i64, height.max(0) as i64);
+        return VSLRect::new(
+            x,
+            y,
+            (right - x as i64) as c_int,
+            (bottom - y as i64) as c_int,
+        );
+    }
 }
 
 impl Encoder {
     pub fn create(profile: u32, output_fourcc: u32, fps: c_int) -> Self {
         return Encoder {
-            ptr: unsafe { ffi::vsl_encoder_create(profile, output_fourcc, fps) },
+            ptr: Cell::new(unsafe { ffi::vsl_encoder_create(profile, output_fourcc, fps) }),
+            profile,
+            output_fourcc,
+            fps,
+            keyframe_requested: Cell::new(false),
+            thumbnail: None,
+            codec_config: RefCell::new(None),
         };
     }
 
+    /// Also produces a `width` by `height` RGB thumbnail of each frame
+    /// encoded by [`Encoder::encode`], for previews which would otherwise
+    /// need their own resizing of the source.
+    ///
+    /// The library's encoder does not expose its scaler, so the thumbnail is
+    /// scaled from the source by `vsl_frame_copy`, which uses the hardware
+    /// accelerator where available.  This costs a buffer allocation and a
+    /// scaled copy for every frame, so it is disabled by default.
+    pub fn with_thumbnail(mut self, width: c_int, height: c_int) -> Self {
+        self.thumbnail = Some((width, height));
+        return self;
+    }
+
+    /// Requests that the next call to [`Encoder::frame`] produces a keyframe,
+    /// such as when a new client needs a keyframe to start decoding.
+    ///
+    /// The library cannot force a keyframe within a session, so the encoder
+    /// is restarted before the next frame, a new session beginning with a
+    /// keyframe.  This resets the encoder's rate control.
+    pub fn request_keyframe(&self) {
+        self.keyframe_requested.set(true);
+    }
+
+    /// Returns true if a keyframe has been requested which has not yet been
+    /// encoded.
+    pub fn keyframe_requested(&self) -> bool {
+        return self.keyframe_requested.get();
+    }
+
+    /// Returns the fourcc of the encoded frames.
+    pub(crate) fn output_fourcc(&self) -> u32 {
+        return self.output_fourcc;
+    }
+
     pub fn new_output_frame(
         &self,
         width: c_int,
@@ -58,17 +319,14 @@ impl Encoder {
         duration: i64,
         pts: i64,
         dts: i64,
-    ) -> Result<frame::Frame, Box<dyn Error>> {
+    ) -> Result<frame::Frame, VslError> {
         let frame_ptr = unsafe {
-            ffi::vsl_encoder_new_output_frame(self.ptr, width, height, duration, pts, dts)
+            ffi::vsl_encoder_new_output_frame(self.ptr.get(), width, height, duration, pts, dts)
         };
         if frame_ptr.is_null() {
-            return Err(Box::new(NullStringError {}));
+            return Err(VslError::NullPointer);
         }
-        match frame_ptr.try_into() {
-            Ok(frame) => return Ok(frame),
-            Err(()) => return Err(Box::new(NullStringError {})),
-        };
+        return frame::Frame::try_from(frame_ptr);
     }
 
     pub fn frame(
@@ -78,20 +336,152 @@ impl Encoder {
         crop_region: &mut VSLRect,
         keyframe: *mut c_int,
     ) -> i32 {
-        return unsafe {
+        if self.keyframe_requested.get() {
+            self.restart();
+        }
+        let ret = unsafe {
             ffi::vsl_encode_frame(
-                self.ptr,
+                self.ptr.get(),
                 source.get_ptr(),
                 destination.get_ptr(),
                 &mut crop_region.rect,
                 keyframe,
             )
         };
+        if ret == 0 {
+            if let Ok(bitstream) = destination.mmap() {
+                let sets = extract_parameter_sets(self.output_fourcc, bitstream);
+                if !sets.is_empty() {
+                    *self.codec_config.borrow_mut() = Some(sets);
+                }
+            }
+        }
+        return ret;
+    }
+
+    /// Returns the codec's out of band configuration, the parameter sets of
+    /// the most recent frame carrying them, such as to fill an MP4 `avcC` or
+    /// `hvcC` box or an SDP `sprop-parameter-sets`.  The parameter sets are
+    /// returned as an Annex B bitstream, see [`extract_parameter_sets`].
+    ///
+    /// The library does not expose the encoder's configuration, so it is
+    /// taken from the encoded frames and fails with
+    /// [`EncoderError::NoCodecConfig`] until the first keyframe is encoded.
+    pub fn codec_config(&self) -> Result<Vec<u8>, VslError> {
+        if self.ptr.get().is_null() {
+            return Err(VslError::from(EncoderError::Unavailable));
+        }
+        return self
+            .codec_config
+            .borrow()
+            .clone()
+            .ok_or(VslError::from(EncoderError::NoCodecConfig));
+    }
+
+    /// Encodes the crop region of the source frame into the destination,
+    /// returning whether a keyframe was produced along with the thumbnail
+    /// enabled by [`Encoder::with_thumbnail`].  Unlike [`Encoder::frame`]
+    /// the crop is checked against the source frame first, as the backend
+    /// does not check it and may read beyond the source's buffer, failing
+    /// with [`EncoderError::InvalidCrop`].
+    pub fn encode(
+        &self,
+        source: &frame::Frame,
+        destination: &frame::Frame,
+        crop: &VSLRect,
+    ) -> Result<EncodeOutput, VslError> {
+        let source_size = (source.width(), source.height());
+        if !crop.fits(source_size.0, source_size.1) {
+            return Err(VslError::from(EncoderError::InvalidCrop {
+                rect: *crop,
+                source_size,
+            }));
+        }
+        if self.ptr.get().is_null() {
+            return Err(VslError::from(EncoderError::Unavailable));
+        }
+        let thumbnail = match self.thumbnail {
+            Some((width, height)) => Some(thumbnail(source, crop, width, height)?),
+            None => None,
+        };
+        let mut crop = *crop;
+        let mut keyframe: c_int = 0;
+        if self.frame(source, destination, &mut crop, &mut keyframe) != 0 {
+            return Err(VslError::from(EncoderError::Io(io::Error::last_os_error())));
+        }
+        return Ok(EncodeOutput {
+            keyframe: keyframe != 0,
+            thumbnail,
+            hdr_metadata: source.hdr_metadata(),
+        });
+    }
+
+    /// Encodes as [`Encoder::encode`] after clamping the crop region to the
+    /// source frame, for callers whose regions may extend beyond the frame
+    /// such as tracked objects near its edges.  Regions entirely outside the
+    /// frame still fail with [`EncoderError::InvalidCrop`].
+    pub fn encode_clamped(
+        &self,
+        source: &frame::Frame,
+        destination: &frame::Frame,
+        crop: &VSLRect,
+    ) -> Result<EncodeOutput, VslError> {
+        let clamped = crop.clamp(source.width(), source.height());
+        if !clamped.fits(source.width(), source.height()) {
+            return Err(VslError::from(EncoderError::InvalidCrop {
+                rect: *crop,
+                source_size: (source.width(), source.height()),
+            }));
+        }
+        return self.encode(source, destination, &clamped);
+    }
+
+    /// Replaces the encoder with a new session, keeping the current one if a
+    /// new encoder cannot be created.
+    fn restart(&self) {
+        if self.ptr.get().is_null() {
+            return;
+        }
+        let ptr = unsafe { ffi::vsl_encoder_create(self.profile, self.output_fourcc, self.fps) };
+        if ptr.is_null() {
+            return;
+        }
+        unsafe { ffi::vsl_encoder_release(self.ptr.replace(ptr)) };
+        self.keyframe_requested.set(false);
+    }
+
+    /// Drains the output frames still held by the encoder once the input
+    /// stream has finished.
+    ///
+    /// The library documents no way to signal the end of the stream to an
+    /// encoder, so the frames a backend holds back for reordering cannot be
+    /// drained and this fails with [`EncoderError::FlushUnsupported`].
+    pub fn flush(&self) -> Result<Vec<frame::Frame>, VslError> {
+        if self.ptr.get().is_null() {
+            return Err(VslError::from(EncoderError::Unavailable));
+        }
+        return Err(VslError::from(EncoderError::FlushUnsupported));
+    }
+}
+
+/// Scales the crop region of the source into a new RGB frame.
+fn thumbnail(
+    source: &frame::Frame,
+    crop: &VSLRect,
+    width: c_int,
+    height: c_int,
+) -> Result<frame::Frame, VslError> {
+    let thumbnail = frame::Frame::new(width.max(0) as u32, height.max(0) as u32, 0, "RGB3")?;
+    thumbnail.alloc(None)?;
+    let ret = unsafe { ffi::vsl_frame_copy(thumbnail.get_ptr(), source.get_ptr(), &crop.rect) };
+    if ret < 0 {
+        return Err(VslError::from(EncoderError::Io(io::Error::last_os_error())));
     }
+    return Ok(thumbnail);
 }
 
 impl Drop for Encoder {
     fn drop(&mut self) {
-        unsafe { ffi::vsl_encoder_release(self.ptr) }
+        unsafe { ffi::vsl_encoder_release(self.ptr.get()) }
     }
 }
@@ -0,0 +1,177 @@
+use crate::{
+    client::ClientError, encoder::EncoderError, frame::FrameError, host::HostError, mux::MuxError,
+    FourccError, VersionMismatch,
+};
+use std::{error, ffi::NulError, fmt, io, str::Utf8Error};
+
+/// A Result of the crate's [`VslError`], the error type defaulting to it.
+pub type Result<T, E = VslError> = std::result::Result<T, E>;
+
+/// The VslError is returned by every fallible API of the crate.  It wraps the
+/// errors of each module, so code handling frames, clients, hosts and the
+/// encoder together can use `?` across them, and the module's own error
+/// remains available by matching on the variant.
+#[derive(Debug)]
+pub enum VslError {
+    /// An error of the frame module.
+    Frame(FrameError),
+    /// An error of the client module.
+    Client(ClientError),
+    /// An error of the host module.
+    Host(HostError),
+    /// An error of the encoder module.
+    Encoder(EncoderError),
+    /// An error of the mux module.
+    Mux(MuxError),
+    /// The loaded library is incompatible with the bindings.
+    Version(VersionMismatch),
+    /// A fourcc code is not exactly four ASCII characters.
+    InvalidFourcc(FourccError),
+    /// The library returned a null pointer or string without reporting an
+    /// OS error.
+    NullPointer,
+    /// A string or path holds a NUL byte and cannot be passed to the library,
+    /// or a string returned by the library is not UTF-8.
+    InvalidString,
+    /// The library could not allocate a frame's buffer for a reason other
+    /// than exhausted memory, which is reported as
+    /// [`FrameError::OutOfBufferMemory`].
+    AllocFailed(io::Error),
+    /// An OS error, or the message of a boxed error of an API which does not
+    /// return one of the module errors.
+    Os(io::Error),
+}
+
+impl error::Error for VslError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            VslError::Frame(err) => return Some(err),
+            VslError::Client(err) => return Some(err),
+            VslError::Host(err) => return Some(err),
+            VslError::Encoder(err) => return Some(err),
+            VslError::Mux(err) => return Some(err),
+            VslError::Version(err) => return Some(err),
+            VslError::InvalidFourcc(err) => return Some(err),
+            VslError::NullPointer | VslError::InvalidString => return None,
+            VslError::AllocFailed(err) | VslError::Os(err) => return Some(err),
+        }
+    }
+}
+
+impl fmt::Display for VslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VslError::Frame(err) => write!(f, "{}", err),
+            VslError::Client(err) => write!(f, "{}", err),
+            VslError::Host(err) => write!(f, "{}", err),
+            VslError::Encoder(err) => write!(f, "{}", err),
+            VslError::Mux(err) => write!(f, "{}", err),
+            VslError::Version(err) => write!(f, "{}", err),
+            VslError::InvalidFourcc(err) => write!(f, "{}", err),
+            VslError::NullPointer => write!(f, "null pointer returned by the library"),
+            VslError::InvalidString => write!(f, "string is not valid for the library"),
+            VslError::AllocFailed(err) => write!(f, "failed to allocate the buffer: {}", err),
+            VslError::Os(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<FrameError> for VslError {
+    fn from(err: FrameError) -> Self {
+        return VslError::Frame(err);
+    }
+}
+
+impl From<ClientError> for VslError {
+    fn from(err: ClientError) -> Self {
+        return VslError::Client(err);
+    }
+}
+
+impl From<HostError> for VslError {
+    fn from(err: HostError) -> Self {
+        return VslError::Host(err);
+    }
+}
+
+impl From<EncoderError> for VslError {
+    fn from(err: EncoderError) -> Self {
+        return VslError::Encoder(err);
+    }
+}
+
+impl From<MuxError> for VslError {
+    fn from(err: MuxError) -> Self {
+        return VslError::Mux(err);
+    }
+}
+
+impl From<VersionMismatch> for VslError {
+    fn from(err: VersionMismatch) -> Self {
+        return VslError::Version(err);
+    }
+}
+
+impl From<FourccError> for VslError {
+    fn from(err: FourccError) -> Self {
+        return VslError::InvalidFourcc(err);
+    }
+}
+
+impl From<NulError> for VslError {
+    fn from(_: NulError) -> Self {
+        return VslError::InvalidString;
+    }
+}
+
+impl From<Utf8Error> for VslError {
+    fn from(_: Utf8Error) -> Self {
+        return VslError::InvalidString;
+    }
+}
+
+impl From<io::Error> for VslError {
+    fn from(err: io::Error) -> Self {
+        return VslError::Os(err);
+    }
+}
+
+/// Converts boxed errors, such as those returned through user code, recovering
+/// the module error they hold.  Other errors keep only their message, so the
+/// VslError stays Send and Sync.
+impl From<Box<dyn error::Error>> for VslError {
+    fn from(err: Box<dyn error::Error>) -> Self {
+        let err = match err.downcast::<FrameError>() {
+            Ok(err) => return VslError::Frame(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<ClientError>() {
+            Ok(err) => return VslError::Client(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<HostError>() {
+            Ok(err) => return VslError::Host(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<EncoderError>() {
+            Ok(err) => return VslError::Encoder(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<MuxError>() {
+            Ok(err) => return VslError::Mux(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<VersionMismatch>() {
+            Ok(err) => return VslError::Version(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<FourccError>() {
+            Ok(err) => return VslError::InvalidFourcc(*err),
+            Err(err) => err,
+        };
+        match err.downcast::<io::Error>() {
+            Ok(err) => return VslError::Os(*err),
+            Err(err) => return VslError::Os(io::Error::other(err.to_string())),
+        }
+    }
+}
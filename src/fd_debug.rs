@@ -0,0 +1,33 @@
+use std::{fs, os::fd::RawFd};
+
+/// Logs a file descriptor lifecycle event to stderr.
+pub(crate) fn trace(event: &str, fd: RawFd) {
+    eprintln!("videostream: {} fd {}", event, fd);
+}
+
+/// Returns the number of file descriptors currently open in the process, tests
+/// can compare it before and after exercising frames to assert nothing leaked.
+pub fn open_fd_count() -> usize {
+    let entries = match fs::read_dir("/proc/self/fd") {
+        Ok(entries) => entries.count(),
+        Err(_) => return 0,
+    };
+    // The directory listing itself holds a descriptor while being read.
+    return entries.saturating_sub(1);
+}
+
+/// Returns the number of shared memory mappings in the process, which include
+/// the mappings of frame buffers, tests can compare it before and after
+/// exercising frames to assert no mapping leaked.
+pub fn open_mapping_count() -> usize {
+    let maps = match fs::read_to_string("/proc/self/maps") {
+        Ok(maps) => maps,
+        Err(_) => return 0,
+    };
+    // The permissions, such as "rw-s", end with 's' for shared mappings.
+    return maps
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter(|perms| perms.ends_with('s'))
+        .count();
+}
@@ -0,0 +1,280 @@
+/// A fourcc code identifying a pixel format, as returned by
+/// [`crate::frame::Frame::fourcc`].
+pub type FourCC = u32;
+
+/// Builds the fourcc code from its four characters, as VSL_FOURCC.
+pub const fn fourcc_code(code: &[u8; 4]) -> FourCC {
+    return code[0] as u32
+        | (code[1] as u32) << 8
+        | (code[2] as u32) << 16
+        | (code[3] as u32) << 24;
+}
+
+pub const RGB3: FourCC = fourcc_code(b"RGB3");
+pub const BGR3: FourCC = fourcc_code(b"BGR3");
+pub const RGBA: FourCC = fourcc_code(b"RGBA");
+pub const BGRA: FourCC = fourcc_code(b"BGRA");
+pub const RGBX: FourCC = fourcc_code(b"RGBX");
+pub const BGRX: FourCC = fourcc_code(b"BGRX");
+pub const YUYV: FourCC = fourcc_code(b"YUYV");
+pub const YUY2: FourCC = fourcc_code(b"YUY2");
+pub const UYVY: FourCC = fourcc_code(b"UYVY");
+pub const GREY: FourCC = fourcc_code(b"GREY");
+/// An alias of GREY used by some camera stacks.
+pub const Y8: FourCC = fourcc_code(b"Y8  ");
+pub const Y16: FourCC = fourcc_code(b"Y16 ");
+pub const NV12: FourCC = fourcc_code(b"NV12");
+pub const NV21: FourCC = fourcc_code(b"NV21");
+pub const NV16: FourCC = fourcc_code(b"NV16");
+pub const I420: FourCC = fourcc_code(b"I420");
+pub const YV12: FourCC = fourcc_code(b"YV12");
+/// Semi-planar 4:2:0 YUV as NV12 with 10 bit samples in the high bits of 16
+/// bit little endian words.
+pub const P010: FourCC = fourcc_code(b"P010");
+/// Semi-planar 4:2:0 YUV as P010 with 16 bit samples.
+pub const P016: FourCC = fourcc_code(b"P016");
+
+/// The largest width or height accepted in a frame layout, beyond the widest
+/// scientific sensors.  Larger values are treated as malformed.
+const MAX_DIMENSION: u32 = 1 << 20;
+
+/// The largest row stride in bytes accepted in a frame layout, room for the
+/// widest frame of the largest pixels with padding.
+const MAX_STRIDE: u32 = 1 << 23;
+
+/// How the chroma of a YUV format is subsampled relative to its luma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsampling {
+    /// Every pixel has its own color, as with RGB and greyscale formats.
+    None,
+    /// Chroma is shared by each horizontal pair of pixels.
+    Yuv422,
+    /// Chroma is shared by each 2x2 block of pixels.
+    Yuv420,
+}
+
+/// The PixelFormatInfo describes the layout of an uncompressed pixel format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormatInfo {
+    /// Number of planes the pixel data is stored in.
+    pub planes: u8,
+    /// Average bits per pixel across all planes.
+    pub bits_per_pixel: u8,
+    pub subsampling: Subsampling,
+    /// True if the luma and chroma are stored in separate planes.
+    pub is_planar: bool,
+    /// The channels in memory order, planes separated by '/' and with 'X'
+    /// for padding.  For example "RGBX", "YUYV" or "Y/UV".
+    pub channel_order: &'static str,
+}
+
+/// Returns the description of the pixel format, or None if the fourcc is not
+/// a known uncompressed format.
+pub fn format_info(fourcc: FourCC) -> Option<PixelFormatInfo> {
+    let info = |planes, bits_per_pixel, subsampling, channel_order| {
+        return PixelFormatInfo {
+            planes,
+            bits_per_pixel,
+            subsampling,
+            is_planar: planes > 1,
+            channel_order,
+        };
+    };
+    let info = match fourcc {
+        RGB3 => info(1, 24, Subsampling::None, "RGB"),
+        BGR3 => info(1, 24, Subsampling::None, "BGR"),
+        RGBA => info(1, 32, Subsampling::None, "RGBA"),
+        BGRA => info(1, 32, Subsampling::None, "BGRA"),
+        RGBX => info(1, 32, Subsampling::None, "RGBX"),
+        BGRX => info(1, 32, Subsampling::None, "BGRX"),
+        YUYV | YUY2 => info(1, 16, Subsampling::Yuv422, "YUYV"),
+        UYVY => info(1, 16, Subsampling::Yuv422, "UYVY"),
+        GREY | Y8 => info(1, 8, Subsampling::None, "Y"),
+        Y16 => info(1, 16, Subsampling::None, "Y"),
+        NV12 => info(2, 12, Subsampling::Yuv420, "Y/UV"),
+        NV21 => info(2, 12, Subsampling::Yuv420, "Y/VU"),
+        NV16 => info(2, 16, Subsampling::Yuv422, "Y/UV"),
+        I420 => info(3, 12, Subsampling::Yuv420, "Y/U/V"),
+        YV12 => info(3, 12, Subsampling::Yuv420, "Y/V/U"),
+        P010 | P016 => info(2, 24, Subsampling::Yuv420, "Y/UV"),
+        _ => return None,
+    };
+    return Some(info);
+}
+
+/// The byte positions of the color channels within a pixel of a packed RGB
+/// format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Channels {
+    pub bytes: usize,
+    pub r: usize,
+    pub g: usize,
+    pub b: usize,
+    pub alpha: Option<usize>,
+    pub padding: Option<usize>,
+}
+
+/// Returns the channel layout of a packed RGB format, or None for other
+/// formats.
+pub(crate) fn channels(fourcc: FourCC) -> Option<Channels> {
+    let rgb = |r: usize, b: usize| Channels {
+        bytes: 3,
+        r,
+        g: 1,
+        b,
+        alpha: None,
+        padding: None,
+    };
+    let channels = match fourcc {
+        RGB3 => rgb(0, 2),
+        BGR3 => rgb(2, 0),
+        RGBA => Channels {
+            bytes: 4,
+            alpha: Some(3),
+            ..rgb(0, 2)
+        },
+        BGRA => Channels {
+            bytes: 4,
+            alpha: Some(3),
+            ..rgb(2, 0)
+        },
+        RGBX => Channels {
+            bytes: 4,
+            padding: Some(3),
+            ..rgb(0, 2)
+        },
+        BGRX => Channels {
+            bytes: 4,
+            padding: Some(3),
+            ..rgb(2, 0)
+        },
+        _ => return None,
+    };
+    return Some(channels);
+}
+
+/// The location of a single plane within a frame's buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Plane {
+    /// Offset in bytes of the plane's first row from the start of the buffer.
+    pub offset: usize,
+    /// Bytes from the start of one row to the next.
+    pub stride: usize,
+    /// Bytes of pixel data in each row, excluding any padding.
+    pub row_bytes: usize,
+    /// Number of rows in the plane.
+    pub rows: usize,
+}
+
+impl Plane {
+    /// Returns the offset just beyond the plane's last row, including its
+    /// padding, where the following plane starts.
+    pub fn end(&self) -> usize {
+        return self.offset + self.stride * self.rows;
+    }
+
+    /// Returns the number of bytes from the start of the plane to the end of
+    /// the pixel data of its last row.
+    pub fn len(&self) -> usize {
+        if self.rows == 0 {
+            return 0;
+        }
+        return self.stride * (self.rows - 1) + self.row_bytes;
+    }
+}
+
+/// Returns the plane layout of a frame, where `stride` is the row pitch of the
+/// first plane, or None if the fourcc is not a known uncompressed format.
+///
+/// Subsampled chroma planes round their dimensions up so odd sized frames
+/// keep their last column and row of chroma, with the stride widened when a
+/// rounded up row would not otherwise fit.
+pub(crate) fn planes(fourcc: FourCC, width: u32, height: u32, stride: u32) -> Option<Vec<Plane>> {
+    if width > MAX_DIMENSION || height > MAX_DIMENSION || stride > MAX_STRIDE {
+        return None;
+    }
+    // No plane has rows wider than four bytes a pixel, plus the rounding of
+    // its chroma, nor more rows than the frame, so the layouts of three such
+    // planes bound the arithmetic which follows, keeping it from overflowing
+    // on 32 bit targets.
+    let widest = (stride as u64).max(width as u64 * 4 + 4);
+    if widest * height as u64 * 3 > usize::MAX as u64 {
+        return None;
+    }
+    let width = width as usize;
+    let height = height as usize;
+    let stride = stride as usize;
+    let chroma_width = width.div_ceil(2);
+    let chroma_height = height.div_ceil(2);
+
+    let plane = |offset: usize, stride: usize, row_bytes: usize, rows: usize| {
+        return Plane {
+            offset,
+            stride: stride.max(row_bytes),
+            row_bytes,
+            rows,
+        };
+    };
+    let luma = plane(0, stride, width, height);
+
+    let planes = match fourcc {
+        GREY | Y8 => vec![luma],
+        // Each four bytes hold a pair of pixels sharing their chroma.
+        YUYV | YUY2 | UYVY => vec![plane(0, stride, chroma_width * 4, height)],
+        Y16 => vec![plane(0, stride, width * 2, height)],
+        RGB3 | BGR3 => vec![plane(0, stride, width * 3, height)],
+        RGBA | BGRA | RGBX | BGRX => vec![plane(0, stride, width * 4, height)],
+        NV12 | NV21 | NV16 => {
+            let rows = if fourcc == NV16 {
+                height
+            } else {
+                chroma_height
+            };
+            let chroma = plane(luma.end(), stride, chroma_width * 2, rows);
+            vec![luma, chroma]
+        }
+        I420 | YV12 => {
+            let chroma_stride = stride.div_ceil(2);
+            let first = plane(luma.end(), chroma_stride, chroma_width, chroma_height);
+            let second = plane(first.end(), chroma_stride, chroma_width, chroma_height);
+            vec![luma, first, second]
+        }
+        P010 | P016 => {
+            let luma = plane(0, stride, width * 2, height);
+            let chroma = plane(luma.end(), stride, chroma_width * 4, chroma_height);
+            vec![luma, chroma]
+        }
+        _ => return None,
+    };
+    return Some(planes);
+}
+
+/// Returns true if a frame of the given layout, as described by a host, fits
+/// within its buffer of `size` bytes.  Formats without a known layout, such as
+/// compressed formats, only need a non-empty buffer.
+pub fn layout_fits(fourcc: FourCC, width: u32, height: u32, stride: u32, size: usize) -> bool {
+    if format_info(fourcc).is_none() {
+        return size > 0;
+    }
+    match planes(fourcc, width, height, stride) {
+        Some(planes) => {
+            return planes
+                .iter()
+                .all(|plane| plane.offset + plane.len() <= size)
+        }
+        None => return false,
+    }
+}
+
+/// Returns the row pitch of the first plane of a frame `width` pixels wide
+/// rounded up to a multiple of `align` bytes, the stride to give frames and
+/// external buffers laid out as the crate expects.  An `align` of 0 or 1
+/// leaves the rows unpadded.  Formats without a known layout, such as
+/// compressed formats, return 0 which lets the library choose the stride.
+pub fn default_stride(fourcc: FourCC, width: u32, align: u32) -> u32 {
+    let row_bytes = match planes(fourcc, width, 1, 0) {
+        Some(planes) => planes[0].row_bytes as u32,
+        None => return 0,
+    };
+    return row_bytes.next_multiple_of(align.max(1));
+}
@@ -1,14 +1,559 @@
-use crate::client;
+#[cfg(feature = "fd-debug")]
+use crate::fd_debug;
+use crate::{
+    ack::PendingAck,
+    client,
+    color::{self, ColorRange, ColorSpace, HdrMetadata, Rgb, Rgba, YuvMatrix},
+    encoder::VSLRect,
+    format,
+    metadata::Metadata,
+    simd, sync_file,
+    timebase::Timebase,
+    VslError,
+};
 use std::{
+    cell::Cell,
     error::Error,
-    ffi::{CStr, CString},
-    io,
-    os::fd::RawFd,
-    path::Path,
-    ptr, slice,
+    ffi::{c_void, CStr, CString},
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut, Range},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process, ptr, slice,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 use videostream_sys as ffi;
 
+/// Errors reported by frame operations.
+#[derive(Debug)]
+pub enum FrameError {
+    /// The fourcc must be a four character ascii code.
+    InvalidFourcc,
+    /// A null pointer was provided.
+    NullPointer,
+    /// The provided buffer is smaller than the frame requires.
+    BufferTooSmall { required: usize, len: usize },
+    /// The operation does not support the frame's fourcc.
+    UnsupportedFourcc(u32),
+    /// The plane index is beyond the planes of the frame's format.
+    InvalidPlane { plane: usize, planes: usize },
+    /// The row or region is outside of the frame.
+    OutOfBounds,
+    /// The frame could not be mapped into memory.
+    MapFailed,
+    /// The frame was received from a host and cannot be written to.
+    ReadOnly,
+    /// The frame was received from a host which owns its buffer, so it cannot
+    /// be allocated or attached to another buffer.
+    NotOwned,
+    /// The frame has no buffer, it must be allocated or attached first.
+    NoBuffer,
+    /// The frames must have the same width and height.
+    DimensionMismatch,
+    /// The alignment must be a non-zero power of two.
+    InvalidAlignment(usize),
+    /// The operation cannot be performed in the frame's current state, such
+    /// as mapping a frame before it is allocated.
+    InvalidState {
+        operation: &'static str,
+        state: &'static str,
+    },
+    /// The number of strides or offsets given for a planar layout does not
+    /// match the number of planes of the frame's format.
+    PlaneCountMismatch {
+        planes: usize,
+        strides: usize,
+        offsets: usize,
+    },
+    /// The stride of the plane is shorter than the bytes of its rows.
+    StrideTooSmall {
+        plane: usize,
+        stride: usize,
+        required: usize,
+    },
+    /// The frame cannot be reinterpreted as the fourcc as their layouts
+    /// differ, or either layout is unknown.
+    IncompatibleFourcc { from: u32, to: u32 },
+    /// No dmabuf heap is available, as on kernels before 5.6 and systems
+    /// without `/dev/dma_heap` or permission to use it.
+    DmabufUnavailable,
+    /// The region does not start on a chroma sample of the frame's
+    /// subsampled format, such as an odd column of an NV12 frame.
+    MisalignedRegion { x: u32, y: u32 },
+    /// The buffer of `size` bytes could not be allocated as the dmabuf heap or
+    /// shared memory is exhausted.  A smaller frame or reusing existing frames
+    /// may still succeed.
+    OutOfBufferMemory { size: usize },
+    /// The subsampling factor is zero or does not evenly divide the frame's
+    /// width and height.
+    InvalidFactor(u32),
+    /// The frame is locked by another user of its buffer, the lock may be
+    /// tried again.
+    WouldBlock,
+    /// The frame was not ready within the time given to
+    /// [`Frame::wait_ready`].
+    Timeout,
+    /// The underlying library reported an OS error.
+    Io(io::Error),
+}
+
+impl Error for FrameError {}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::InvalidFourcc => write!(f, "fourcc must be 4 character ascii code"),
+            FrameError::NullPointer => write!(f, "null pointer provided"),
+            FrameError::BufferTooSmall { required, len } => write!(
+                f,
+                "buffer of {} bytes is too small, frame requires {} bytes",
+                len, required
+            ),
+            FrameError::UnsupportedFourcc(fourcc) => {
+                write!(f, "unsupported fourcc {}", fourcc_string(*fourcc))
+            }
+            FrameError::InvalidPlane { plane, planes } => {
+                write!(f, "invalid plane {}, format has {} planes", plane, planes)
+            }
+            FrameError::OutOfBounds => write!(f, "out of frame bounds"),
+            FrameError::MapFailed => write!(f, "failed to map frame"),
+            FrameError::ReadOnly => write!(f, "frame received from a host is read-only"),
+            FrameError::NotOwned => write!(f, "frame buffer is owned by the host"),
+            FrameError::NoBuffer => write!(f, "frame has no buffer"),
+            FrameError::DimensionMismatch => write!(f, "frame dimensions do not match"),
+            FrameError::InvalidAlignment(alignment) => {
+                write!(f, "alignment {} is not a power of two", alignment)
+            }
+            FrameError::InvalidState { operation, state } => {
+                write!(f, "cannot {} a frame which is {}", operation, state)
+            }
+            FrameError::PlaneCountMismatch {
+                planes,
+                strides,
+                offsets,
+            } => write!(
+                f,
+                "format has {} planes but {} strides and {} offsets were given",
+                planes, strides, offsets
+            ),
+            FrameError::StrideTooSmall {
+                plane,
+                stride,
+                required,
+            } => write!(
+                f,
+                "stride {} of plane {} is less than its {} bytes per row",
+                stride, plane, required
+            ),
+            FrameError::IncompatibleFourcc { from, to } => write!(
+                f,
+                "cannot reinterpret {} as {} with a different layout",
+                fourcc_string(*from),
+                fourcc_string(*to)
+            ),
+            FrameError::DmabufUnavailable => write!(f, "no dmabuf heap is available"),
+            FrameError::MisalignedRegion { x, y } => {
+                write!(
+                    f,
+                    "region at {}, {} is not aligned to the chroma samples",
+                    x, y
+                )
+            }
+            FrameError::OutOfBufferMemory { size } => {
+                write!(f, "out of buffer memory allocating {} bytes", size)
+            }
+            FrameError::InvalidFactor(factor) => write!(
+                f,
+                "factor {} does not evenly divide the frame dimensions",
+                factor
+            ),
+            FrameError::WouldBlock => write!(f, "frame is locked elsewhere"),
+            FrameError::Timeout => write!(f, "timed out waiting for the frame"),
+            FrameError::Io(err) => write!(f, "frame error: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for FrameError {
+    fn from(err: io::Error) -> Self {
+        return FrameError::Io(err);
+    }
+}
+
+/// The field order of a frame's content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interlace {
+    /// Every row belongs to the same instant.
+    #[default]
+    Progressive,
+    /// The rows alternate between two fields, starting with the top field.
+    TopFieldFirst,
+    /// The rows alternate between two fields, starting with the bottom field.
+    BottomFieldFirst,
+}
+
+/// The transformation which displays a frame's content upright, such as for
+/// a camera mounted sideways.  Rotations are clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    /// The content is already upright.
+    #[default]
+    None,
+    Rot90,
+    Rot180,
+    Rot270,
+    /// Mirrors the content left to right.
+    FlipH,
+    /// Mirrors the content top to bottom.
+    FlipV,
+}
+
+impl Rotation {
+    /// Whether the rotation swaps the width and height.
+    fn transposes(&self) -> bool {
+        return matches!(self, Rotation::Rot90 | Rotation::Rot270);
+    }
+
+    /// Returns the column and row of the source element shown at `x`, `y`
+    /// once rotated, for a source of `cols` by `rows` elements.
+    fn source(&self, x: usize, y: usize, cols: usize, rows: usize) -> (usize, usize) {
+        match self {
+            Rotation::None => return (x, y),
+            Rotation::Rot90 => return (y, rows - 1 - x),
+            Rotation::Rot180 => return (cols - 1 - x, rows - 1 - y),
+            Rotation::Rot270 => return (cols - 1 - y, x),
+            Rotation::FlipH => return (cols - 1 - x, y),
+            Rotation::FlipV => return (x, rows - 1 - y),
+        }
+    }
+}
+
+/// Where the 10 bits of each sample of a P010 frame sit within its 16 bit
+/// word.  P010 as defined stores them in the high bits, while some sensors
+/// and decoders deliver them in the low bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitLayout {
+    /// The samples occupy the high bits, the low 6 bits being zero.
+    #[default]
+    Msb,
+    /// The samples occupy the low bits, the high 6 bits being zero.
+    Lsb,
+}
+
+/// The memory backing the buffer allocated by [`Frame::alloc_with`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AllocBacking {
+    /// A dmabuf from the dmabuf heap device, such as
+    /// `/dev/dma_heap/linux,cma`, or without one the first heap available.
+    /// Dmabufs can be imported without a copy by GPUs through EGL or Vulkan,
+    /// by NPUs and by hardware encoders, and a CMA heap provides the
+    /// physically contiguous buffers which hardware without an IOMMU needs.
+    DmaBuf(Option<PathBuf>),
+    /// POSIX shared memory of the name, such as `/camera`, or without one a
+    /// name unique to the process.  Shared memory can be mapped by any
+    /// process on every platform the library supports, but hardware blocks
+    /// need it copied into a dmabuf first.
+    SharedMemory(Option<PathBuf>),
+    /// A dmabuf from the library's default heap, falling back to shared
+    /// memory where no heap is available, as [`Frame::alloc`] without a path.
+    #[default]
+    Auto,
+}
+
+/// Identifies the frames written by [`Frame::save`].
+const SAVE_MAGIC: &[u8; 4] = b"VSLF";
+
+/// The version of the [`Frame::save`] format.
+const SAVE_VERSION: u32 = 1;
+
+/// Bytes of the [`Frame::save`] header: the magic, version, width, height,
+/// stride and fourcc then the pts and buffer length.
+const SAVE_HEADER_LEN: usize = 4 + 4 * 5 + 8 * 2;
+
+/// The dmabuf heaps tried by [`Frame::to_dmabuf_copy`], in order.  Embedded
+/// SoCs such as the i.MX 8 provide the contiguous CMA heap which hardware
+/// blocks without an IOMMU need, while other systems only have the system heap.
+const DMA_HEAPS: [&str; 2] = ["/dev/dma_heap/linux,cma", "/dev/dma_heap/system"];
+
+/// How often [`Frame::wait_ready`] tries the lock of a frame being written.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Numbers the shared memory objects named by [`shm_name`].
+static SHM_NAMES: AtomicUsize = AtomicUsize::new(0);
+
+/// The lifecycle of a frame's buffer.  Operations check the state so calls
+/// made out of order are rejected before they reach the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameState {
+    /// The frame has no buffer yet.
+    Unallocated,
+    /// The frame has a buffer, allocated or attached, which is not mapped.
+    Allocated,
+    /// The frame's buffer is mapped into memory.
+    Mapped,
+    /// The frame is locked, its buffer may or may not be mapped as tracked by
+    /// the frame's `locked_mapped`.
+    Locked,
+    /// The frame was released, only dropping it remains.
+    Released,
+}
+
+impl FrameState {
+    fn name(self) -> &'static str {
+        match self {
+            FrameState::Unallocated => return "unallocated",
+            FrameState::Allocated => return "allocated",
+            FrameState::Mapped => return "mapped",
+            FrameState::Locked => return "locked",
+            FrameState::Released => return "released",
+        }
+    }
+
+    /// Returns true if a frame may move from this state to `next`.
+    fn allows(self, next: FrameState) -> bool {
+        use FrameState::*;
+        match (self, next) {
+            (Released, _) => return false,
+            (_, Released) => return true,
+            (Unallocated, Allocated) => return true,
+            (Allocated, Allocated | Mapped | Locked) => return true,
+            (Mapped, Allocated | Mapped | Locked) => return true,
+            (Locked, Allocated | Mapped | Locked) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// The geometry and pixel format of a frame, returned by [`Frame::format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameFormat {
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub fourcc: u32,
+}
+
+/// The FrameLockGuard keeps its frame locked until dropped, returned by
+/// [`Frame::locked`].  It dereferences to the frame.
+pub struct FrameLockGuard<'a> {
+    frame: &'a Frame,
+}
+
+impl Deref for FrameLockGuard<'_> {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        return self.frame;
+    }
+}
+
+impl Drop for FrameLockGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.frame.unlock();
+    }
+}
+
+/// The MmapGuard holds a read-only mapping of a frame's buffer, returned by
+/// [`Frame::map`], or of part of it, returned by [`Frame::map_range`], which
+/// is unmapped when dropped.  It dereferences to exactly the mapped bytes.
+pub struct MmapGuard<'a> {
+    data: *const u8,
+    len: usize,
+    /// The page aligned mapping holding the bytes, None when they belong to
+    /// a buffer mapped elsewhere.
+    mapping: Option<(*mut c_void, usize)>,
+    /// The frame mapped by [`Frame::map`], unmapped once its last guard is
+    /// dropped.
+    frame: Option<&'a Frame>,
+    _frame: PhantomData<&'a Frame>,
+}
+
+impl Deref for MmapGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return unsafe { slice::from_raw_parts(self.data, self.len) };
+    }
+}
+
+impl Drop for MmapGuard<'_> {
+    fn drop(&mut self) {
+        if let Some((ptr, len)) = self.mapping {
+            unsafe { libc::munmap(ptr, len) };
+        }
+        if let Some(frame) = self.frame {
+            frame.release_guard();
+        }
+    }
+}
+
+/// The MmapMutGuard holds a writable mapping of a frame's buffer, returned by
+/// [`Frame::map_mut`], which is unmapped when dropped.  It borrows the frame
+/// mutably, so no other mapping of the frame can be used meanwhile.
+pub struct MmapMutGuard<'a> {
+    data: *mut u8,
+    len: usize,
+    /// Whether the guard mapped the frame, otherwise the mapping is kept for
+    /// the slices of [`Frame::mmap`].
+    unmap: bool,
+    frame: &'a Frame,
+}
+
+impl Deref for MmapMutGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return unsafe { slice::from_raw_parts(self.data, self.len) };
+    }
+}
+
+impl DerefMut for MmapMutGuard<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        return unsafe { slice::from_raw_parts_mut(self.data, self.len) };
+    }
+}
+
+impl Drop for MmapMutGuard<'_> {
+    fn drop(&mut self) {
+        if self.unmap {
+            self.frame.munmap();
+        }
+    }
+}
+
+/// The CroppedFrame is a rectangular region of a frame, returned by
+/// [`Frame::crop_view`], whose rows are slices of the parent frame's mapped
+/// buffer so no pixels are copied.
+pub struct CroppedFrame<'a> {
+    parent: &'a Frame,
+    mem: &'a [u8],
+    planes: Vec<format::Plane>,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> CroppedFrame<'a> {
+    /// Returns the frame the region was cropped from.
+    pub fn parent(&self) -> &'a Frame {
+        return self.parent;
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.height;
+    }
+
+    pub fn fourcc(&self) -> u32 {
+        return self.parent.fourcc();
+    }
+
+    /// Returns the pixel data of row `y` of the given plane within the region,
+    /// as [`Frame::row`].
+    pub fn row(&self, plane: usize, y: u32) -> Result<&'a [u8], VslError> {
+        let layout = match self.planes.get(plane) {
+            Some(layout) => layout,
+            None => {
+                return Err(VslError::from(FrameError::InvalidPlane {
+                    plane,
+                    planes: self.planes.len(),
+                }))
+            }
+        };
+        if y as usize >= layout.rows {
+            return Err(VslError::from(FrameError::OutOfBounds));
+        }
+        let start = layout.offset + y as usize * layout.stride;
+        return self
+            .mem
+            .get(start..start + layout.row_bytes)
+            .ok_or(VslError::from(FrameError::OutOfBounds));
+    }
+
+    /// Copies the pixel data of every plane within the region into a vector,
+    /// as [`Frame::to_vec`].
+    pub fn to_vec(&self) -> Result<Vec<u8>, VslError> {
+        return copy_planes(self.mem, &self.planes);
+    }
+}
+
+/// The Histogram is the distribution of the values of each of a frame's
+/// channels, returned by [`Frame::histogram`].  Each channel has the same
+/// number of bins evenly dividing the 8 bit range of its values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// The counts of each channel: red, green and blue for RGB frames, Y, U
+    /// and V for YUV frames, or only the luma of greyscale frames.
+    pub channels: Vec<Vec<u64>>,
+}
+
+impl Histogram {
+    /// Returns the number of bins of each channel.
+    pub fn bins(&self) -> usize {
+        return self.channels.first().map_or(0, |channel| channel.len());
+    }
+}
+
+/// The PlaneLayout describes one plane of a frame's buffer for importing it
+/// into another API, returned by [`Frame::planes_layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+    /// The buffer's descriptor, shared by the planes of a frame.
+    pub fd: Option<RawFd>,
+    /// Offset in bytes of the plane from the start of the descriptor.
+    pub offset: usize,
+    /// Bytes from the start of one row to the next.
+    pub stride: u32,
+    /// Bytes of the plane, its rows including their padding.
+    pub size: usize,
+}
+
+/// The PhysAddr is the physical address of a frame's buffer, returned by
+/// [`Frame::physical_address`].  It is only meaningful to hardware reading
+/// memory directly, such as a DMA engine, and must not be dereferenced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysAddr(u64);
+
+impl PhysAddr {
+    pub fn as_u64(&self) -> u64 {
+        return self.0;
+    }
+}
+
+impl fmt::Display for PhysAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{:#x}", self.0);
+    }
+}
+
+/// The FrameExport holds everything needed to reconstruct a frame in another
+/// process, returned by [`Frame::export`] and consumed by [`Frame::import`].
+/// The descriptor can be sent to the other process over a UNIX socket using
+/// SCM_RIGHTS while the remaining fields are sent as regular data.
+#[derive(Debug)]
+pub struct FrameExport {
+    pub fd: OwnedFd,
+    pub size: usize,
+    pub offset: usize,
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub fourcc: u32,
+    pub pts: i64,
+}
+
 /// The Frame structure handles the frame and underlying framebuffer.  A frame
 /// can be an image or a single video frame, the distinction is not considered.
 ///
@@ -18,151 +563,2059 @@ use videostream_sys as ffi;
 /// an optimized method for resizing or converting between formats.
 pub struct Frame {
     ptr: *mut ffi::VSLFrame,
+    owned_fd: Cell<Option<OwnedFd>>,
+    // The size the buffer was attached with, or zero.
+    attached_len: Cell<usize>,
+    offset: Cell<usize>,
+    imported_pts: Option<i64>,
+    interlace: Interlace,
+    orientation: Rotation,
+    color_space: Option<ColorSpace>,
+    color_range: Option<ColorRange>,
+    hdr: Option<Box<HdrMetadata>>,
+    external: Option<(*mut u8, usize)>,
+    // Whether `external` is a mapping made by Frame::from_file_mmap, which is
+    // unmapped when the frame is released.
+    file_mapped: bool,
+    layout: Option<Vec<format::Plane>>,
+    reinterpreted: Option<u32>,
+    timing: Option<Timing>,
+    // Sent to the host once the frame is dropped, see ClientOptions::send_acks.
+    ack: Option<Box<PendingAck>>,
+    host_owned: bool,
+    // Whether the frame was created by Frame::probe.
+    probe: bool,
+    bit_layout: BitLayout,
+    dirty: Vec<VSLRect>,
+    state: Cell<FrameState>,
+    // The guards of Frame::map sharing the mapping they made.
+    guards: Cell<u32>,
+    // Whether the buffer of a locked frame is mapped, restored by unlock.
+    locked_mapped: Cell<bool>,
+    // Whether slices returned by Frame::mmap may still use the mapping, which
+    // the guards of Frame::map then leave for Frame::munmap to unmap.
+    mmap_pinned: Cell<bool>,
+    // The frame whose buffer is shared, held so it stays locked.
+    origin: Option<Arc<SharedOrigin>>,
+    // Whether the library frame calls release_trampoline when released, as
+    // frames created by this crate do.
+    release_hook: bool,
+    // Declared last so the frame is released before its client.
+    client: Option<Arc<client::ClientHandle>>,
+}
+
+unsafe impl Send for Frame {}
+
+/// The timestamps of a frame, carried over to the frames created by
+/// [`Frame::split`] as their library frames have none.
+#[derive(Clone, Copy)]
+struct Timing {
+    serial: i64,
+    timestamp: i64,
+    duration: i64,
+    pts: i64,
+    dts: i64,
+    expires: i64,
+}
+
+/// Holds a frame shared by [`Frame::split`] until its last share is dropped.
+struct SharedOrigin {
+    _frame: Frame,
 }
 
-unsafe impl Send for Frame {}
+// The held frame is never accessed, only dropped by the last share.
+unsafe impl Sync for SharedOrigin {}
+
+impl Frame {
+    pub fn new(width: u32, height: u32, stride: u32, fourcc_str: &str) -> Result<Self, VslError> {
+        let fourcc = crate::fourcc(fourcc_str)?;
+        return Frame::init(width, height, stride, fourcc);
+    }
+
+    /// Creates a new frame whose planes are each at the given offset, in bytes
+    /// from the start of the buffer, and with the given stride.  This wraps
+    /// buffers, such as those of hardware decoders, whose planes are padded or
+    /// placed differently than the layout derived from a single stride which
+    /// [`Frame::new`] assumes.  A stride and an offset must be given for each
+    /// plane of the format, for example two of each for NV12.
+    ///
+    /// The library's frame only records the first plane's stride, so the
+    /// layout is known to this process only.  The frame is expected to be
+    /// attached to the buffer the layout describes, through
+    /// [`Frame::attach`] or [`Frame::attach_owned`], which check that the
+    /// buffer covers every plane.
+    pub fn new_planar(
+        width: u32,
+        height: u32,
+        fourcc: &str,
+        strides: &[u32],
+        offsets: &[usize],
+    ) -> Result<Self, VslError> {
+        let fourcc = crate::fourcc(fourcc)?;
+        let stride = strides.first().copied().unwrap_or(0);
+        let planes = match format::planes(fourcc, width, height, stride) {
+            Some(planes) => planes,
+            None => return Err(VslError::from(FrameError::UnsupportedFourcc(fourcc))),
+        };
+        if strides.len() != planes.len() || offsets.len() != planes.len() {
+            return Err(VslError::from(FrameError::PlaneCountMismatch {
+                planes: planes.len(),
+                strides: strides.len(),
+                offsets: offsets.len(),
+            }));
+        }
+
+        let mut layout = Vec::with_capacity(planes.len());
+        for (index, plane) in planes.iter().enumerate() {
+            let stride = strides[index] as usize;
+            if stride < plane.row_bytes {
+                return Err(VslError::from(FrameError::StrideTooSmall {
+                    plane: index,
+                    stride,
+                    required: plane.row_bytes,
+                }));
+            }
+            layout.push(format::Plane {
+                offset: offsets[index],
+                stride,
+                ..*plane
+            });
+        }
+
+        let mut frame = Frame::init(width, height, stride, fourcc)?;
+        frame.layout = Some(layout);
+        return Ok(frame);
+    }
+
+    /// Creates a new frame as [`Frame::new`] which invokes `on_release` once
+    /// the library frees it, either when dropped or, for frames posted to a
+    /// host, once the host has expired it.  This lets the owner of externally
+    /// provided memory know when it can be reclaimed.
+    pub fn new_with_release(
+        width: u32,
+        height: u32,
+        stride: u32,
+        fourcc: &str,
+        on_release: Box<dyn FnOnce() + Send>,
+    ) -> Result<Self, VslError> {
+        let fourcc = crate::fourcc(fourcc)?;
+        // The trampoline takes the closure back from the userptr on release.
+        let userptr = Box::into_raw(Box::new(on_release)) as *mut c_void;
+        let ptr = unsafe {
+            ffi::vsl_frame_init(
+                width,
+                height,
+                stride,
+                fourcc,
+                userptr,
+                Some(release_trampoline),
+            )
+        };
+        if ptr.is_null() {
+            let err = io::Error::last_os_error();
+            drop(unsafe { Box::from_raw(userptr as *mut Box<dyn FnOnce() + Send>) });
+            return Err(VslError::from(err));
+        }
+        let mut frame = Frame::from_ptr(ptr);
+        frame.release_hook = true;
+        return Ok(frame);
+    }
+
+    /// Creates a probe describing the format of the frames a host intends to
+    /// post, which carries only the fourcc and dimensions without a buffer.
+    /// Probes are exchanged through [`crate::host::Host::post_probe`] and
+    /// [`crate::client::ClientEvent::Probe`] to negotiate a stream before
+    /// transferring any pixels.
+    ///
+    /// A probe cannot be allocated or attached, nor can a frame with a width
+    /// or height of zero, which is likewise metadata only.  Both report
+    /// [`FrameError::InvalidState`] when given a buffer.
+    pub fn probe(fourcc: &str, width: u32, height: u32) -> Result<Self, VslError> {
+        let fourcc = crate::fourcc(fourcc)?;
+        return Frame::init_probe(width, height, fourcc);
+    }
+
+    pub(crate) fn init_probe(width: u32, height: u32, fourcc: u32) -> Result<Self, VslError> {
+        let mut frame = Frame::init(width, height, 0, fourcc)?;
+        frame.probe = true;
+        return Ok(frame);
+    }
+
+    /// Returns true if the frame only carries metadata, either a probe from
+    /// [`Frame::probe`] or a frame with a width or height of zero, so it can
+    /// never have a buffer.
+    pub fn is_probe(&self) -> bool {
+        return self.probe || self.width() <= 0 || self.height() <= 0;
+    }
+
+    pub(crate) fn init(
+        width: u32,
+        height: u32,
+        stride: u32,
+        fourcc: u32,
+    ) -> Result<Self, VslError> {
+        // The trampoline runs what into_raw hands over to the library frame.
+        let ptr = unsafe {
+            ffi::vsl_frame_init(
+                width,
+                height,
+                stride,
+                fourcc,
+                std::ptr::null_mut(),
+                Some(release_trampoline),
+            )
+        };
+
+        if ptr.is_null() {
+            let err = io::Error::last_os_error();
+            return Err(VslError::from(err));
+        }
+        let mut frame = Frame::from_ptr(ptr);
+        frame.release_hook = true;
+        return Ok(frame);
+    }
+
+    fn from_ptr(ptr: *mut ffi::VSLFrame) -> Self {
+        let frame = Frame {
+            ptr,
+            owned_fd: Cell::new(None),
+            attached_len: Cell::new(0),
+            offset: Cell::new(0),
+            imported_pts: None,
+            interlace: Interlace::Progressive,
+            orientation: Rotation::None,
+            color_space: None,
+            color_range: None,
+            hdr: None,
+            external: None,
+            file_mapped: false,
+            layout: None,
+            reinterpreted: None,
+            timing: None,
+            ack: None,
+            host_owned: false,
+            probe: false,
+            bit_layout: BitLayout::Msb,
+            dirty: Vec::new(),
+            state: Cell::new(FrameState::Unallocated),
+            guards: Cell::new(0),
+            locked_mapped: Cell::new(false),
+            mmap_pinned: Cell::new(false),
+            origin: None,
+            release_hook: false,
+            client: None,
+        };
+        if frame.handle().is_some() {
+            frame.state.set(FrameState::Allocated);
+        }
+        return frame;
+    }
+
+    /// Wraps a frame received by a client, whose buffer belongs to the host.
+    pub(crate) fn from_client(ptr: *mut ffi::VSLFrame, client: Arc<client::ClientHandle>) -> Self {
+        let mut frame = Frame::from_ptr(ptr);
+        frame.host_owned = true;
+        frame.client = Some(client);
+        return frame;
+    }
+
+    /// Returns true if the frame was received from a host through a client.
+    /// Such frames only borrow the host's buffer so they can be read but not
+    /// written, allocated or attached.
+    pub fn is_host_owned(&self) -> bool {
+        return self.host_owned;
+    }
+
+    /// Creates a frame whose buffer is memory already mapped in this process,
+    /// such as a buffer produced by another library, without copying it.
+    ///
+    /// The library can only reference buffers through a file descriptor so
+    /// the memory is known to this crate alone: the frame can be mapped with
+    /// [`Frame::mmap`] and used by the helpers which work on the mapping but
+    /// has no [`Frame::handle`], so it cannot be posted to a host or copied by
+    /// the library.  To publish external memory without a copy it must be
+    /// shared through a descriptor using [`Frame::attach`].  The buffer must
+    /// hold every plane of the format, otherwise
+    /// [`FrameError::BufferTooSmall`] is returned.
+    ///
+    /// # Safety
+    ///
+    /// The `len` bytes at `ptr` must be valid for reads and writes and must
+    /// outlive the frame, nor may they be accessed other than through the
+    /// frame while it is mapped.
+    pub unsafe fn from_raw_buffer(
+        ptr: *mut u8,
+        len: usize,
+        width: u32,
+        height: u32,
+        stride: u32,
+        fourcc: &str,
+    ) -> Result<Self, VslError> {
+        if ptr.is_null() {
+            return Err(VslError::from(FrameError::NullPointer));
+        }
+        let mut frame = Frame::new(width, height, stride, fourcc)?;
+        // Every plane must lie within the buffer, not only the first.
+        let required = frame.required_size();
+        if len < required {
+            return Err(VslError::from(FrameError::BufferTooSmall { required, len }));
+        }
+        frame.external = Some((ptr, len));
+        frame.state.set(FrameState::Allocated);
+        return Ok(frame);
+    }
+
+    /// Creates a frame whose buffer is the raw image in the file at `path`,
+    /// mapped without copying it, such as to feed golden images to tests.
+    /// The file holds the frame's planes as laid out by `stride`, without any
+    /// header, unlike the files written by [`Frame::save`].
+    ///
+    /// The mapping is private, so the file is never modified: pages written
+    /// through [`Frame::mmap_mut`] are copied for this process alone.  As
+    /// with [`Frame::from_raw_buffer`] the frame has no [`Frame::handle`] and
+    /// cannot be posted to a host.
+    pub fn from_file_mmap<P: AsRef<Path>>(
+        path: P,
+        width: u32,
+        height: u32,
+        stride: u32,
+        fourcc: &str,
+    ) -> Result<Self, VslError> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(VslError::from(FrameError::BufferTooSmall {
+                required: stride as usize * height as usize,
+                len,
+            }));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(VslError::from(FrameError::Io(io::Error::last_os_error())));
+        }
+
+        let mut frame = match unsafe {
+            Frame::from_raw_buffer(ptr as *mut u8, len, width, height, stride, fourcc)
+        } {
+            Ok(frame) => frame,
+            Err(err) => {
+                unsafe { libc::munmap(ptr, len) };
+                return Err(err);
+            }
+        };
+        // The frame unmaps the file from now on.
+        frame.file_mapped = true;
+        return Ok(frame);
+    }
+
+    /// Creates a new frame referencing the same underlying buffer through a
+    /// duplicate of its file descriptor, which the new frame owns.
+    pub(crate) fn share(&self) -> Result<Self, VslError> {
+        let handle = match self.handle() {
+            Some(handle) => handle,
+            None => return Err(FrameError::NoBuffer.into()),
+        };
+        let fd = unsafe { BorrowedFd::borrow_raw(handle) }.try_clone_to_owned()?;
+        let stride = self.stride();
+        let frame = Frame::init(
+            self.width() as u32,
+            self.height() as u32,
+            stride,
+            self.fourcc(),
+        )?;
+        frame.attach_owned(fd, self.buffer_size(), self.offset.get())?;
+        return Ok(frame);
+    }
+
+    /// Sets the pts reported by [`Frame::pts`] for a frame not yet posted,
+    /// such as one being recorded as it is posted.
+    pub(crate) fn set_pts(&mut self, pts: i64) {
+        self.imported_pts = Some(pts);
+    }
+
+    /// Reports the timestamps of `other` as the frame's own, for a frame built
+    /// from a received one.
+    pub(crate) fn copy_timing(&mut self, other: &Frame) {
+        self.timing = Some(other.timing());
+    }
+
+    /// Returns the metadata sent to clients ahead of the frame, see
+    /// [`crate::host::HostBuilder::control_frames`].
+    pub(crate) fn metadata(&self) -> Metadata {
+        return Metadata {
+            interlace: self.interlace,
+            color_space: self.color_space,
+            color_range: self.color_range,
+            orientation: self.orientation,
+            hdr: self.hdr_metadata(),
+        };
+    }
+
+    /// Sets the metadata sent by the host on a frame received from it.
+    pub(crate) fn set_metadata(&mut self, meta: &Metadata) {
+        self.interlace = meta.interlace;
+        self.color_space = meta.color_space;
+        self.color_range = meta.color_range;
+        self.orientation = meta.orientation;
+        self.hdr = meta.hdr.map(Box::new);
+    }
+
+    /// Splits the frame into `count` frames sharing its buffer, each through
+    /// its own duplicate of the descriptor so they can be mapped and dropped
+    /// independently, on different threads.  The frame itself is held until
+    /// the last of them is dropped, so a frame received from a host stays
+    /// locked as long as any of them is used.
+    ///
+    /// The library frame of each share is a new one, so the timestamps and
+    /// the state kept by this crate are carried over by the shares.
+    pub(crate) fn split(self, count: usize) -> Result<Vec<Frame>, VslError> {
+        let handle = match self.handle() {
+            Some(handle) => handle,
+            None => return Err(VslError::from(FrameError::NoBuffer)),
+        };
+        let timing = self.timing();
+        let stride = self.stride();
+        let fourcc = unsafe { ffi::vsl_frame_fourcc(self.ptr) };
+
+        let mut shares = Vec::with_capacity(count);
+        for _ in 0..count {
+            let fd = unsafe { BorrowedFd::borrow_raw(handle) }.try_clone_to_owned()?;
+            let mut share = Frame::init(self.width() as u32, self.height() as u32, stride, fourcc)?;
+            share.attach_owned(fd, self.buffer_size(), self.offset.get())?;
+            share.imported_pts = self.imported_pts;
+            share.interlace = self.interlace;
+            share.orientation = self.orientation;
+            share.color_space = self.color_space;
+            share.color_range = self.color_range;
+            share.hdr = self.hdr.clone();
+            share.layout = self.layout.clone();
+            share.reinterpreted = self.reinterpreted;
+            share.timing = Some(timing);
+            share.host_owned = self.host_owned;
+            share.client = self.client.clone();
+            shares.push(share);
+        }
+
+        let origin = Arc::new(SharedOrigin { _frame: self });
+        for share in &mut shares {
+            share.origin = Some(origin.clone());
+        }
+        return Ok(shares);
+    }
+
+    /// Consumes the frame without releasing it, transferring ownership of the
+    /// underlying library frame to the caller who becomes responsible for
+    /// calling `vsl_frame_release` or reclaiming it with [`Frame::from_raw`].
+    ///
+    /// A descriptor owned through [`Frame::attach_owned`], and the frame
+    /// shared by [`Frame::split`], are kept until the library releases the
+    /// frame, through its cleanup callback, for frames created by this crate.
+    /// Frames taken with [`Frame::from_raw`] have no such callback so their
+    /// descriptor is handed over to the caller to close.
+    ///
+    /// State kept by this crate alone is dropped, so a frame from
+    /// [`Frame::from_raw_buffer`] loses its mapping and a frame received from
+    /// a host is no longer known to be read-only.  Such a frame must be
+    /// released before its [`crate::client::Client`] is dropped.
+    pub fn into_raw(mut self) -> *mut ffi::VSLFrame {
+        let fd = self.owned_fd.take();
+        let origin = self.origin.take();
+        if self.release_hook {
+            if fd.is_some() || origin.is_some() {
+                let on_release = Box::new(move || {
+                    drop(fd);
+                    drop(origin);
+                });
+                unsafe { chain_release(self.ptr, on_release) };
+            }
+        } else if let Some(fd) = fd {
+            let _ = fd.into_raw_fd();
+        }
+        let ptr = self.ptr;
+        // Dropping a released frame keeps the library frame, while the state
+        // held by this crate is dropped with the Frame.
+        self.state.set(FrameState::Released);
+        return ptr;
+    }
+
+    /// Takes ownership of a library frame, the counterpart of
+    /// [`Frame::into_raw`].  The frame is released when dropped.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be a valid frame which is not released elsewhere,
+    /// including by another Frame.
+    pub unsafe fn from_raw(ptr: *mut ffi::VSLFrame) -> Self {
+        return Frame::from_ptr(ptr);
+    }
+
+    pub fn alloc(&self, path: Option<&Path>) -> Result<(), VslError> {
+        if self.host_owned {
+            return Err(VslError::from(FrameError::NotOwned));
+        }
+        self.require("allocate", &[FrameState::Unallocated])?;
+        self.require_buffer("allocate")?;
+        let path_ptr;
+        if let Some(path) = path {
+            let path = path.to_str().ok_or(VslError::InvalidString)?;
+            let path = CString::new(path)?;
+            path_ptr = path.into_raw();
+        } else {
+            path_ptr = ptr::null_mut();
+        }
+        let ret = unsafe { ffi::vsl_frame_alloc(self.ptr, path_ptr) } as i32;
+        if ret != 0 {
+            let err = io::Error::last_os_error();
+            return Err(alloc_error(err, self.required_size()));
+        }
+        self.transition(FrameState::Allocated);
+        #[cfg(feature = "fd-debug")]
+        if let Some(fd) = self.handle() {
+            fd_debug::trace("alloc", fd);
+        }
+        return Ok(());
+    }
+
+    /// Allocates the frame's buffer from the given backing.  Unlike
+    /// [`Frame::alloc`], whose path selects a dmabuf heap when it is under
+    /// `/dev` and shared memory otherwise, the backing states the intent and
+    /// only [`AllocBacking::Auto`] falls back to shared memory.  A dmabuf
+    /// reports [`FrameError::DmabufUnavailable`] when the heap does not exist
+    /// or cannot be opened, as in most containers unless the heap devices are
+    /// passed through.
+    pub fn alloc_with(&self, backing: AllocBacking) -> Result<(), VslError> {
+        match backing {
+            AllocBacking::DmaBuf(heap) => {
+                let heap = match heap {
+                    Some(heap) => heap,
+                    None => match DMA_HEAPS
+                        .iter()
+                        .map(PathBuf::from)
+                        .find(|heap| heap.exists())
+                    {
+                        Some(heap) => heap,
+                        None => return Err(VslError::from(FrameError::DmabufUnavailable)),
+                    },
+                };
+                // The library only treats paths under /dev as heaps.
+                if !heap.starts_with("/dev") || !heap.exists() {
+                    return Err(VslError::from(FrameError::DmabufUnavailable));
+                }
+                match self.alloc(Some(&heap)) {
+                    Err(VslError::AllocFailed(err))
+                        if err.kind() == io::ErrorKind::PermissionDenied =>
+                    {
+                        return Err(VslError::from(FrameError::DmabufUnavailable))
+                    }
+                    result => return result,
+                }
+            }
+            AllocBacking::SharedMemory(name) => {
+                let name = name.unwrap_or_else(|| return shm_name("frame"));
+                return self.alloc(Some(&name));
+            }
+            AllocBacking::Auto => return self.alloc(None),
+        }
+    }
+
+    /// Allocates the frame as [`Frame::alloc`] with the buffer size rounded up
+    /// to a multiple of `alignment` bytes, which [`Frame::buffer_size`] then
+    /// reports.
+    ///
+    /// Buffers always start on a page boundary, but importers which map the
+    /// buffer in larger units reject a size which is not a multiple of their
+    /// alignment.  Vulkan imports through external memory and VA-API surface
+    /// imports generally need 4096 bytes, while GPUs using large pages need
+    /// 65536 bytes.  The required alignment can be found in the importer's
+    /// memory requirements, such as `VkMemoryRequirements::alignment`.
+    pub fn alloc_aligned(&self, path: Option<&Path>, alignment: usize) -> Result<(), VslError> {
+        if !alignment.is_power_of_two() {
+            return Err(VslError::from(FrameError::InvalidAlignment(alignment)));
+        }
+        if self.host_owned {
+            return Err(VslError::from(FrameError::NotOwned));
+        }
+        self.require("allocate", &[FrameState::Unallocated])?;
+        self.require_buffer("allocate")?;
+        let stride = self.stride() as usize;
+        if stride == 0 {
+            return Err(VslError::from(FrameError::UnsupportedFourcc(self.fourcc())));
+        }
+        let size = self.required_size().next_multiple_of(alignment);
+        return self.alloc_size(path, size);
+    }
+
+    /// Allocates a buffer of `size` bytes for an unallocated frame.
+    fn alloc_size(&self, path: Option<&Path>, size: usize) -> Result<(), VslError> {
+        let stride = self.stride().max(1) as usize;
+        // The library sizes allocations by rows so a taller frame is allocated
+        // to cover the size, then its buffer is attached to this frame.
+        let rows = size.div_ceil(stride) as u32;
+        let padded = Frame::init(self.width() as u32, rows, stride as u32, self.fourcc())?;
+        padded.alloc(path)?;
+        let handle = match padded.handle() {
+            Some(handle) => handle,
+            None => return Err(VslError::from(FrameError::MapFailed)),
+        };
+        let fd = unsafe { BorrowedFd::borrow_raw(handle) }.try_clone_to_owned()?;
+        drop(padded);
+        return self.attach_owned(fd, size, 0);
+    }
+
+    /// Exports the frame's buffer, through a duplicate of its descriptor, along
+    /// with the layout needed to import it in another process.
+    ///
+    /// Frames received from a host cannot be exported as the host reclaims
+    /// their buffer once they expire.
+    pub fn export(&self) -> Result<FrameExport, VslError> {
+        if self.host_owned {
+            return Err(VslError::from(FrameError::NotOwned));
+        }
+        let handle = match self.handle() {
+            Some(handle) => handle,
+            None => return Err(VslError::from(FrameError::NoBuffer)),
+        };
+        let fd = unsafe { BorrowedFd::borrow_raw(handle) }.try_clone_to_owned()?;
+        let stride = self.stride();
+        return Ok(FrameExport {
+            fd,
+            size: self.buffer_size(),
+            offset: self.offset.get(),
+            width: self.width() as u32,
+            height: self.height() as u32,
+            stride,
+            fourcc: self.fourcc(),
+            pts: self.pts(),
+        });
+    }
+
+    /// Reconstructs a frame from an export, taking ownership of its
+    /// descriptor.  The exported pts is reported by [`Frame::pts`] as the
+    /// library only assigns timestamps when a frame is posted.
+    pub fn import(export: FrameExport) -> Result<Frame, VslError> {
+        let mut frame = Frame::init(export.width, export.height, export.stride, export.fourcc)?;
+        frame.attach_owned(export.fd, export.size, export.offset)?;
+        frame.imported_pts = Some(export.pts);
+        return Ok(frame);
+    }
+
+    /// Writes the frame's layout, pts and buffer to `writer`, for reading back
+    /// with [`Frame::load`].  Frames saved one after another to the same
+    /// writer are loaded in turn, as used by [`crate::playback::FileHost`].
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), VslError> {
+        let format = self.format();
+        let size = self.buffer_size();
+        let mem = self.mmap()?;
+        let data = match mem.get(..size) {
+            Some(data) => data,
+            None => return Err(VslError::from(FrameError::OutOfBounds)),
+        };
+
+        let mut header = Vec::with_capacity(SAVE_HEADER_LEN);
+        header.extend_from_slice(SAVE_MAGIC);
+        header.extend_from_slice(&SAVE_VERSION.to_le_bytes());
+        for value in [format.width, format.height, format.stride, format.fourcc] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&self.pts().to_le_bytes());
+        header.extend_from_slice(&(size as u64).to_le_bytes());
+        writer.write_all(&header)?;
+        writer.write_all(data)?;
+        return Ok(());
+    }
+
+    /// Reads a frame written by [`Frame::save`] into a newly allocated frame.
+    /// The saved pts is reported by [`Frame::pts`] as with [`Frame::import`].
+    pub fn load<R: Read>(reader: &mut R) -> Result<Frame, VslError> {
+        let mut header = [0u8; SAVE_HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+        if &header[..4] != SAVE_MAGIC || u32_at(4) != SAVE_VERSION {
+            return Err(VslError::from(FrameError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a saved frame",
+            ))));
+        }
+        let (width, height, stride, fourcc) = (u32_at(8), u32_at(12), u32_at(16), u32_at(20));
+        let pts = u64_at(24) as i64;
+        let len = u64_at(32) as usize;
+
+        let mut frame = Frame::init(width, height, stride, fourcc)?;
+        frame.alloc(None)?;
+        let mem = frame.mmap_mut()?;
+        let data = match mem.get_mut(..len) {
+            Some(data) => data,
+            None => {
+                return Err(VslError::from(FrameError::BufferTooSmall {
+                    required: len,
+                    len: mem.len(),
+                }))
+            }
+        };
+        reader.read_exact(data)?;
+        frame.imported_pts = Some(pts);
+        return Ok(frame);
+    }
+
+    pub fn wrap(ptr: *mut ffi::VSLFrame) -> Result<Self, VslError> {
+        if ptr.is_null() {
+            return Err(VslError::NullPointer);
+        }
+
+        return Ok(Frame::from_ptr(ptr));
+    }
+
+    /// Releases the underlying library frame ahead of the Frame being dropped.
+    /// Afterwards every operation which needs the frame's buffer fails with
+    /// [`FrameError::InvalidState`].
+    pub fn release(&self) {
+        if self.state.get() == FrameState::Released {
+            return;
+        }
+        self.unmap_outstanding();
+        unsafe { ffi::vsl_frame_release(self.ptr) };
+        self.transition(FrameState::Released);
+    }
+
+    /// Unmaps the file behind a frame made by [`Frame::from_file_mmap`], once
+    /// the library frame using it was released.
+    fn unmap_file(&self) {
+        if let (true, Some((ptr, len))) = (self.file_mapped, self.external) {
+            unsafe { libc::munmap(ptr as *mut c_void, len) };
+        }
+    }
+
+    /// Unmaps the frame if it may still be mapped, so a frame released without
+    /// calling [`Frame::munmap`] does not leak its mapping.
+    fn unmap_outstanding(&self) {
+        let mapped = match self.state.get() {
+            FrameState::Mapped => true,
+            FrameState::Locked => self.locked_mapped.get(),
+            _ => false,
+        };
+        if mapped {
+            unsafe { ffi::vsl_frame_munmap(self.ptr) };
+        }
+    }
+
+    pub fn wait(client: &client::Client, until: i64) -> Result<Self, VslError> {
+        return client.receive(until);
+    }
+
+    /// Locks the frame's buffer without waiting.  A buffer already locked by
+    /// another user fails with [`FrameError::WouldBlock`], which may be
+    /// retried, while other errors are not transient.
+    pub fn trylock(&self) -> Result<(), VslError> {
+        let state = self.require("lock", &[FrameState::Allocated, FrameState::Mapped])?;
+        let ret = unsafe { ffi::vsl_frame_trylock(self.ptr) };
+        if ret != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                return Err(VslError::from(FrameError::WouldBlock));
+            }
+            return Err(VslError::from(FrameError::Io(err)));
+        }
+        self.locked_mapped.set(state == FrameState::Mapped);
+        self.transition(FrameState::Locked);
+        return Ok(());
+    }
+
+    /// Waits up to `timeout` until the producer has finished writing the
+    /// frame, so it can be mapped without reading a partly written buffer.
+    /// The frame is ready once the write fence of a dmabuf, see
+    /// [`Frame::acquire_fence`], has signalled and the producer has released
+    /// its lock, see [`Frame::trylock`].  Returns [`FrameError::Timeout`] if
+    /// the frame did not become ready in time.
+    ///
+    /// The lock is polled, as the library cannot wait for it, so the wait may
+    /// end up to a millisecond after the producer unlocks.  A frame locked by
+    /// this Frame itself cannot become ready and is rejected.
+    pub fn wait_ready(&self, timeout: Duration) -> Result<(), VslError> {
+        self.require("wait for", &[FrameState::Allocated, FrameState::Mapped])?;
+        let deadline = Instant::now() + timeout;
+        if let Some(fence) = self.acquire_fence() {
+            if !sync_file::wait_until(fence.as_fd(), Some(deadline))? {
+                return Err(VslError::from(FrameError::Timeout));
+            }
+        }
+        loop {
+            match self.trylock() {
+                Ok(()) => {
+                    self.unlock()?;
+                    return Ok(());
+                }
+                Err(VslError::Frame(FrameError::WouldBlock)) if Instant::now() < deadline => {
+                    thread::sleep(LOCK_POLL_INTERVAL);
+                }
+                Err(VslError::Frame(FrameError::WouldBlock)) => {
+                    return Err(VslError::from(FrameError::Timeout))
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Locks the frame as [`Frame::trylock`], returning a guard which unlocks
+    /// it when dropped so an early return cannot leave the frame locked.
+    pub fn locked(&self) -> Result<FrameLockGuard<'_>, VslError> {
+        self.trylock()?;
+        return Ok(FrameLockGuard { frame: self });
+    }
+
+    pub fn unlock(&self) -> Result<(), VslError> {
+        self.require("unlock", &[FrameState::Locked])?;
+        if unsafe { ffi::vsl_frame_unlock(self.ptr) as i32 } == -1 {
+            let err = io::Error::last_os_error();
+            return Err(VslError::from(err));
+        }
+        // The mapping outlives the lock.
+        if self.locked_mapped.get() {
+            self.transition(FrameState::Mapped);
+        } else {
+            self.transition(FrameState::Allocated);
+        }
+        return Ok(());
+    }
+
+    /// Returns true while the frame is locked by [`Frame::trylock`], during
+    /// which it cannot be posted to a host.
+    pub fn is_locked(&self) -> bool {
+        return self.state.get() == FrameState::Locked;
+    }
+
+    fn timing(&self) -> Timing {
+        return Timing {
+            serial: self.serial(),
+            timestamp: self.timestamp(),
+            duration: self.duration(),
+            pts: self.pts(),
+            dts: self.dts(),
+            expires: self.expires(),
+        };
+    }
+
+    pub fn serial(&self) -> i64 {
+        if let Some(timing) = self.timing {
+            return timing.serial;
+        }
+        return unsafe { ffi::vsl_frame_serial(self.ptr) };
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        if let Some(timing) = self.timing {
+            return timing.timestamp;
+        }
+        let timestamp: i64 = unsafe { ffi::vsl_frame_timestamp(self.ptr) };
+        return timestamp;
+    }
+
+    /// Returns the time elapsed since the frame's timestamp, measured using
+    /// the library clock.  Frames timestamped in the future have an age of
+    /// zero.
+    pub fn age(&self) -> Duration {
+        let age = crate::timestamp() - self.timestamp();
+        return Duration::from_nanos(age.max(0) as u64);
+    }
+
+    /// Returns true if the frame is older than `max_age`.
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        return self.age() > max_age;
+    }
+
+    pub fn duration(&self) -> i64 {
+        if let Some(timing) = self.timing {
+            return timing.duration;
+        }
+        return unsafe { ffi::vsl_frame_duration(self.ptr) };
+    }
+
+    pub fn pts(&self) -> i64 {
+        if let Some(pts) = self.imported_pts {
+            return pts;
+        }
+        if let Some(timing) = self.timing {
+            return timing.pts;
+        }
+        return unsafe { ffi::vsl_frame_pts(self.ptr) };
+    }
+
+    pub fn dts(&self) -> i64 {
+        if let Some(timing) = self.timing {
+            return timing.dts;
+        }
+        return unsafe { ffi::vsl_frame_dts(self.ptr) };
+    }
+
+    /// Returns the presentation timestamp rescaled into the `num/den` seconds
+    /// timebase, see [`Timebase`] to also rescale the dts and duration.
+    pub fn pts_in_timebase(&self, num: i64, den: i64) -> i64 {
+        return Timebase::new(num, den).pts(self);
+    }
+
+    /// Sets the field order of the frame's content, sent to clients as
+    /// [metadata](crate::host::HostBuilder::control_frames).
+    pub fn set_interlaced(&mut self, mode: Interlace) {
+        self.interlace = mode;
+    }
+
+    /// Returns the field order set by [`Frame::set_interlaced`].
+    pub fn interlaced(&self) -> Interlace {
+        return self.interlace;
+    }
+
+    /// Sets where the samples of a P010 frame sit within their words, for a
+    /// buffer filled by a source delivering them in the low bits.  The layout
+    /// is known to this process only, so frames received from a host are
+    /// always [`BitLayout::Msb`].
+    pub fn set_bit_layout(&mut self, layout: BitLayout) {
+        self.bit_layout = layout;
+    }
+
+    /// Returns the layout set by [`Frame::set_bit_layout`].
+    pub fn bit_layout(&self) -> BitLayout {
+        return self.bit_layout;
+    }
+
+    /// Sets the rotation which displays the frame's content upright, which
+    /// renderers may apply themselves or through [`Frame::apply_orientation`].
+    /// It is sent to clients as
+    /// [metadata](crate::host::HostBuilder::control_frames).
+    pub fn set_orientation(&mut self, rot: Rotation) {
+        self.orientation = rot;
+    }
+
+    /// Returns the rotation set by [`Frame::set_orientation`].
+    pub fn orientation(&self) -> Rotation {
+        return self.orientation;
+    }
+
+    /// Sets the color space of the frame's YUV content, used by
+    /// [`Frame::convert_to`] and sent to clients as
+    /// [metadata](crate::host::HostBuilder::control_frames).
+    pub fn set_color_space(&mut self, cs: ColorSpace) {
+        self.color_space = Some(cs);
+    }
+
+    /// Returns the color space set by [`Frame::set_color_space`], otherwise
+    /// BT.709 for high definition frames taller than 576 rows and BT.601 for
+    /// standard definition frames.
+    pub fn color_space(&self) -> ColorSpace {
+        match self.color_space {
+            Some(cs) => return cs,
+            None if self.height() > 576 => return ColorSpace::Bt709,
+            None => return ColorSpace::Bt601,
+        }
+    }
+
+    /// Sets the range of the frame's YUV content, used by
+    /// [`Frame::convert_to`] and sent to clients as
+    /// [metadata](crate::host::HostBuilder::control_frames).
+    pub fn set_color_range(&mut self, range: ColorRange) {
+        self.color_range = Some(range);
+    }
+
+    /// Returns the range set by [`Frame::set_color_range`], otherwise limited
+    /// range.
+    pub fn color_range(&self) -> ColorRange {
+        return self.color_range.unwrap_or(ColorRange::Limited);
+    }
+
+    /// Sets the static HDR metadata of the frame's content, carried to the
+    /// frames copied from it, reported by [`crate::encoder::EncodeOutput`] and
+    /// sent to clients as [metadata](crate::host::HostBuilder::control_frames).
+    pub fn set_hdr_metadata(&mut self, meta: HdrMetadata) {
+        self.hdr = Some(Box::new(meta));
+    }
+
+    /// Returns the metadata set by [`Frame::set_hdr_metadata`].
+    pub fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        return self.hdr.as_deref().copied();
+    }
+
+    /// Marks the rectangle as changed since the previous frame posted, so
+    /// hosts built with [`crate::host::HostBuilder::partial_updates`] send
+    /// clients only the marked regions.  The rectangle is given in pixels and
+    /// covers the matching part of each plane, rounded out to whole elements
+    /// for subsampled chroma.  It is constrained to the frame, and marking a
+    /// rectangle outside of it has no effect.
+    ///
+    /// The regions are known to this process only and do not change the
+    /// frame's content, which must still be complete as hosts post it in full
+    /// when clients could lack the previous frame.
+    pub fn mark_dirty(&mut self, rect: VSLRect) {
+        let rect = rect.clamp(self.width(), self.height());
+        if rect.get_width() > 0 && rect.get_height() > 0 {
+            self.dirty.push(rect);
+        }
+    }
+
+    /// Returns the rectangles marked by [`Frame::mark_dirty`].
+    pub fn dirty_regions(&self) -> &[VSLRect] {
+        return &self.dirty;
+    }
+
+    pub fn expires(&self) -> i64 {
+        if let Some(timing) = self.timing {
+            return timing.expires;
+        }
+        return unsafe { ffi::vsl_frame_expires(self.ptr) };
+    }
+
+    pub fn fourcc(&self) -> u32 {
+        if let Some(fourcc) = self.reinterpreted {
+            return fourcc;
+        }
+        return unsafe { ffi::vsl_frame_fourcc(self.ptr) };
+    }
+
+    /// Tags the frame with another fourcc whose layout is identical, such as
+    /// a YUY2 frame received as YUYV, without copying it.  Both formats must
+    /// be known and have the same planes with the same bytes per row for the
+    /// frame's geometry, otherwise [`FrameError::IncompatibleFourcc`] is
+    /// returned and the frame is unchanged.
+    ///
+    /// The library's frame descriptor cannot be retagged, so as with the field
+    /// order the new fourcc is known to this process only and frames posted to
+    /// a host keep their original fourcc.
+    pub fn reinterpret_fourcc(&mut self, fourcc: format::FourCC) -> Result<(), VslError> {
+        let from = self.fourcc();
+        let stride = self.stride();
+        let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        // Frames with a planar layout keep their own strides and offsets, which
+        // suit the new format when its default layout matches too.
+        let current = format::planes(from, width, height, stride);
+        let target = format::planes(fourcc, width, height, stride);
+        match (current, target) {
+            (Some(current), Some(target)) if current == target => {}
+            _ => {
+                return Err(VslError::from(FrameError::IncompatibleFourcc {
+                    from,
+                    to: fourcc,
+                }))
+            }
+        }
+        self.reinterpreted = Some(fourcc);
+        return Ok(());
+    }
+
+    /// Returns a sync_file which signals once the producer has finished writing
+    /// to the frame, posted with [`crate::host::Host::post_frame_with_fence`].
+    /// Wait for it to become readable with poll, or import it as a Vulkan
+    /// semaphore or EGL sync, before reading the frame.  A fence which has
+    /// already signalled is returned when no write is pending.
+    ///
+    /// Returns None for frames which are not dmabufs, or on kernels older than
+    /// 6.0, whose hosts wait on the fence before posting instead.
+    pub fn acquire_fence(&self) -> Option<OwnedFd> {
+        return sync_file::export_read_fence(self.handle()?).ok();
+    }
+
+    /// Returns the frame's geometry and pixel format.  Each frame received
+    /// from a host carries its own, so a stream may change format.
+    pub fn format(&self) -> FrameFormat {
+        let stride = self.stride();
+        return FrameFormat {
+            width: self.width().max(0) as u32,
+            height: self.height().max(0) as u32,
+            stride,
+            fourcc: self.fourcc(),
+        };
+    }
+
+    pub fn width(&self) -> i32 {
+        let width: std::os::raw::c_int = unsafe { ffi::vsl_frame_width(self.ptr) };
+        return width as i32;
+    }
+
+    pub fn height(&self) -> i32 {
+        let height: std::os::raw::c_int = unsafe { ffi::vsl_frame_height(self.ptr) };
+        return height as i32;
+    }
+
+    /// Returns the bytes of the frame's buffer, saturating at `i32::MAX`
+    /// for buffers beyond 2 GiB whose size is given by
+    /// [`Frame::buffer_size`].
+    pub fn size(&self) -> i32 {
+        return self.buffer_size().min(i32::MAX as usize) as i32;
+    }
+
+    /// Returns the bytes of the frame's buffer as [`Frame::size`] without
+    /// its limit, for frames beyond 2 GiB such as 8K at high bit depths or
+    /// gigapixel scientific images.
+    ///
+    /// The library reports sizes as an int, so the size of a larger buffer is
+    /// the size it was attached with or otherwise the size its planes
+    /// require, which the library allocates.
+    pub fn buffer_size(&self) -> usize {
+        if let Some((_, len)) = self.external {
+            return len;
+        }
+        if self.attached_len.get() > 0 {
+            return self.attached_len.get();
+        }
+        let required = self.required_size();
+        if required > i32::MAX as usize {
+            return required;
+        }
+        return unsafe { ffi::vsl_frame_size(self.ptr) }.max(0) as usize;
+    }
+
+    /// Returns the bytes from the start of one row of the frame to the next,
+    /// which hardware aligning its rows makes larger than the row's pixels.
+    /// Rows of the buffer from [`Frame::mmap`] must be indexed by the stride.
+    /// For planar formats this is the first plane's stride, see
+    /// [`Frame::stride_plane`] for the others.
+    pub fn stride(&self) -> u32 {
+        return unsafe { ffi::vsl_frame_stride(self.ptr) }.max(0) as u32;
+    }
+
+    /// Returns the stride of the plane, or None for a plane the format does
+    /// not have and for compressed and unknown formats.  Frames created by
+    /// [`Frame::new_planar`] report the stride given for each plane,
+    /// otherwise the library's single stride sets the layout of every plane,
+    /// such as half of it for the chroma planes of I420.
+    pub fn stride_plane(&self, index: usize) -> Option<u32> {
+        let plane = self.planes().ok()?.get(index).copied()?;
+        return Some(plane.stride as u32);
+    }
+
+    /// Returns the description of the frame's pixel format, or None for
+    /// compressed and unknown formats.
+    pub fn format_info(&self) -> Option<format::PixelFormatInfo> {
+        return format::format_info(self.fourcc());
+    }
+
+    /// Returns the bytes needed for the frame's buffer, from its plane layout
+    /// when the format is known otherwise its stride and height.
+    fn required_size(&self) -> usize {
+        if let Ok(planes) = self.planes() {
+            return planes.iter().map(|plane| plane.end()).max().unwrap_or(0);
+        }
+        let stride = self.stride();
+        return stride as usize * self.height().max(0) as usize;
+    }
+
+    /// Returns the layout of the frame's planes within its buffer.
+    pub(crate) fn planes(&self) -> Result<Vec<format::Plane>, VslError> {
+        if let Some(layout) = &self.layout {
+            return Ok(layout.clone());
+        }
+        let stride = self.stride();
+        let planes = format::planes(
+            self.fourcc(),
+            self.width() as u32,
+            self.height() as u32,
+            stride,
+        );
+        return planes.ok_or(VslError::from(FrameError::UnsupportedFourcc(self.fourcc())));
+    }
+
+    /// Returns the layout of a single plane.
+    fn plane(&self, plane: usize) -> Result<format::Plane, VslError> {
+        let planes = self.planes()?;
+        match planes.get(plane) {
+            Some(layout) => return Ok(*layout),
+            None => {
+                return Err(VslError::from(FrameError::InvalidPlane {
+                    plane,
+                    planes: planes.len(),
+                }))
+            }
+        }
+    }
+
+    /// Returns the descriptor, offset, stride and size of each of the frame's
+    /// planes, everything importers such as EGL, Vulkan and DRM need to
+    /// import the buffer, taken together so they cannot disagree.
+    ///
+    /// Unlike [`Frame::plane_offset`] the offsets are from the start of the
+    /// descriptor, including the offset the buffer was attached at, as
+    /// importers expect.  The size of each plane includes the padding of its
+    /// rows.  Compressed and unknown formats are described as a single plane
+    /// covering the buffer.  The descriptor is None for frames without one,
+    /// such as those from [`Frame::from_raw_buffer`], and remains owned by
+    /// the frame.
+    pub fn planes_layout(&self) -> Vec<PlaneLayout> {
+        let fd = self.handle();
+        let base = self.offset.get();
+        let planes = match self.planes() {
+            Ok(planes) => planes,
+            Err(_) => {
+                let stride = self.stride();
+                return vec![PlaneLayout {
+                    fd,
+                    offset: base,
+                    stride,
+                    size: self.buffer_size(),
+                }];
+            }
+        };
+        return planes
+            .iter()
+            .map(|plane| {
+                return PlaneLayout {
+                    fd,
+                    offset: base + plane.offset,
+                    stride: plane.stride as u32,
+                    size: plane.end() - plane.offset,
+                };
+            })
+            .collect();
+    }
+
+    /// Returns the offset in bytes of the plane from the start of the frame's
+    /// buffer.
+    pub fn plane_offset(&self, plane: usize) -> Result<usize, VslError> {
+        return Ok(self.plane(plane)?.offset);
+    }
+
+    /// Returns the plane's data from its first row up to the end of its last
+    /// row's pixels, including the padding between rows.
+    pub fn plane_slice(&self, plane: usize) -> Result<&[u8], VslError> {
+        let layout = self.plane(plane)?;
+        let mem = self.mmap()?;
+        return mem
+            .get(layout.offset..layout.offset + layout.len())
+            .ok_or(VslError::from(FrameError::OutOfBounds));
+    }
+
+    /// Copies the pixel data of every plane into a vector, in plane order and
+    /// without any row padding.
+    pub fn to_vec(&self) -> Result<Vec<u8>, VslError> {
+        let planes = self.planes()?;
+        return copy_planes(self.mmap()?, &planes);
+    }
+
+    /// Copies the pixel data as [`Frame::to_vec`] into `dst`, or the whole
+    /// buffer for formats without a known layout such as compressed frames,
+    /// returning the number of bytes copied.  Fails with
+    /// [`FrameError::BufferTooSmall`] when `dst` cannot hold them.
+    pub fn copy_to_slice(&self, dst: &mut [u8]) -> Result<usize, VslError> {
+        let mem = self.mmap()?;
+        let planes = self.planes().ok();
+        let required = match &planes {
+            Some(planes) => packed_len(planes),
+            None => self.buffer_size().min(mem.len()),
+        };
+        if dst.len() < required {
+            return Err(VslError::from(FrameError::BufferTooSmall {
+                required,
+                len: dst.len(),
+            }));
+        }
+        match &planes {
+            Some(planes) => copy_planes_into(mem, planes, dst)?,
+            None => dst[..required].copy_from_slice(&mem[..required]),
+        }
+        return Ok(required);
+    }
+
+    /// Returns a view of the rectangle within the frame whose rows borrow the
+    /// frame's mapped buffer, such as the region of interest passed to an
+    /// inference model, without allocating or copying any pixels.
+    ///
+    /// The rectangle must lie within the frame and, for subsampled formats,
+    /// start on a chroma sample so the view shares the parent's chroma.  Use
+    /// [`Frame::crop_copy`] when the region must be a frame of its own, for
+    /// example to post it to a host.
+    pub fn crop_view(&self, rect: &VSLRect) -> Result<CroppedFrame<'_>, VslError> {
+        let (x, y) = (rect.get_x(), rect.get_y());
+        let (width, height) = (rect.get_width(), rect.get_height());
+        if x < 0 || y < 0 || width <= 0 || height <= 0 {
+            return Err(VslError::from(FrameError::OutOfBounds));
+        }
+        let (x, y, width, height) = (x as u32, y as u32, width as u32, height as u32);
+        if x + width > self.width().max(0) as u32 || y + height > self.height().max(0) as u32 {
+            return Err(VslError::from(FrameError::OutOfBounds));
+        }
+
+        let fourcc = self.fourcc();
+        let (x_align, y_align) = match format::format_info(fourcc).map(|info| info.subsampling) {
+            Some(format::Subsampling::None) => (1, 1),
+            Some(format::Subsampling::Yuv422) => (2, 1),
+            Some(format::Subsampling::Yuv420) => (2, 2),
+            None => return Err(VslError::from(FrameError::UnsupportedFourcc(fourcc))),
+        };
+        if x % x_align != 0 || y % y_align != 0 {
+            return Err(VslError::from(FrameError::MisalignedRegion { x, y }));
+        }
+
+        // The region's origin, as a frame of the pixels before it, gives the
+        // bytes and rows to skip in each plane while the region's own layout
+        // gives the bytes and rows to keep.
+        let parent = self.planes()?;
+        let origin = format::planes(fourcc, x, y, 0).ok_or(FrameError::OutOfBounds)?;
+        let region = format::planes(fourcc, width, height, 0).ok_or(FrameError::OutOfBounds)?;
+        let planes = parent
+            .iter()
+            .zip(origin.iter().zip(&region))
+            .map(|(parent, (origin, region))| {
+                return format::Plane {
+                    offset: parent.offset + origin.rows * parent.stride + origin.row_bytes,
+                    stride: parent.stride,
+                    row_bytes: region.row_bytes,
+                    rows: region.rows,
+                };
+            })
+            .collect();
+
+        return Ok(CroppedFrame {
+            parent: self,
+            mem: self.mmap()?,
+            planes,
+            width,
+            height,
+        });
+    }
+
+    /// Copies the frame into a new frame backed by POSIX shared memory, which
+    /// is available on every platform the library supports.  This suits
+    /// consumers which map frames through a named object rather than a
+    /// descriptor.
+    pub fn to_shm_copy(&self) -> Result<Frame, VslError> {
+        return self.alloc_copy(Some(&shm_name("copy")));
+    }
+
+    /// Copies the frame into a new dmabuf allocated from a dmabuf heap, for
+    /// interop which requires one such as importing into a GPU, NPU or video
+    /// encoder.  Dmabuf heaps are provided by Linux 5.6 and later through
+    /// `/dev/dma_heap`, notably on embedded SoCs, and
+    /// [`FrameError::DmabufUnavailable`] is returned on systems without them,
+    /// including most containers unless the heap devices are passed through.
+    pub fn to_dmabuf_copy(&self) -> Result<Frame, VslError> {
+        let heap = DMA_HEAPS.iter().map(Path::new).find(|heap| heap.exists());
+        let heap = match heap {
+            Some(heap) => heap,
+            None => return Err(VslError::from(FrameError::DmabufUnavailable)),
+        };
+        match self.alloc_copy(Some(heap)) {
+            Err(VslError::AllocFailed(err)) if err.kind() == io::ErrorKind::PermissionDenied => {
+                return Err(VslError::from(FrameError::DmabufUnavailable))
+            }
+            result => return result,
+        }
+    }
+
+    /// Copies the frame into `dst`, converting to its fourcc and scaling to its
+    /// dimensions through the library's `vsl_frame_copy`, which uses hardware
+    /// accelerators where available.  Both frames must have a buffer known to
+    /// the library, allocated or attached, and the library maps them as
+    /// required so neither needs to be mapped beforehand.  Only the pixels are
+    /// copied, `dst` keeps its own timestamps and color description.
+    ///
+    /// Conversions the library does not support fail with an OS error, see
+    /// [`Frame::convert_to`] for a software conversion to RGB.
+    pub fn copy_to(&self, dst: &mut Frame) -> Result<(), VslError> {
+        if dst.host_owned {
+            return Err(FrameError::ReadOnly.into());
+        }
+        let states = [
+            FrameState::Allocated,
+            FrameState::Mapped,
+            FrameState::Locked,
+        ];
+        self.require("copy", &states)?;
+        dst.require("copy into", &states)?;
+        if self.handle().is_none() || dst.handle().is_none() {
+            return Err(FrameError::NoBuffer.into());
+        }
+        let ret = unsafe { ffi::vsl_frame_copy(dst.ptr, self.ptr, ptr::null()) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        return Ok(());
+    }
+
+    /// Copies the frame into a new frame allocated at `path`, a dmabuf heap
+    /// device or a shared memory name, or as [`Frame::alloc`] without one.
+    pub(crate) fn alloc_copy(&self, path: Option<&Path>) -> Result<Frame, VslError> {
+        let src = self.mmap()?;
+        let format = self.format();
+        let mut frame = Frame::init(format.width, format.height, format.stride, format.fourcc)?;
+        frame.alloc(path)?;
+        frame.layout = self.layout.clone();
+        frame.interlace = self.interlace;
+        frame.orientation = self.orientation;
+        frame.color_space = self.color_space;
+        frame.color_range = self.color_range;
+        frame.hdr = self.hdr.clone();
+
+        let dst = frame.mmap_mut()?;
+        let len = src.len().min(dst.len());
+        dst[..len].copy_from_slice(&src[..len]);
+        return Ok(frame);
+    }
+
+    /// Copies the rectangle within the frame into a newly allocated frame of
+    /// the same format, for regions which must outlive the frame or be posted
+    /// to a host.  The rectangle is constrained as for [`Frame::crop_view`].
+    pub fn crop_copy(&self, rect: &VSLRect) -> Result<Frame, VslError> {
+        let view = self.crop_view(rect)?;
+        let mut frame = Frame::init(view.width, view.height, 0, self.fourcc())?;
+        frame.alloc(None)?;
+        frame.interlace = self.interlace;
+        frame.orientation = self.orientation;
+        frame.color_space = self.color_space;
+        frame.color_range = self.color_range;
+        frame.hdr = self.hdr.clone();
+
+        let planes = frame.planes()?;
+        let mem = frame.mmap_mut()?;
+        for (index, plane) in planes.iter().enumerate() {
+            for y in 0..plane.rows {
+                let start = plane.offset + y * plane.stride;
+                let dst = mem
+                    .get_mut(start..start + plane.row_bytes)
+                    .ok_or(FrameError::OutOfBounds)?;
+                dst.copy_from_slice(view.row(index, y as u32)?);
+            }
+        }
+        return Ok(frame);
+    }
+
+    /// Copies the frame into a newly allocated frame with its
+    /// [`Frame::orientation`] applied, so the copy is upright and has the
+    /// orientation [`Rotation::None`].  The frame must be a packed RGB,
+    /// greyscale or planar YUV format, those with horizontally subsampled
+    /// chroma only such as NV16 cannot be rotated by 90 or 270 degrees.
+    pub fn apply_orientation(&self) -> Result<Frame, VslError> {
+        let fourcc = self.fourcc();
+        let elements = element_bytes(fourcc).ok_or(FrameError::UnsupportedFourcc(fourcc))?;
+        let rot = self.orientation;
+        let (mut width, mut height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        if rot.transposes() {
+            (width, height) = (height, width);
+        }
+        let mut frame = Frame::init(width, height, 0, fourcc)?;
+        frame.alloc(None)?;
+        frame.timing = Some(self.timing());
+        frame.color_space = self.color_space;
+        frame.color_range = self.color_range;
+        frame.hdr = self.hdr.clone();
+
+        let src_planes = self.planes()?;
+        let dst_planes = frame.planes()?;
+        let src = self.mmap()?;
+        let dst = frame.mmap_mut()?;
+        for ((src_plane, dst_plane), bytes) in src_planes.iter().zip(&dst_planes).zip(elements) {
+            let (cols, rows) = (src_plane.row_bytes / bytes, src_plane.rows);
+            let (dst_cols, dst_rows) = (dst_plane.row_bytes / bytes, dst_plane.rows);
+            let expected = match rot.transposes() {
+                true => (rows, cols),
+                false => (cols, rows),
+            };
+            if (dst_cols, dst_rows) != expected {
+                return Err(VslError::from(FrameError::UnsupportedFourcc(fourcc)));
+            }
+            for y in 0..dst_rows {
+                for x in 0..dst_cols {
+                    let (sx, sy) = rot.source(x, y, cols, rows);
+                    let from = src_plane.offset + sy * src_plane.stride + sx * bytes;
+                    let to = dst_plane.offset + y * dst_plane.stride + x * bytes;
+                    let pixel = src.get(from..from + bytes).ok_or(FrameError::OutOfBounds)?;
+                    dst.get_mut(to..to + bytes)
+                        .ok_or(FrameError::OutOfBounds)?
+                        .copy_from_slice(pixel);
+                }
+            }
+        }
+        return Ok(frame);
+    }
+
+    /// Downscales the frame by an integer factor into a newly allocated frame
+    /// of the same format, taking every `factor`th pixel of every `factor`th
+    /// row without filtering.  This is far cheaper than a resize for
+    /// thumbnails and analysis at a reduced resolution, at the cost of
+    /// aliasing.  The factor must evenly divide the width and height, use
+    /// [`Frame::subsample_floor`] to drop the remaining pixels instead.  The
+    /// frame must be one of the formats supported by
+    /// [`Frame::apply_orientation`].
+    pub fn subsample(&self, factor: u32) -> Result<Frame, VslError> {
+        let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        if factor == 0 || width % factor != 0 || height % factor != 0 {
+            return Err(VslError::from(FrameError::InvalidFactor(factor)));
+        }
+        return self.subsample_floor(factor);
+    }
 
-impl Frame {
-    pub fn new(
-        width: u32,
-        height: u32,
-        stride: u32,
-        fourcc_str: &str,
-    ) -> Result<Self, Box<dyn Error>> {
-        let buf = fourcc_str.as_bytes();
-        if buf.len() != 4 {
-            return Err("fourcc must be 4 character ascii code".into());
+    /// Downscales the frame as [`Frame::subsample`], for factors which need
+    /// not divide the dimensions, discarding the columns and rows beyond the
+    /// last multiple of the factor.
+    pub fn subsample_floor(&self, factor: u32) -> Result<Frame, VslError> {
+        let fourcc = self.fourcc();
+        let elements = element_bytes(fourcc).ok_or(FrameError::UnsupportedFourcc(fourcc))?;
+        let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        if factor == 0 || factor > width || factor > height {
+            return Err(VslError::from(FrameError::InvalidFactor(factor)));
         }
-        let mut fourcc: u32 = 0;
-        for i in 0..buf.len() {
-            fourcc += (buf[i] as u32) << i * 8;
+        let mut frame = Frame::init(width / factor, height / factor, 0, fourcc)?;
+        frame.alloc(None)?;
+        // Sampled rows no longer alternate between fields, so the interlacing
+        // is not carried.
+        frame.timing = Some(self.timing());
+        frame.orientation = self.orientation;
+        frame.color_space = self.color_space;
+        frame.color_range = self.color_range;
+        frame.hdr = self.hdr.clone();
+
+        let factor = factor as usize;
+        let src_planes = self.planes()?;
+        let dst_planes = frame.planes()?;
+        let src = self.mmap()?;
+        let dst = frame.mmap_mut()?;
+        for ((src_plane, dst_plane), bytes) in src_planes.iter().zip(&dst_planes).zip(elements) {
+            let (cols, rows) = (src_plane.row_bytes / bytes, src_plane.rows);
+            for y in 0..dst_plane.rows {
+                // Subsampled chroma of odd dimensions may round up past the
+                // last sampled element of the source.
+                let sy = (y * factor).min(rows - 1);
+                for x in 0..dst_plane.row_bytes / bytes {
+                    let sx = (x * factor).min(cols - 1);
+                    let from = src_plane.offset + sy * src_plane.stride + sx * bytes;
+                    let to = dst_plane.offset + y * dst_plane.stride + x * bytes;
+                    let pixel = src.get(from..from + bytes).ok_or(FrameError::OutOfBounds)?;
+                    dst.get_mut(to..to + bytes)
+                        .ok_or(FrameError::OutOfBounds)?
+                        .copy_from_slice(pixel);
+                }
+            }
         }
+        return Ok(frame);
+    }
 
-        let ptr = unsafe {
-            ffi::vsl_frame_init(width, height, stride, fourcc, std::ptr::null_mut(), None)
+    /// Scales a planar or semi-planar YUV frame, NV12, NV21, NV16, I420 or
+    /// YV12, to `width` by `height` into a newly allocated frame of the same
+    /// format.  Each plane is scaled on its own at its subsampled size, so a
+    /// thumbnail of a camera's NV12 frames needs neither a conversion to RGB
+    /// nor one back.  Each destination sample averages the source samples it
+    /// covers, or repeats the nearest when enlarging.  Interleaved chroma is
+    /// scaled as pairs so its components are never mixed.
+    pub fn resize_yuv(&self, width: u32, height: u32) -> Result<Frame, VslError> {
+        let fourcc = self.fourcc();
+        let yuv = matches!(
+            fourcc,
+            format::NV12 | format::NV21 | format::NV16 | format::I420 | format::YV12
+        );
+        let elements = match element_bytes(fourcc) {
+            Some(elements) if yuv => elements,
+            _ => return Err(VslError::from(FrameError::UnsupportedFourcc(fourcc))),
         };
+        let mut frame = Frame::init(width, height, 0, fourcc)?;
+        frame.alloc(None)?;
+        // Scaled rows no longer alternate between fields, so the interlacing
+        // is not carried.
+        frame.timing = Some(self.timing());
+        frame.orientation = self.orientation;
+        frame.color_space = self.color_space;
+        frame.color_range = self.color_range;
+        frame.hdr = self.hdr.clone();
 
-        if ptr.is_null() {
-            let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+        let src_planes = self.planes()?;
+        let dst_planes = frame.planes()?;
+        let src = self.mmap()?;
+        let dst = frame.mmap_mut()?;
+        for ((src_plane, dst_plane), bytes) in src_planes.iter().zip(&dst_planes).zip(elements) {
+            let (src_cols, dst_cols) = (src_plane.row_bytes / bytes, dst_plane.row_bytes / bytes);
+            let spans: Vec<Range<usize>> = (0..dst_cols)
+                .map(|x| return covered(x, src_cols, dst_cols))
+                .collect();
+            for y in 0..dst_plane.rows {
+                let rows = covered(y, src_plane.rows, dst_plane.rows);
+                for (x, cols) in spans.iter().enumerate() {
+                    let count = (rows.len() * cols.len()) as u32;
+                    for channel in 0..bytes {
+                        let mut sum = 0;
+                        for sy in rows.clone() {
+                            let row = src_plane.offset + sy * src_plane.stride;
+                            for sx in cols.clone() {
+                                let from = row + sx * bytes + channel;
+                                sum += *src.get(from).ok_or(FrameError::OutOfBounds)? as u32;
+                            }
+                        }
+                        let to = dst_plane.offset + y * dst_plane.stride + x * bytes + channel;
+                        *dst.get_mut(to).ok_or(FrameError::OutOfBounds)? =
+                            ((sum + count / 2) / count) as u8;
+                    }
+                }
+            }
         }
-        return Ok(Frame { ptr });
+        return Ok(frame);
     }
 
-    pub fn alloc(&self, path: Option<&Path>) -> Result<(), Box<dyn Error>> {
-        let path_ptr;
-        if let Some(path) = path {
-            let path = path.to_str().unwrap();
-            let path = CString::new(path).unwrap();
-            path_ptr = path.into_raw();
-        } else {
-            path_ptr = ptr::null_mut();
-        }
-        let ret = unsafe { ffi::vsl_frame_alloc(self.ptr, path_ptr) } as i32;
-        if ret != 0 {
-            let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+    /// Repacks a P010 frame into a newly allocated P010 frame whose samples
+    /// are in the given layout, shifting each 10 bit sample between the high
+    /// and low bits of its word.  Bits outside the sample are cleared, so a
+    /// frame repacked to its own layout is a masked copy.
+    pub fn repack_10bit(&self, layout: BitLayout) -> Result<Frame, VslError> {
+        if self.fourcc() != format::P010 {
+            return Err(VslError::from(FrameError::UnsupportedFourcc(self.fourcc())));
         }
-        return Ok(());
+        let mut frame = self.convert_words(format::P010, |word| {
+            let sample = self.sample_10bit(word);
+            if layout == BitLayout::Lsb {
+                return sample >> 6;
+            }
+            return sample;
+        })?;
+        frame.bit_layout = layout;
+        return Ok(frame);
     }
 
-    pub fn wrap(ptr: *mut ffi::VSLFrame) -> Result<Self, ()> {
-        if ptr.is_null() {
-            return Err(());
+    /// Widens a P010 frame to a newly allocated P016 frame, the samples read
+    /// in the frame's [`Frame::bit_layout`].  The 10 bits are replicated into
+    /// the low bits, so black and white remain 0 and 0xffff.
+    pub fn to_16bit(&self) -> Result<Frame, VslError> {
+        if self.fourcc() != format::P010 {
+            return Err(VslError::from(FrameError::UnsupportedFourcc(self.fourcc())));
         }
-
-        return Ok(Frame { ptr });
+        return self.convert_words(format::P016, |word| {
+            let sample = self.sample_10bit(word) >> 6;
+            return sample << 6 | sample >> 4;
+        });
     }
 
-    pub fn release(&self) {
-        unsafe { ffi::vsl_frame_release(self.ptr) };
+    /// Narrows a P016 frame to a newly allocated P010 frame with its samples
+    /// in the high bits, keeping the 10 most significant bits of each.  This
+    /// is the exact inverse of [`Frame::to_16bit`].
+    pub fn to_10bit(&self) -> Result<Frame, VslError> {
+        if self.fourcc() != format::P016 {
+            return Err(VslError::from(FrameError::UnsupportedFourcc(self.fourcc())));
+        }
+        return self.convert_words(format::P010, |word| return word & 0xffc0);
     }
 
-    pub fn wait(client: &client::Client, until: i64) -> Result<Self, Box<dyn Error>> {
-        let wrapper = client.get_frame(until)?;
-        return Ok(Frame { ptr: wrapper.ptr });
+    /// Returns a 10 bit sample of the frame in the high bits of its word.
+    fn sample_10bit(&self, word: u16) -> u16 {
+        if self.bit_layout == BitLayout::Lsb {
+            return (word & 0x3ff) << 6;
+        }
+        return word & 0xffc0;
     }
 
-    pub fn trylock(&self) -> Result<(), Box<dyn Error>> {
-        let ret = unsafe { ffi::vsl_frame_trylock(self.ptr) };
-        if ret != 0 {
-            let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+    /// Converts each 16 bit word of the frame's planes into a newly allocated
+    /// frame of `fourcc` with the same dimensions and plane layout.
+    fn convert_words<F>(&self, fourcc: format::FourCC, convert: F) -> Result<Frame, VslError>
+    where
+        F: Fn(u16) -> u16,
+    {
+        let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        let mut frame = Frame::init(width, height, width * 2, fourcc)?;
+        // The library does not know the size of the chroma plane.
+        frame.require_buffer("allocate")?;
+        frame.alloc_size(None, frame.required_size())?;
+        frame.timing = Some(self.timing());
+        frame.interlace = self.interlace;
+        frame.orientation = self.orientation;
+        frame.color_space = self.color_space;
+        frame.color_range = self.color_range;
+        frame.hdr = self.hdr.clone();
+
+        let src_planes = self.planes()?;
+        let dst_planes = frame.planes()?;
+        let src = self.mmap()?;
+        let dst = frame.mmap_mut()?;
+        for (src_plane, dst_plane) in src_planes.iter().zip(&dst_planes) {
+            for y in 0..dst_plane.rows {
+                let from = src_plane.offset + y * src_plane.stride;
+                let to = dst_plane.offset + y * dst_plane.stride;
+                let src_row = src
+                    .get(from..from + src_plane.row_bytes)
+                    .ok_or(FrameError::OutOfBounds)?;
+                let dst_row = dst
+                    .get_mut(to..to + dst_plane.row_bytes)
+                    .ok_or(FrameError::OutOfBounds)?;
+                for (from, to) in src_row.chunks_exact(2).zip(dst_row.chunks_exact_mut(2)) {
+                    let word = convert(u16::from_le_bytes([from[0], from[1]]));
+                    to.copy_from_slice(&word.to_le_bytes());
+                }
+            }
         }
-        return Ok(());
+        return Ok(frame);
     }
 
-    pub fn unlock(&self) -> Result<(), Box<dyn Error>> {
-        if unsafe { ffi::vsl_frame_unlock(self.ptr) as i32 } == -1 {
-            let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+    /// Returns the pixel data of row `y` of the given plane, excluding any
+    /// padding at the end of the row.  Planar formats number their planes in
+    /// memory order, for example NV12 has the luma plane 0 and the interleaved
+    /// chroma plane 1 which has half the rows.
+    pub fn row(&self, plane: usize, y: u32) -> Result<&[u8], VslError> {
+        let layout = self.plane(plane)?;
+        if y as usize >= layout.rows {
+            return Err(VslError::from(FrameError::OutOfBounds));
         }
-        return Ok(());
+
+        let mem = self.mmap()?;
+        let start = layout.offset + y as usize * layout.stride;
+        return mem
+            .get(start..start + layout.row_bytes)
+            .ok_or(VslError::from(FrameError::OutOfBounds));
     }
 
-    pub fn serial(&self) -> i64 {
-        return unsafe { ffi::vsl_frame_serial(self.ptr) };
+    /// Returns the channel layout and the single plane of a packed RGB frame.
+    fn rgb_layout(&self) -> Result<(format::Channels, format::Plane), VslError> {
+        let channels = match format::channels(self.fourcc()) {
+            Some(channels) => channels,
+            None => return Err(VslError::from(FrameError::UnsupportedFourcc(self.fourcc()))),
+        };
+        return Ok((channels, self.planes()?[0]));
     }
 
-    pub fn timestamp(&self) -> i64 {
-        let timestamp: i64 = unsafe { ffi::vsl_frame_timestamp(self.ptr) };
-        return timestamp;
+    /// Returns the single plane of a packed RGB or greyscale frame with the
+    /// bytes of a pixel of the color.  Greyscale frames take the color's luma
+    /// in the frame's color space and range, with Y16 scaling it to 16 bits.
+    fn pixel_layout(&self, color: Rgba) -> Result<(Vec<u8>, format::Plane), VslError> {
+        let fourcc = self.fourcc();
+        let pixel = match format::channels(fourcc) {
+            Some(channels) => {
+                let mut pixel = vec![0u8; channels.bytes];
+                write_pixel(&mut pixel, &channels, color);
+                pixel
+            }
+            None if matches!(fourcc, format::GREY | format::Y8 | format::Y16) => {
+                let mut luma = color::luma(self.color_space(), color) as u32;
+                if self.color_range() == ColorRange::Limited {
+                    luma = 16 + (luma * 219 + 127) / 255;
+                }
+                if fourcc == format::Y16 {
+                    ((luma * 257) as u16).to_le_bytes().to_vec()
+                } else {
+                    vec![luma as u8]
+                }
+            }
+            None => return Err(VslError::from(FrameError::UnsupportedFourcc(fourcc))),
+        };
+        return Ok((pixel, self.planes()?[0]));
     }
 
-    pub fn duration(&self) -> i64 {
-        return unsafe { ffi::vsl_frame_duration(self.ptr) };
+    /// Fills the frame with the color, which must be one of the packed RGB or
+    /// greyscale formats.  The alpha channel is written for RGBA and BGRA
+    /// frames and ignored by formats without one.
+    pub fn fill_color(&mut self, color: Rgba) -> Result<(), VslError> {
+        let (pixel, plane) = self.pixel_layout(color)?;
+        let mem = self.mmap_mut()?;
+        return fill_region(mem, &plane, &pixel, 0..i64::MAX, 0..i64::MAX);
     }
 
-    pub fn pts(&self) -> i64 {
-        return unsafe { ffi::vsl_frame_pts(self.ptr) };
+    /// Converts the frame to a newly allocated frame of a packed RGB or
+    /// greyscale fourcc, for [`client::ClientOptions::deliver_as`], through
+    /// [`Frame::convert_to`] or [`Frame::to_gray`].  The new frame keeps the
+    /// frame's timestamps and field order.
+    pub(crate) fn convert_into(&self, fourcc: format::FourCC) -> Result<Frame, VslError> {
+        let mut frame = match fourcc {
+            format::GREY | format::Y8 => {
+                let mut gray = self.to_gray()?;
+                gray.reinterpret_fourcc(fourcc)?;
+                gray
+            }
+            _ => {
+                let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+                let mut rgb = Frame::init(width, height, 0, fourcc)?;
+                rgb.alloc(None)?;
+                self.convert_to(&mut rgb)?;
+                rgb
+            }
+        };
+        frame.timing = Some(self.timing());
+        frame.interlace = self.interlace;
+        frame.orientation = self.orientation;
+        return Ok(frame);
     }
 
-    pub fn dts(&self) -> i64 {
-        return unsafe { ffi::vsl_frame_dts(self.ptr) };
+    /// Sets the acknowledgement sent to the host once the frame is dropped.
+    pub(crate) fn set_ack(&mut self, ack: PendingAck) {
+        self.ack = Some(Box::new(ack));
     }
 
-    pub fn expires(&self) -> i64 {
-        return unsafe { ffi::vsl_frame_expires(self.ptr) };
+    /// Counts the values of each of the frame's channels into `bins` bins,
+    /// from 1 to 256, such as for auto-exposure.  Each pixel is counted once
+    /// per channel, so the chroma of subsampled YUV formats is counted for
+    /// every pixel sharing it.  Y16 frames are counted by their high byte.
+    pub fn histogram(&self, bins: usize) -> Result<Histogram, VslError> {
+        let bins = bins.clamp(1, 256);
+        let sampler = Sampler::new(self)?;
+        let count = match self.fourcc() {
+            format::GREY | format::Y8 | format::Y16 => 1,
+            _ => 3,
+        };
+        let mut channels = vec![vec![0u64; bins]; count];
+        let src = self.mmap()?;
+        for y in 0..self.height().max(0) as usize {
+            for x in 0..self.width().max(0) as usize {
+                let values = match sampler.sample(src, x, y)? {
+                    Sample::Rgba(color) => [color.r, color.g, color.b],
+                    Sample::Yuv(luma, u, v) => [luma, u, v],
+                };
+                for (channel, value) in channels.iter_mut().zip(values) {
+                    channel[value as usize * bins / 256] += 1;
+                }
+            }
+        }
+        return Ok(Histogram { channels });
     }
 
-    pub fn fourcc(&self) -> u32 {
-        return unsafe { ffi::vsl_frame_fourcc(self.ptr) };
+    /// Returns the mean luma of the frame, from 0 to 255, taken from the Y
+    /// plane of YUV frames and computed with the frame's
+    /// [`Frame::color_space`] for RGB frames.
+    pub fn luma_mean(&self) -> Result<f64, VslError> {
+        let sampler = Sampler::new(self)?;
+        let space = self.color_space();
+        let (width, height) = (self.width().max(0) as usize, self.height().max(0) as usize);
+        if width == 0 || height == 0 {
+            return Ok(0.0);
+        }
+        let src = self.mmap()?;
+        let mut sum = 0u64;
+        for y in 0..height {
+            for x in 0..width {
+                sum += match sampler.sample(src, x, y)? {
+                    Sample::Rgba(color) => color::luma(space, color),
+                    Sample::Yuv(luma, _, _) => luma,
+                } as u64;
+            }
+        }
+        return Ok(sum as f64 / (width * height) as f64);
     }
 
-    pub fn width(&self) -> i32 {
-        let width: std::os::raw::c_int = unsafe { ffi::vsl_frame_width(self.ptr) };
-        return width as i32;
+    /// Converts the frame to a newly allocated GREY frame of its luma, such as
+    /// for detectors which only take a single channel.  YUV frames keep their
+    /// luma and range while RGB frames are converted with the frame's
+    /// [`Frame::color_space`] into full range luma.
+    pub fn to_gray(&self) -> Result<Frame, VslError> {
+        let sampler = Sampler::new(self)?;
+        let (width, height) = (self.width().max(0) as u32, self.height().max(0) as u32);
+        let mut gray = Frame::init(width, height, 0, format::GREY)?;
+        gray.alloc(None)?;
+        gray.color_space = self.color_space;
+        gray.color_range = match sampler.channels {
+            Some(_) => Some(ColorRange::Full),
+            None => self.color_range,
+        };
+
+        let space = self.color_space();
+        let plane = gray.planes()?[0];
+        let src = self.mmap()?;
+        let mem = gray.mmap_mut()?;
+        for y in 0..plane.rows {
+            let start = plane.offset + y * plane.stride;
+            let row = match mem.get_mut(start..start + plane.row_bytes) {
+                Some(row) => row,
+                None => return Err(VslError::from(FrameError::OutOfBounds)),
+            };
+            for (x, luma) in row.iter_mut().enumerate() {
+                *luma = match sampler.sample(src, x, y)? {
+                    Sample::Rgba(color) => color::luma(space, color),
+                    Sample::Yuv(luma, _, _) => luma,
+                };
+            }
+        }
+        return Ok(gray);
     }
 
-    pub fn height(&self) -> i32 {
-        let height: std::os::raw::c_int = unsafe { ffi::vsl_frame_height(self.ptr) };
-        return height as i32;
+    /// Draws the outline of the rectangle in the color, with lines
+    /// `thickness` pixels wide inside the rectangle's edges.  A thickness of
+    /// half the rectangle's width or height or more fills the rectangle.  The
+    /// frame must be one of the packed RGB or greyscale formats and the
+    /// rectangle is clipped to the frame's bounds.
+    pub fn draw_rect(
+        &mut self,
+        rect: &VSLRect,
+        color: Rgb,
+        thickness: u32,
+    ) -> Result<(), VslError> {
+        let (pixel, plane) = self.pixel_layout(color.into())?;
+
+        let x0 = rect.get_x() as i64;
+        let y0 = rect.get_y() as i64;
+        let x1 = x0 + rect.get_width() as i64;
+        let y1 = y0 + rect.get_height() as i64;
+        if x1 <= x0 || y1 <= y0 || thickness == 0 {
+            return Ok(());
+        }
+        // Thicker lines would cross the opposite edge.
+        let t = (thickness as i64)
+            .min((x1 - x0 + 1) / 2)
+            .min((y1 - y0 + 1) / 2);
+
+        let mem = self.mmap_mut()?;
+        fill_region(mem, &plane, &pixel, x0..x1, y0..y0 + t)?;
+        fill_region(mem, &plane, &pixel, x0..x1, y1 - t..y1)?;
+        fill_region(mem, &plane, &pixel, x0..x0 + t, y0..y1)?;
+        fill_region(mem, &plane, &pixel, x1 - t..x1, y0..y1)?;
+        return Ok(());
     }
 
-    pub fn size(&self) -> i32 {
-        return unsafe { ffi::vsl_frame_size(self.ptr) as i32 }; //Needs work
+    /// Draws each rectangle as [`Frame::draw_rect`], such as the bounding
+    /// boxes from a detector.
+    pub fn draw_rects(&mut self, rects: &[(VSLRect, Rgb)], thickness: u32) -> Result<(), VslError> {
+        for (rect, color) in rects {
+            self.draw_rect(rect, *color, thickness)?;
+        }
+        return Ok(());
+    }
+
+    /// Composites the RGBA or BGRA overlay onto the frame with its top-left
+    /// corner at `x`, `y`, using straight alpha-over blending.  The overlay is
+    /// clipped to the frame's bounds.
+    pub fn blend_over(&mut self, overlay: &Frame, x: u32, y: u32) -> Result<(), VslError> {
+        let (channels, plane) = self.rgb_layout()?;
+        let (overlay_channels, overlay_plane) = overlay.rgb_layout()?;
+        if overlay_channels.alpha.is_none() {
+            return Err(VslError::from(FrameError::UnsupportedFourcc(
+                overlay.fourcc(),
+            )));
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        let columns = (self.width().max(0) as usize)
+            .saturating_sub(x)
+            .min(overlay.width().max(0) as usize);
+        let rows = plane.rows.saturating_sub(y).min(overlay_plane.rows);
+
+        let src = overlay.mmap()?;
+        let dst = self.mmap_mut()?;
+        for row in 0..rows {
+            let src_start = overlay_plane.offset + row * overlay_plane.stride;
+            let dst_start = plane.offset + (y + row) * plane.stride + x * channels.bytes;
+            let src_row = src.get(src_start..src_start + columns * overlay_channels.bytes);
+            let dst_row = dst.get_mut(dst_start..dst_start + columns * channels.bytes);
+            let (src_row, dst_row) = match (src_row, dst_row) {
+                (Some(src_row), Some(dst_row)) => (src_row, dst_row),
+                _ => return Err(VslError::from(FrameError::OutOfBounds)),
+            };
+
+            let pixels = src_row
+                .chunks_exact(overlay_channels.bytes)
+                .zip(dst_row.chunks_exact_mut(channels.bytes));
+            for (src, dst) in pixels {
+                let color = alpha_over(
+                    read_pixel(src, &overlay_channels),
+                    read_pixel(dst, &channels),
+                );
+                write_pixel(dst, &channels, color);
+            }
+        }
+        return Ok(());
     }
 
-    /*
-    pub fn stride(&self) -> i32 {
-        return unsafe { ffi::vsl_frame_stride(self.ptr) as i32};
+    /// Converts the frame into `dst`, which must be one of the packed RGB
+    /// formats and have the same dimensions.  YUV frames are converted using
+    /// their [`Frame::color_space`] and [`Frame::color_range`], with chroma
+    /// taken from the nearest sample.
+    ///
+    /// This is a software conversion for formats and platforms which the
+    /// library's accelerated `vsl_frame_copy` does not handle.
+    pub fn convert_to(&self, dst: &mut Frame) -> Result<(), VslError> {
+        if self.width() != dst.width() || self.height() != dst.height() {
+            return Err(VslError::from(FrameError::DimensionMismatch));
+        }
+        let (channels, plane) = dst.rgb_layout()?;
+        let width = self.width().max(0) as usize;
+        let matrix = YuvMatrix::new(self.color_space(), self.color_range());
+        let sampler = Sampler::new(self)?;
+        // NV12 rows are converted a row at a time, using SIMD where supported,
+        // into separate channels before being packed.
+        let mut channel_rows = match self.fourcc() {
+            format::NV12 => vec![vec![0u8; width]; 3],
+            _ => Vec::new(),
+        };
+
+        let src = self.mmap()?;
+        let mem = dst.mmap_mut()?;
+        for y in 0..plane.rows {
+            let start = plane.offset + y * plane.stride;
+            let row = match mem.get_mut(start..start + plane.row_bytes) {
+                Some(row) => row,
+                None => return Err(VslError::from(FrameError::OutOfBounds)),
+            };
+            if let [r, g, b] = channel_rows.as_mut_slice() {
+                sampler.nv12_row(src, y, &matrix, [r, g, b])?;
+                for (x, pixel) in row.chunks_exact_mut(channels.bytes).enumerate().take(width) {
+                    write_pixel(pixel, &channels, Rgba::new(r[x], g[x], b[x], 255));
+                }
+                continue;
+            }
+            for (x, pixel) in row.chunks_exact_mut(channels.bytes).enumerate().take(width) {
+                let color = match sampler.sample(src, x, y)? {
+                    Sample::Rgba(color) => color,
+                    Sample::Yuv(luma, u, v) => matrix.convert(luma, u, v),
+                };
+                write_pixel(pixel, &channels, color);
+            }
+        }
+        return Ok(());
     }
-    */
 
     pub fn handle(&self) -> Option<i32> {
         let handle: std::os::raw::c_int = unsafe { ffi::vsl_frame_handle(self.ptr) };
         if handle == -1 {
             return None;
         }
+        #[cfg(feature = "fd-debug")]
+        fd_debug::trace("handle", handle);
         return Some(handle as i32);
     }
 
-    pub fn paddr(&self) -> Option<isize> {
+    /// Returns true if both frames refer to the same buffer, such as the
+    /// branches of a [`crate::client::Client::tee`] sharing a received frame,
+    /// rather than merely holding equal pixels.  Frames sharing a buffer hold
+    /// their own duplicates of its descriptor, so the descriptors are compared
+    /// by the file they refer to, which is cheaper than comparing the pixels.
+    pub fn same_buffer(&self, other: &Frame) -> bool {
+        if self.ptr == other.ptr {
+            return true;
+        }
+        if let (Some((ptr, _)), Some((other_ptr, _))) = (self.external, other.external) {
+            return ptr == other_ptr;
+        }
+        let (handle, other_handle) = match (self.handle(), other.handle()) {
+            (Some(handle), Some(other_handle)) => (handle, other_handle),
+            _ => return false,
+        };
+        let file = |handle: i32| {
+            let mut stat: libc::stat = unsafe { mem::zeroed() };
+            if unsafe { libc::fstat(handle, &mut stat) } != 0 {
+                return None;
+            }
+            return Some((stat.st_dev, stat.st_ino));
+        };
+        match (file(handle), file(other_handle)) {
+            (Some(file), Some(other_file)) => {
+                return file == other_file && self.offset.get() == other.offset.get();
+            }
+            _ => return false,
+        }
+    }
+
+    /// Returns the physical address of the frame's buffer, see
+    /// [`Frame::physical_address`].
+    pub fn paddr(&self) -> Option<u64> {
         let ret = unsafe { ffi::vsl_frame_paddr(self.ptr) };
         if ret == -1 {
             return None;
         }
-        return Some(ret);
+        // The library returns the address as a signed integer, so addresses
+        // above the sign bit of 32-bit targets are negative.
+        return Some(ret as usize as u64);
+    }
+
+    /// Returns the physical address of the frame's buffer, for programming
+    /// peripherals which read memory directly such as a DMA engine.  Only
+    /// physically contiguous buffers, those allocated from the CMA heap, have
+    /// one, other buffers return None.
+    ///
+    /// The address is only valid while the frame holds its buffer and knows
+    /// nothing of any IOMMU between the peripheral and memory.
+    pub fn physical_address(&self) -> Option<PhysAddr> {
+        return self.paddr().map(PhysAddr);
     }
 
     pub fn path(&self) -> Option<&str> {
@@ -181,64 +2634,709 @@ impl Frame {
         return Some(path);
     }
 
-    pub fn mmap(&self) -> Result<&[u8], ()> {
-        if self.handle() == None {
-            return Err(());
+    /// Maps the frame for reading, the mapping remaining until
+    /// [`Frame::munmap`] which must not be called while the slice is used.
+    /// [`Frame::map`] is preferred, as its guard unmaps the frame itself.
+    pub fn mmap(&self) -> Result<&[u8], VslError> {
+        let (ptr, len) = self.map_buffer()?;
+        self.mmap_pinned.set(true);
+        return Ok(unsafe { slice::from_raw_parts(ptr, len) });
+    }
+
+    /// Maps the frame for reading, returning a guard which dereferences to
+    /// the buffer and unmaps it when dropped, so the buffer can never be used
+    /// once unmapped.  Guards taken together share the mapping, which remains
+    /// until the last is dropped, and [`Frame::munmap`] leaves it in place
+    /// meanwhile.  A frame mapped by [`Frame::mmap`], before or while the
+    /// guards are held, stays mapped until [`Frame::munmap`] as its slices
+    /// may still be in use.
+    pub fn map(&self) -> Result<MmapGuard<'_>, VslError> {
+        let unmapped = self.is_unmapped();
+        let (ptr, len) = self.map_buffer()?;
+        let shared = unmapped || self.guards.get() > 0;
+        if shared {
+            self.guards.set(self.guards.get() + 1);
+        }
+        return Ok(MmapGuard {
+            data: ptr,
+            len,
+            mapping: None,
+            frame: if shared { Some(self) } else { None },
+            _frame: PhantomData,
+        });
+    }
+
+    /// Maps the frame for writing as [`Frame::map`].  The guard borrows the
+    /// frame mutably, so it is the only mapping in use until dropped.  Frames
+    /// received from a host are read-only and return [`FrameError::ReadOnly`].
+    pub fn map_mut(&mut self) -> Result<MmapMutGuard<'_>, VslError> {
+        if self.host_owned {
+            return Err(VslError::from(FrameError::ReadOnly));
+        }
+        let unmapped = self.is_unmapped();
+        let (ptr, len) = self.map_buffer()?;
+        return Ok(MmapMutGuard {
+            data: ptr,
+            len,
+            unmap: unmapped,
+            frame: self,
+        });
+    }
+
+    /// Maps only the `len` bytes at `offset` within the frame's buffer for
+    /// reading, such as to scan a header or a single tile of a large frame
+    /// without mapping all of it.  The range must be within
+    /// [`Frame::buffer_size`].
+    ///
+    /// The buffer's descriptor is mapped directly from the page holding the
+    /// range's first byte, independently of [`Frame::mmap`], so the frame's
+    /// state is unchanged.  Frames wrapping a caller's memory, such as by
+    /// [`Frame::from_raw_buffer`], borrow the range from it instead.
+    pub fn map_range(&self, offset: usize, len: usize) -> Result<MmapGuard<'_>, VslError> {
+        self.require(
+            "map_range",
+            &[
+                FrameState::Allocated,
+                FrameState::Mapped,
+                FrameState::Locked,
+            ],
+        )?;
+        let size = self.buffer_size();
+        match offset.checked_add(len) {
+            Some(end) if end <= size => {}
+            _ => return Err(VslError::from(FrameError::OutOfBounds)),
+        }
+        let guard = |data: *const u8, mapping| {
+            return MmapGuard {
+                data,
+                len,
+                mapping,
+                frame: None,
+                _frame: PhantomData,
+            };
+        };
+        if let Some((ptr, _)) = self.external {
+            return Ok(guard(unsafe { ptr.add(offset) }, None));
+        }
+        if len == 0 {
+            return Ok(guard(ptr::NonNull::dangling().as_ptr(), None));
+        }
+
+        let fd = self.handle().ok_or(FrameError::NoBuffer)?;
+        let start = self.offset.get() + offset;
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize;
+        let base = start - start % page;
+        let map_len = start - base + len;
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                map_len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                base as libc::off_t,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(VslError::from(FrameError::Io(io::Error::last_os_error())));
+        }
+        let data = unsafe { (ptr as *const u8).add(start - base) };
+        return Ok(guard(data, Some((ptr, map_len))));
+    }
+
+    /// Maps the frame for writing, as [`Frame::mmap`] for reading, where
+    /// [`Frame::map_mut`] is preferred.  The frame is borrowed mutably so the
+    /// slice is the only reference to the buffer while it is used.  Frames
+    /// received from a host are read-only and return [`FrameError::ReadOnly`].
+    pub fn mmap_mut(&mut self) -> Result<&mut [u8], VslError> {
+        if self.host_owned {
+            return Err(VslError::from(FrameError::ReadOnly));
         }
-        let mut size: usize = 0;
-        let ptr = unsafe { ffi::vsl_frame_mmap(self.ptr, &mut size as *mut usize) };
-        if ptr.is_null() || size == 0 {
-            return Err(());
+        let (ptr, len) = self.map_buffer()?;
+        return Ok(unsafe { slice::from_raw_parts_mut(ptr, len) });
+    }
+
+    /// Maps the frame's buffer, moving the frame into the mapped state, and
+    /// returns its address and length.  The library keeps the mapping so
+    /// later calls return the same memory.
+    fn map_buffer(&self) -> Result<(*mut u8, usize), VslError> {
+        let state = self.require(
+            "map",
+            &[
+                FrameState::Allocated,
+                FrameState::Mapped,
+                FrameState::Locked,
+            ],
+        )?;
+        let mapping = match self.external {
+            Some(mapping) => mapping,
+            None => {
+                let mut size: usize = 0;
+                let ptr = unsafe { ffi::vsl_frame_mmap(self.ptr, &mut size as *mut usize) };
+                if ptr.is_null() || size == 0 {
+                    return Err(VslError::from(FrameError::MapFailed));
+                }
+                (ptr as *mut u8, size)
+            }
+        };
+        match state {
+            FrameState::Allocated => self.transition(FrameState::Mapped),
+            FrameState::Locked => self.locked_mapped.set(true),
+            _ => {}
         }
-        return Ok(unsafe { slice::from_raw_parts(ptr as *const u8, size) });
+        return Ok(mapping);
     }
 
-    pub fn mmap_mut(&self) -> Result<&mut [u8], ()> {
-        if self.handle() == None {
-            return Err(());
+    /// Returns true if the frame has a buffer which is not mapped, locked or
+    /// not, so a guard mapping it unmaps it again.
+    fn is_unmapped(&self) -> bool {
+        match self.state.get() {
+            FrameState::Allocated => return true,
+            FrameState::Locked => return !self.locked_mapped.get(),
+            _ => return false,
         }
-        let mut size: usize = 0;
-        let ptr = unsafe { ffi::vsl_frame_mmap(self.ptr, &mut size as *mut usize) };
-        if ptr.is_null() || size == 0 {
-            return Err(());
+    }
+
+    /// Drops a guard of [`Frame::map`] sharing the mapping it made, unmapping
+    /// the frame once the last is dropped unless [`Frame::mmap`] was called.
+    fn release_guard(&self) {
+        self.guards.set(self.guards.get() - 1);
+        if !self.mmap_pinned.get() {
+            self.munmap();
         }
-        return Ok(unsafe { slice::from_raw_parts_mut(ptr as *mut u8, size) });
     }
 
+    /// Unmaps the frame mapped by [`Frame::mmap`], unless guards returned by
+    /// [`Frame::map`] still use the mapping, in which case the last of them
+    /// unmaps it.
     pub fn munmap(&self) {
+        self.mmap_pinned.set(false);
+        if self.guards.get() > 0 {
+            return;
+        }
+        match self.state.get() {
+            FrameState::Mapped => self.transition(FrameState::Allocated),
+            FrameState::Locked if self.locked_mapped.get() => self.locked_mapped.set(false),
+            _ => return,
+        }
         return unsafe { ffi::vsl_frame_munmap(self.ptr) };
     }
 
-    pub fn attach(&self, fd: RawFd, size: usize, offset: usize) -> Result<(), Box<dyn Error>> {
+    /// Attaches the frame to the buffer referenced by the file descriptor.
+    ///
+    /// The descriptor is borrowed, the caller retains ownership and must keep
+    /// it open for as long as the frame uses it then close it afterwards.  Use
+    /// [`Frame::attach_owned`] to hand the descriptor over to the frame.
+    pub fn attach(&self, fd: RawFd, size: usize, offset: usize) -> Result<(), VslError> {
+        if self.host_owned {
+            return Err(VslError::from(FrameError::NotOwned));
+        }
+        // Attaching replaces the buffer, which would invalidate its mapping.
+        self.require("attach", &[FrameState::Unallocated, FrameState::Allocated])?;
+        self.require_buffer("attach")?;
+        if let Some(layout) = &self.layout {
+            let required = layout
+                .iter()
+                .map(|plane| plane.offset + plane.len())
+                .max()
+                .unwrap_or(0);
+            if size < required {
+                return Err(VslError::from(FrameError::BufferTooSmall {
+                    required,
+                    len: size,
+                }));
+            }
+        }
         let ret = unsafe { ffi::vsl_frame_attach(self.ptr, fd, size, offset) };
         if ret < 0 {
             let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+            return Err(VslError::from(err));
+        }
+        self.offset.set(offset);
+        self.attached_len.set(size);
+        self.transition(FrameState::Allocated);
+        #[cfg(feature = "fd-debug")]
+        fd_debug::trace("attach", fd);
+        return Ok(());
+    }
+
+    /// Attaches the frame to the buffer referenced by the file descriptor,
+    /// taking ownership of it.  The descriptor is closed when the frame is
+    /// dropped, or when the frame is attached to another owned descriptor.
+    pub fn attach_owned(&self, fd: OwnedFd, size: usize, offset: usize) -> Result<(), VslError> {
+        self.attach(fd.as_raw_fd(), size, offset)?;
+        if let Some(_old) = self.owned_fd.replace(Some(fd)) {
+            #[cfg(feature = "fd-debug")]
+            fd_debug::trace("close", _old.as_raw_fd());
         }
         return Ok(());
     }
 
+    /// Returns the underlying library frame for use with the videostream-sys
+    /// functions while the Frame keeps ownership, it must not be released.
+    /// Use [`Frame::into_raw`] to transfer ownership instead.
     pub fn get_ptr(&self) -> *mut ffi::VSLFrame {
         return self.ptr.clone();
     }
+
+    /// Returns the frame's state if it is one of `states`, otherwise the
+    /// operation is rejected.
+    fn require(
+        &self,
+        operation: &'static str,
+        states: &[FrameState],
+    ) -> Result<FrameState, VslError> {
+        let state = self.state.get();
+        if !states.contains(&state) {
+            return Err(VslError::from(FrameError::InvalidState {
+                operation,
+                state: state.name(),
+            }));
+        }
+        return Ok(state);
+    }
+
+    /// Rejects the operation giving the frame a buffer if it is metadata only,
+    /// see [`Frame::is_probe`].
+    fn require_buffer(&self, operation: &'static str) -> Result<(), VslError> {
+        if self.is_probe() {
+            return Err(VslError::from(FrameError::InvalidState {
+                operation,
+                state: "metadata only",
+            }));
+        }
+        return Ok(());
+    }
+
+    /// Moves the frame to the next state of its lifecycle.  Operations check
+    /// their state with [`Frame::require`] first, so an invalid transition is
+    /// a bug in this crate.
+    fn transition(&self, next: FrameState) {
+        let state = self.state.get();
+        debug_assert!(
+            state.allows(next),
+            "invalid frame transition from {:?} to {:?}",
+            state,
+            next
+        );
+        self.state.set(next);
+    }
+}
+
+/// Returns the bytes of buffer memory available to frames allocated at
+/// `path`, as given to [`Frame::alloc`], so a producer can size its pool of
+/// frames rather than allocate until [`FrameError::OutOfBufferMemory`].
+///
+/// Paths under `/dev` are dmabuf heaps: the CMA heap draws on the memory
+/// reserved for it at boot and the system heap on available system memory.
+/// Other paths name shared memory objects, limited by the free space of
+/// `/dev/shm` as well as the system memory backing it.  Without a path the
+/// library prefers a dmabuf heap, which is assumed to be the first of those
+/// tried by [`Frame::to_dmabuf_copy`], falling back to shared memory.
+///
+/// Returns None for other heaps and where the kernel does not report the
+/// memory.  The figure is a snapshot which other processes allocating from
+/// the same memory can change at any time, so allocations may still fail.
+pub fn available_buffer_memory(path: Option<&Path>) -> Option<u64> {
+    let path = match path {
+        Some(path) => path,
+        None => match DMA_HEAPS.iter().map(Path::new).find(|heap| heap.exists()) {
+            Some(heap) => heap,
+            None => return shm_available(),
+        },
+    };
+    if !path.starts_with("/dev") {
+        return shm_available();
+    }
+    match path.file_name()?.to_str()? {
+        "linux,cma" | "reserved" => return meminfo("CmaFree"),
+        "system" => return meminfo("MemAvailable"),
+        _ => return None,
+    }
+}
+
+/// Returns the bytes shared memory objects can still be allocated, limited by
+/// both the size of the filesystem and the memory backing it.
+fn shm_available() -> Option<u64> {
+    let path = CString::new("/dev/shm").unwrap();
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    match meminfo("MemAvailable") {
+        Some(memory) => return Some(free.min(memory)),
+        None => return Some(free),
+    }
+}
+
+/// Returns the bytes of the `/proc/meminfo` entry, which reports kilobytes.
+fn meminfo(key: &str) -> Option<u64> {
+    let info = std::fs::read_to_string("/proc/meminfo").ok()?;
+    for line in info.lines() {
+        if let Some(value) = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(':'))
+        {
+            let kb: u64 = value.trim().trim_end_matches("kB").trim().parse().ok()?;
+            return Some(kb * 1024);
+        }
+    }
+    return None;
+}
+
+/// Converts an allocation failure into a VslError, distinguishing exhausted
+/// buffer memory, reported as ENOSPC by shared memory and ENOMEM by dmabuf
+/// heaps, so callers can fall back rather than fail.
+fn alloc_error(err: io::Error, size: usize) -> VslError {
+    match err.kind() {
+        io::ErrorKind::StorageFull | io::ErrorKind::OutOfMemory => {
+            return VslError::from(FrameError::OutOfBufferMemory { size })
+        }
+        _ => return VslError::AllocFailed(err),
+    }
+}
+
+/// Returns a shared memory name unique to the process for a frame of the
+/// purpose.
+fn shm_name(purpose: &str) -> PathBuf {
+    let count = SHM_NAMES.fetch_add(1, Ordering::Relaxed);
+    return PathBuf::from(format!("/vsl-{}-{}-{}", purpose, process::id(), count));
+}
+
+/// Formats the fourcc code as its four characters for messages.
+fn fourcc_string(fourcc: u32) -> String {
+    return fourcc
+        .to_le_bytes()
+        .iter()
+        .map(|&c| {
+            if c.is_ascii_graphic() || c == b' ' {
+                c as char
+            } else {
+                '?'
+            }
+        })
+        .collect();
+}
+
+/// A pixel read from a source frame.
+enum Sample {
+    Rgba(Rgba),
+    Yuv(u8, u8, u8),
+}
+
+/// Reads the pixels of a frame in any of the uncompressed formats.
+struct Sampler {
+    fourcc: u32,
+    planes: Vec<format::Plane>,
+    channels: Option<format::Channels>,
+}
+
+impl Sampler {
+    fn new(frame: &Frame) -> Result<Self, VslError> {
+        return Ok(Sampler {
+            fourcc: frame.fourcc(),
+            planes: frame.planes()?,
+            channels: format::channels(frame.fourcc()),
+        });
+    }
+
+    /// Returns the byte at `x` bytes into row `y` of the plane.
+    fn byte(&self, mem: &[u8], plane: usize, x: usize, y: usize) -> Result<u8, VslError> {
+        let plane = &self.planes[plane];
+        let index = plane.offset + y * plane.stride + x;
+        return mem
+            .get(index)
+            .copied()
+            .ok_or(VslError::from(FrameError::OutOfBounds));
+    }
+
+    /// Converts row `y` of an NV12 frame into its red, green and blue rows.
+    fn nv12_row(
+        &self,
+        mem: &[u8],
+        y: usize,
+        matrix: &YuvMatrix,
+        rgb: [&mut Vec<u8>; 3],
+    ) -> Result<(), VslError> {
+        let row = |plane: &format::Plane, y: usize| {
+            let start = plane.offset + y * plane.stride;
+            return mem
+                .get(start..start + plane.row_bytes)
+                .ok_or(VslError::from(FrameError::OutOfBounds));
+        };
+        let luma = row(&self.planes[0], y)?;
+        let chroma = row(&self.planes[1], y / 2)?;
+        let [r, g, b] = rgb;
+        simd::nv12_row(luma, chroma, matrix, [r, g, b]);
+        return Ok(());
+    }
+
+    fn sample(&self, mem: &[u8], x: usize, y: usize) -> Result<Sample, VslError> {
+        if let Some(channels) = &self.channels {
+            let start = self.planes[0].offset + y * self.planes[0].stride + x * channels.bytes;
+            return match mem.get(start..start + channels.bytes) {
+                Some(pixel) => Ok(Sample::Rgba(read_pixel(pixel, channels))),
+                None => Err(VslError::from(FrameError::OutOfBounds)),
+            };
+        }
+
+        let (cx, cy) = (x / 2, y / 2);
+        let sample = match self.fourcc {
+            format::GREY | format::Y8 => Sample::Yuv(self.byte(mem, 0, x, y)?, 128, 128),
+            // The high byte of the little endian sample.
+            format::Y16 => Sample::Yuv(self.byte(mem, 0, x * 2 + 1, y)?, 128, 128),
+            format::YUYV | format::YUY2 => Sample::Yuv(
+                self.byte(mem, 0, x * 2, y)?,
+                self.byte(mem, 0, cx * 4 + 1, y)?,
+                self.byte(mem, 0, cx * 4 + 3, y)?,
+            ),
+            format::UYVY => Sample::Yuv(
+                self.byte(mem, 0, x * 2 + 1, y)?,
+                self.byte(mem, 0, cx * 4, y)?,
+                self.byte(mem, 0, cx * 4 + 2, y)?,
+            ),
+            format::NV12 | format::NV21 | format::NV16 => {
+                let cy = if self.fourcc == format::NV16 { y } else { cy };
+                let first = self.byte(mem, 1, cx * 2, cy)?;
+                let second = self.byte(mem, 1, cx * 2 + 1, cy)?;
+                let (u, v) = if self.fourcc == format::NV21 {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                Sample::Yuv(self.byte(mem, 0, x, y)?, u, v)
+            }
+            format::I420 | format::YV12 => {
+                let first = self.byte(mem, 1, cx, cy)?;
+                let second = self.byte(mem, 2, cx, cy)?;
+                let (u, v) = if self.fourcc == format::YV12 {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                Sample::Yuv(self.byte(mem, 0, x, y)?, u, v)
+            }
+            _ => return Err(VslError::from(FrameError::UnsupportedFourcc(self.fourcc))),
+        };
+        return Ok(sample);
+    }
+}
+
+fn read_pixel(pixel: &[u8], channels: &format::Channels) -> Rgba {
+    let a = match channels.alpha {
+        Some(alpha) => pixel[alpha],
+        None => 255,
+    };
+    return Rgba::new(pixel[channels.r], pixel[channels.g], pixel[channels.b], a);
+}
+
+fn write_pixel(pixel: &mut [u8], channels: &format::Channels, color: Rgba) {
+    pixel[channels.r] = color.r;
+    pixel[channels.g] = color.g;
+    pixel[channels.b] = color.b;
+    if let Some(alpha) = channels.alpha {
+        pixel[alpha] = color.a;
+    }
+    if let Some(padding) = channels.padding {
+        pixel[padding] = 255;
+    }
+}
+
+/// Copies the pixel data of the planes within `mem` into a vector, in plane
+/// order and without any row padding.
+fn copy_planes(mem: &[u8], planes: &[format::Plane]) -> Result<Vec<u8>, VslError> {
+    let mut data = vec![0u8; packed_len(planes)];
+    copy_planes_into(mem, planes, &mut data)?;
+    return Ok(data);
+}
+
+/// Returns the bytes of an element of each plane of the formats which
+/// [`Frame::apply_orientation`] rotates, an element being a pixel or the
+/// interleaved chroma of a block of pixels.
+pub(crate) fn element_bytes(fourcc: u32) -> Option<Vec<usize>> {
+    if let Some(channels) = format::channels(fourcc) {
+        return Some(vec![channels.bytes]);
+    }
+    match fourcc {
+        format::GREY | format::Y8 => return Some(vec![1]),
+        format::Y16 => return Some(vec![2]),
+        format::NV12 | format::NV21 | format::NV16 => return Some(vec![1, 2]),
+        format::I420 | format::YV12 => return Some(vec![1, 1, 1]),
+        format::P010 | format::P016 => return Some(vec![2, 4]),
+        _ => return None,
+    }
+}
+
+/// Returns the source samples covered by sample `index` of a destination of
+/// `dst` samples scaled from `src` samples, at least the nearest one.
+fn covered(index: usize, src: usize, dst: usize) -> Range<usize> {
+    let start = (index * src / dst).min(src - 1);
+    let end = ((index + 1) * src).div_ceil(dst).clamp(start + 1, src);
+    return start..end;
+}
+
+/// Returns the bytes of the planes' pixel data without any row padding.
+fn packed_len(planes: &[format::Plane]) -> usize {
+    return planes
+        .iter()
+        .map(|plane| plane.row_bytes * plane.rows)
+        .sum();
+}
+
+/// Copies the pixel data of the planes as [`copy_planes`] to the start of
+/// `dst`, which must hold at least [`packed_len`] bytes.
+fn copy_planes_into(mem: &[u8], planes: &[format::Plane], dst: &mut [u8]) -> Result<(), VslError> {
+    let mut pos = 0;
+    for plane in planes {
+        for y in 0..plane.rows {
+            let start = plane.offset + y * plane.stride;
+            let row = match mem.get(start..start + plane.row_bytes) {
+                Some(row) => row,
+                None => return Err(VslError::from(FrameError::OutOfBounds)),
+            };
+            dst[pos..pos + row.len()].copy_from_slice(row);
+            pos += row.len();
+        }
+    }
+    return Ok(());
+}
+
+/// Writes the pixel over the region of columns `x` and rows `y`, clipped to the
+/// plane.
+fn fill_region(
+    mem: &mut [u8],
+    plane: &format::Plane,
+    pixel: &[u8],
+    x: Range<i64>,
+    y: Range<i64>,
+) -> Result<(), VslError> {
+    let bytes = pixel.len();
+    let columns = (plane.row_bytes / bytes) as i64;
+    let rows = plane.rows as i64;
+    let (x0, x1) = (x.start.clamp(0, columns), x.end.clamp(0, columns));
+    let (y0, y1) = (y.start.clamp(0, rows), y.end.clamp(0, rows));
+    for y in y0..y1 {
+        let start = plane.offset + y as usize * plane.stride;
+        let row = match mem.get_mut(start..start + plane.row_bytes) {
+            Some(row) => row,
+            None => return Err(VslError::from(FrameError::OutOfBounds)),
+        };
+        let span = &mut row[x0 as usize * bytes..x1.max(x0) as usize * bytes];
+        for dst in span.chunks_exact_mut(bytes) {
+            dst.copy_from_slice(pixel);
+        }
+    }
+    return Ok(());
+}
+
+/// Composites the straight alpha `src` color over `dst`.
+fn alpha_over(src: Rgba, dst: Rgba) -> Rgba {
+    // Alphas are scaled by 255 * 255 to keep the arithmetic in integers.
+    let src_alpha = src.a as u32 * 255;
+    let dst_alpha = dst.a as u32 * (255 - src.a as u32);
+    let alpha = src_alpha + dst_alpha;
+    if alpha == 0 {
+        return Rgba::new(0, 0, 0, 0);
+    }
+    let channel = |s: u8, d: u8| {
+        return ((s as u32 * src_alpha + d as u32 * dst_alpha + alpha / 2) / alpha) as u8;
+    };
+    return Rgba::new(
+        channel(src.r, dst.r),
+        channel(src.g, dst.g),
+        channel(src.b, dst.b),
+        ((alpha + 127) / 255) as u8,
+    );
+}
+
+/// Installs `on_release` to run when the library releases the frame, ahead of
+/// a closure already installed by [`Frame::new_with_release`].
+unsafe fn chain_release(frame: *mut ffi::VSLFrame, on_release: Box<dyn FnOnce() + Send>) {
+    let previous = ffi::vsl_frame_userptr(frame);
+    let on_release: Box<dyn FnOnce() + Send> = if previous.is_null() {
+        on_release
+    } else {
+        let previous = Box::from_raw(previous as *mut Box<dyn FnOnce() + Send>);
+        Box::new(move || {
+            on_release();
+            previous();
+        })
+    };
+    let userptr = Box::into_raw(Box::new(on_release)) as *mut c_void;
+    ffi::vsl_frame_set_userptr(frame, userptr);
+}
+
+/// Invokes the closure installed by [`Frame::new_with_release`] or
+/// [`Frame::into_raw`].
+unsafe extern "C" fn release_trampoline(frame: *mut ffi::VSLFrame) {
+    let userptr = ffi::vsl_frame_userptr(frame);
+    if userptr.is_null() {
+        return;
+    }
+    ffi::vsl_frame_set_userptr(frame, ptr::null_mut());
+    let on_release = Box::from_raw(userptr as *mut Box<dyn FnOnce() + Send>);
+    // Unwinding into the library would abort, so panics are contained here.
+    let _ = panic::catch_unwind(AssertUnwindSafe(on_release));
 }
 
 impl TryFrom<*mut ffi::VSLFrame> for Frame {
-    type Error = ();
+    type Error = VslError;
 
     fn try_from(ptr: *mut ffi::VSLFrame) -> Result<Self, Self::Error> {
         if ptr.is_null() {
-            return Err(());
+            return Err(VslError::NullPointer);
         }
-        return Ok(Frame { ptr });
+        return Ok(Frame::from_ptr(ptr));
     }
 }
 
 impl Drop for Frame {
     fn drop(&mut self) {
-        unsafe {
-            ffi::vsl_frame_unlock(self.ptr);
-            ffi::vsl_frame_release(self.ptr);
-        };
+        if self.state.get() == FrameState::Released {
+            self.unmap_file();
+            return;
+        }
+        #[cfg(feature = "fd-debug")]
+        {
+            let handle = unsafe { ffi::vsl_frame_handle(self.ptr) };
+            if handle != -1 {
+                fd_debug::trace("release", handle);
+            }
+            if let Some(fd) = self.owned_fd.get_mut() {
+                fd_debug::trace("close", fd.as_raw_fd());
+            }
+        }
+        unsafe { ffi::vsl_frame_unlock(self.ptr) };
+        self.unmap_outstanding();
+        unsafe { ffi::vsl_frame_release(self.ptr) };
+        self.unmap_file();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alloc_error, FrameError};
+    use crate::VslError;
+    use std::io;
+
+    #[test]
+    fn test_alloc_error() {
+        // ENOSPC, as reported by a full /dev/shm.
+        let err = alloc_error(io::Error::from_raw_os_error(28), 1 << 20);
+        assert!(matches!(
+            err,
+            VslError::Frame(FrameError::OutOfBufferMemory { size }) if size == 1 << 20
+        ));
+        assert!(err.to_string().contains("1048576 bytes"));
+
+        let err = alloc_error(io::ErrorKind::OutOfMemory.into(), 64);
+        assert!(matches!(
+            err,
+            VslError::Frame(FrameError::OutOfBufferMemory { size: 64 })
+        ));
+
+        let err = alloc_error(io::ErrorKind::PermissionDenied.into(), 64);
+        assert!(matches!(err, VslError::AllocFailed(_)));
     }
 }
@@ -1,10 +1,28 @@
-use crate::NullStringError;
+use crate::{
+    ack::AckListener,
+    auth::Authenticator,
+    encoder::Encoder,
+    frame::{Frame, FrameFormat},
+    metadata::Metadata,
+    partial,
+    record::Recorder,
+    sync_file, Endpoint, VslError,
+};
 use std::{
+    collections::BTreeMap,
     error::Error,
     ffi::{CStr, CString},
-    io,
-    os::unix::prelude::OsStrExt,
+    fmt, io,
+    os::{
+        fd::{AsFd, OwnedFd, RawFd},
+        unix::prelude::OsStrExt,
+    },
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 use videostream_sys as ffi;
 
@@ -16,37 +34,911 @@ use videostream_sys as ffi;
 /// allowing clients to connect in order to receive frames.
 pub struct Host {
     ptr: *mut ffi::VSLHost,
+    keep_last_frame: bool,
+    last_frame: Mutex<Option<LastFrame>>,
+    clients: Mutex<usize>,
+    on_connected: Mutex<Vec<Box<dyn FnMut()>>>,
+    acks: Mutex<Option<AckListener>>,
+    on_acked: Mutex<Vec<AckCallback>>,
+    auth: Option<Authenticator>,
+    created: Instant,
+    frames_posted: AtomicU64,
+    bytes_posted: AtomicU64,
+    recorder: Mutex<Option<Recorder>>,
+    probe: Mutex<Option<FrameFormat>>,
+    partial_updates: bool,
+    /// The last frame posted while sending partial updates, or None when the
+    /// next must be posted in full.
+    partial_base: Mutex<Option<PartialBase>>,
+    fps: Option<f64>,
+    control_frames: bool,
+    /// The metadata last sent to clients, see [`HostBuilder::control_frames`].
+    metadata: Mutex<Metadata>,
+    /// The frames dropped by each client sending acknowledgements, see
+    /// [`HostStats::frames_dropped_per_client`].
+    client_drops: Mutex<BTreeMap<u64, u64>>,
 }
 
-impl Host {
-    /// Creates a new Host and creates a socket at the specified path on which
-    /// it will listen for client connections.
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
-        let path_str_c = CString::new(path.as_ref().as_os_str().as_bytes())?;
+/// A callback registered by [`Host::on_frame_acked`].
+type AckCallback = Box<dyn FnMut(u64, i64, Duration)>;
+
+/// The frame the next partial update is relative to, see
+/// [`HostBuilder::partial_updates`].
+#[derive(Clone, Copy)]
+struct PartialBase {
+    format: FrameFormat,
+    serial: i64,
+    /// The partial updates posted since the last frame posted in full.
+    updates: u32,
+}
+
+/// The partial updates posted between frames posted in full, so clients
+/// which missed a frame resume assembling updates.
+const PARTIAL_REFRESH: u32 = 30;
+
+/// How long frames posted by [`Host::serve`] remain available to clients.
+const SERVE_FRAME_LIFETIME: Duration = Duration::from_millis(100);
+
+/// How long heartbeats sent by [`Host::send_heartbeat`] remain available.
+const HEARTBEAT_LIFETIME: Duration = Duration::from_secs(1);
+
+/// How long the goodbye sent by [`Host::shutdown`] remains available.
+const GOODBYE_LIFETIME: Duration = Duration::from_secs(1);
+
+/// How long the framerate sent to clients as they connect remains available.
+const RATE_LIFETIME: Duration = Duration::from_secs(1);
+
+/// How long probes sent by [`Host::post_probe`] remain available, they are
+/// sent again to each client which connects afterwards.
+const PROBE_LIFETIME: Duration = Duration::from_secs(1);
+
+/// How long the metadata sent to clients as they connect remains available.
+const METADATA_LIFETIME: Duration = Duration::from_secs(1);
+
+/// Errors reported by host operations.
+#[derive(Debug)]
+pub enum HostError {
+    /// The underlying library reported an OS error.
+    Io(io::Error),
+    /// The frame was still locked for writing by [`Frame::trylock`] when
+    /// posted, so clients could have read it part way through being written.
+    FrameNotReady,
+    /// The message needs a control frame but the host was not built with
+    /// [`HostBuilder::control_frames`].
+    ControlFramesDisabled,
+}
+
+impl Error for HostError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HostError::Io(err) => return Some(err),
+            HostError::FrameNotReady => return None,
+            HostError::ControlFramesDisabled => return None,
+        }
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostError::Io(err) => write!(f, "host error: {}", err),
+            HostError::FrameNotReady => write!(f, "frame is still locked for writing"),
+            HostError::ControlFramesDisabled => write!(f, "control frames are disabled"),
+        }
+    }
+}
+
+impl From<io::Error> for HostError {
+    fn from(err: io::Error) -> Self {
+        return HostError::Io(err);
+    }
+}
+
+/// The PostReceipt reports the delivery of a posted frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostReceipt {
+    /// Number of connected clients signalled with the frame.
+    pub client_count: usize,
+    /// Size in bytes of the frame's buffer, or of the partial update sent in
+    /// its place, see [`HostBuilder::partial_updates`].
+    pub bytes: usize,
+}
+
+/// The HostStats is a snapshot of a host's activity, returned by
+/// [`Host::stats`] for periodic scraping into metrics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostStats {
+    /// Number of currently connected clients.
+    pub clients: usize,
+    /// Frames posted since the host was created, excluding heartbeats and
+    /// replays of the last frame.
+    pub frames_posted: u64,
+    /// Bytes of the buffers of the frames posted.
+    pub bytes_posted: u64,
+    /// The frames each client has skipped or dropped, its
+    /// [`crate::client::ClientStats::frames_skipped`], by the id of
+    /// [`crate::client::Client::ack_id`] in ascending order.  The library
+    /// does not tell the host of frames its clients miss, so only clients
+    /// created with [`crate::client::ClientOptions::send_acks`] report them,
+    /// with each acknowledgement, once [`Host::on_frame_acked`] has bound the
+    /// socket receiving them.  Clients remain listed after disconnecting.
+    pub frames_dropped_per_client: Vec<(u64, u64)>,
+    /// Time since the host was created.
+    pub uptime: Duration,
+}
+
+/// Why a host is going away, sent to its clients by [`Host::shutdown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// The host will be back at the same path, so clients may reconnect.
+    Restart,
+    /// The host is gone for good and clients should stop reconnecting.
+    Terminate,
+}
+
+/// The file format of a recording started by [`Host::start_recording`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// The pixel data of each frame, without row padding, one frame after
+    /// another.  The layout is not recorded, so readers must know the
+    /// stream's resolution and format.
+    Raw,
+    /// Each frame with its layout and pts as written by [`Frame::save`], for
+    /// replaying through [`crate::playback::FileHost`].
+    Saved,
+    /// The bitstream of the compressed frames posted from an encoder, such as
+    /// the H.264 Annex B elementary stream of [`Host::post_encoded_frame`].
+    /// The host does not encode itself, so raw frames are not recorded.
+    Encoded,
+}
+
+/// The RecordingStats reports a recording finished by
+/// [`Host::stop_recording`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingStats {
+    /// Frames written to the file.
+    pub frames_written: u64,
+    /// Frames posted while recording but missing from the file, as the disk
+    /// fell behind or their buffer could not be shared.
+    pub frames_dropped: u64,
+}
+
+/// The most recently posted frame, kept for replaying to late-joining clients.
+struct LastFrame {
+    frame: Frame,
+    replay: bool,
+    lifetime: i64,
+    duration: i64,
+    pts: i64,
+    dts: i64,
+}
+
+/// The HostBuilder configures optional Host behaviour before creating the
+/// host's socket.
+pub struct HostBuilder {
+    path: PathBuf,
+    keep_last_frame: bool,
+    auth_token: Option<Vec<u8>>,
+    partial_updates: bool,
+    fps: Option<f64>,
+    control_frames: bool,
+}
+
+impl HostBuilder {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        return HostBuilder {
+            path: path.as_ref().to_path_buf(),
+            keep_last_frame: false,
+            auth_token: None,
+            partial_updates: false,
+            fps: None,
+            control_frames: false,
+        };
+    }
+
+    /// When enabled the host keeps the most recently posted frame and posts it
+    /// again as soon as a new client connects, rather than having the client
+    /// wait for the next frame on sparse streams.  Encoded frames are only
+    /// replayed when the most recent one was a keyframe.
+    ///
+    /// The replayed frame is broadcast, so clients already connected will also
+    /// receive it a second time.
+    pub fn keep_last_frame(mut self, keep: bool) -> Self {
+        self.keep_last_frame = keep;
+        return self;
+    }
+
+    /// Only admits clients presenting the same token, through
+    /// [`crate::client::ClientOptions::auth_token`], as a lightweight access
+    /// control for hosts whose socket is reachable by untrusted processes.
+    ///
+    /// The library's handshake cannot carry the token so clients present it
+    /// over a second socket, the host's path with an ".auth" suffix, before
+    /// connecting.  Clients the library accepts from processes which have not
+    /// presented the token are disconnected by [`Host::process`].  Processes
+    /// are identified by the peer credentials of their sockets, so the token
+    /// admits every client of a process which presented it.
+    pub fn auth_token(mut self, token: &[u8]) -> Self {
+        self.auth_token = Some(token.to_vec());
+        return self;
+    }
+
+    /// When enabled, frames with regions marked by [`Frame::mark_dirty`] are
+    /// sent as the pixels of those regions only, which clients created with
+    /// [`crate::client::ClientOptions::assemble_partial`] merge into their
+    /// last frame.  This saves copying and mapping whole buffers on streams
+    /// which change in small areas, such as dashboards or screen sharing.
+    ///
+    /// A frame is posted in full when it has no region marked, when its
+    /// format differs from the previous frame's, after any other control
+    /// frame, for the first frame following a client connecting, accepted by
+    /// [`Host::process`], and after every 30 partial updates.  Each update
+    /// names the frame it updates, so clients which missed that frame skip
+    /// the updates until the next frame posted in full.  The protocol cannot
+    /// tell which clients assemble frames, so clients which do not assemble
+    /// them skip the partial updates and only receive the full frames.
+    /// Partial updates are control frames and are only sent when enabled by
+    /// [`HostBuilder::control_frames`].
+    pub fn partial_updates(mut self, enable: bool) -> Self {
+        self.partial_updates = enable;
+        return self;
+    }
+
+    /// Sets the framerate the host intends to post at, reported by
+    /// [`Host::configured_fps`].  Each client is told the framerate as it
+    /// connects, once accepted by [`Host::process`], so it is known to
+    /// [`crate::client::Client::target_fps`] before the first frame arrives.
+    /// Non-positive framerates are ignored.
+    ///
+    /// The library's handshake cannot carry the framerate, so like a probe
+    /// it is sent as a control frame and only when enabled by
+    /// [`HostBuilder::control_frames`].
+    pub fn frame_rate(mut self, fps: f64) -> Self {
+        if fps > 0.0 {
+            self.fps = Some(fps);
+        }
+        return self;
+    }
+
+    /// When enabled the host sends heartbeats, probes, framerates, goodbyes
+    /// and the metadata the library's frame descriptor cannot carry to its
+    /// clients.  The metadata is that set by [`Frame::set_interlaced`],
+    /// [`Frame::set_orientation`], [`Frame::set_color_space`],
+    /// [`Frame::set_color_range`] and [`Frame::set_hdr_metadata`].  It is
+    /// sent ahead of the first frame which changes it and to each client as
+    /// it connects, and applies to the frames clients receive until it
+    /// changes again.  Frames received from other hosts have the defaults,
+    /// progressive and upright without HDR metadata, and their color
+    /// defaults by size.  The protocol has no messages
+    /// without a frame, so these are sent as small frames with reserved
+    /// fourccs which clients of this crate consume while waiting for
+    /// frames.  Clients using the library directly, or older versions of
+    /// this crate, receive them as ordinary frames, so control frames are
+    /// disabled by default and should only be enabled when every client is
+    /// built with this crate.
+    pub fn control_frames(mut self, enable: bool) -> Self {
+        self.control_frames = enable;
+        return self;
+    }
+
+    /// Creates the Host and its socket on which it will listen for client
+    /// connections.
+    pub fn build(self) -> Result<Host, VslError> {
+        crate::check_version_compatibility()?;
+        let path_str_c = CString::new(self.path.as_os_str().as_bytes())?;
+        let auth = match self.auth_token {
+            Some(token) => Some(Authenticator::new(&self.path, token)?),
+            None => None,
+        };
         let ptr = unsafe { ffi::vsl_host_init(path_str_c.as_ptr()) };
         if ptr.is_null() {
             let err = io::Error::last_os_error();
-            return Err(Box::new(err));
+            return Err(VslError::from(err));
         }
 
-        return Ok(Host { ptr });
+        return Ok(Host {
+            ptr,
+            keep_last_frame: self.keep_last_frame,
+            last_frame: Mutex::new(None),
+            clients: Mutex::new(0),
+            on_connected: Mutex::new(Vec::new()),
+            acks: Mutex::new(None),
+            on_acked: Mutex::new(Vec::new()),
+            auth,
+            created: Instant::now(),
+            frames_posted: AtomicU64::new(0),
+            bytes_posted: AtomicU64::new(0),
+            recorder: Mutex::new(None),
+            probe: Mutex::new(None),
+            partial_updates: self.partial_updates,
+            partial_base: Mutex::new(None),
+            fps: self.fps,
+            control_frames: self.control_frames,
+            metadata: Mutex::new(Metadata::default()),
+            client_drops: Mutex::new(BTreeMap::new()),
+        });
+    }
+}
+
+impl Host {
+    /// Creates a new Host and creates a socket at the specified path on which
+    /// it will listen for client connections.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, VslError> {
+        return HostBuilder::new(path).build();
+    }
+
+    /// Creates a new Host listening on the endpoint, as [`Host::new`].
+    pub fn bind(endpoint: &Endpoint) -> Result<Self, VslError> {
+        return Host::new(endpoint.path());
     }
 
-    pub fn path(&self) -> Result<PathBuf, Box<dyn Error>> {
+    pub fn path(&self) -> Result<PathBuf, VslError> {
         let path_str_c = unsafe { ffi::vsl_host_path(self.ptr) };
         if path_str_c.is_null() {
-            return Err(Box::new(NullStringError {}));
+            return Err(VslError::NullPointer);
         }
 
         let path_str = unsafe { CStr::from_ptr(path_str_c).to_str()? };
         return Ok(PathBuf::from(path_str));
     }
 
-    pub fn poll(&self) {}
+    /// Polls the host's sockets for activity, waiting up to `wait`
+    /// milliseconds.  Returns the number of sockets ready to be processed.
+    pub fn poll(&self, wait: i64) -> Result<i32, VslError> {
+        let ret = unsafe { ffi::vsl_host_poll(self.ptr, wait) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            return Err(VslError::from(err));
+        }
+        return Ok(ret);
+    }
+
+    /// Expires old frames then services the first available connection,
+    /// accepting new clients.  Should be called in a loop blocked by
+    /// [`Host::poll`].
+    pub fn process(&self) -> Result<(), VslError> {
+        if let Some(auth) = &self.auth {
+            auth.service()?;
+        }
+        let ret = unsafe { ffi::vsl_host_process(self.ptr) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            return Err(VslError::from(err));
+        }
+        if let Some(auth) = &self.auth {
+            auth.enforce(&self.list_sockets()?[1..]);
+        }
+        if let Some(acks) = &*self.acks.lock().unwrap() {
+            let mut callbacks = self.on_acked.lock().unwrap();
+            let mut drops = self.client_drops.lock().unwrap();
+            acks.service(|client, serial, processing, dropped| {
+                if let Some(dropped) = dropped {
+                    drops.insert(client, dropped);
+                }
+                for callback in callbacks.iter_mut() {
+                    callback(client, serial, processing);
+                }
+            })?;
+        }
+
+        let probe = *self.probe.lock().unwrap();
+        let metadata = *self.metadata.lock().unwrap();
+        let described = metadata != Metadata::default();
+        if self.keep_last_frame
+            || self.partial_updates
+            || (self.control_frames && (probe.is_some() || self.fps.is_some() || described))
+            || !self.on_connected.lock().unwrap().is_empty()
+        {
+            let clients = self.sockets()?.len() - 1;
+            let mut known = self.clients.lock().unwrap();
+            if clients > *known {
+                for _ in *known..clients {
+                    for callback in self.on_connected.lock().unwrap().iter_mut() {
+                        callback();
+                    }
+                }
+                // Clients which just connected lack the previous frame.
+                *self.partial_base.lock().unwrap() = None;
+                if self.control_frames {
+                    if let Some(fps) = self.fps {
+                        let payload = fps.to_le_bytes();
+                        self.send_signal(crate::RATE_FOURCC, &payload, RATE_LIFETIME)?;
+                    }
+                    if let Some(format) = probe {
+                        self.send_probe(format)?;
+                    }
+                    if described {
+                        let expires = crate::timestamp() + METADATA_LIFETIME.as_nanos() as i64;
+                        self.send_metadata(&metadata, expires)?;
+                    }
+                }
+                if self.keep_last_frame {
+                    self.replay_last_frame()?;
+                }
+            }
+            *known = clients;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the framerate set by [`HostBuilder::frame_rate`].
+    pub fn configured_fps(&self) -> Option<f64> {
+        return self.fps;
+    }
+
+    /// Registers a callback invoked from [`Host::process`] each time a new
+    /// client connects.  The callback must not register further callbacks.
+    pub fn on_client_connected<F: FnMut() + 'static>(&self, callback: F) {
+        self.on_connected.lock().unwrap().push(Box::new(callback));
+    }
+
+    /// Registers a callback invoked from [`Host::process`] with the client id,
+    /// serial and processing time of each frame acknowledged by clients
+    /// created with [`crate::client::ClientOptions::send_acks`], the time
+    /// the client held the frame before dropping it.  The callback must not
+    /// register further callbacks.
+    ///
+    /// The first registration creates the socket receiving the
+    /// acknowledgements, the host's path with an ".ack" suffix, which fails
+    /// when it cannot be bound.  The library does not tell the host which of
+    /// its clients sent an acknowledgement, so clients are identified by the
+    /// ids they send, see [`crate::client::Client::ack_id`].
+    pub fn on_frame_acked<F: FnMut(u64, i64, Duration) + 'static>(
+        &self,
+        callback: F,
+    ) -> Result<(), VslError> {
+        let mut acks = self.acks.lock().unwrap();
+        if acks.is_none() {
+            *acks = Some(AckListener::new(&self.path()?)?);
+        }
+        self.on_acked.lock().unwrap().push(Box::new(callback));
+        return Ok(());
+    }
+
+    /// Requests a keyframe from the encoder whenever a client connects, so
+    /// new subscribers to an encoded stream can start decoding without
+    /// waiting for the next scheduled keyframe.  This only applies to hosts
+    /// posting frames from the encoder, see [`Encoder::request_keyframe`].
+    pub fn attach_encoder(&self, encoder: Arc<Encoder>) {
+        self.on_client_connected(move || encoder.request_keyframe());
+    }
+
+    /// Returns the sockets managed by the host.  The first socket accepts new
+    /// connections while the rest are connected clients.
+    pub fn sockets(&self) -> Result<Vec<RawFd>, VslError> {
+        return Ok(self.list_sockets()?);
+    }
+
+    fn list_sockets(&self) -> Result<Vec<RawFd>, io::Error> {
+        let mut sockets: Vec<RawFd> = vec![0; 16];
+        loop {
+            let mut max_sockets: usize = 0;
+            let ret = unsafe {
+                ffi::vsl_host_sockets(
+                    self.ptr,
+                    sockets.len(),
+                    sockets.as_mut_ptr(),
+                    &mut max_sockets,
+                )
+            };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                // The host gained clients since we sized the buffer.
+                if max_sockets > sockets.len() {
+                    sockets.resize(max_sockets * 2, 0);
+                    continue;
+                }
+                return Err(err);
+            }
+            sockets.truncate(max_sockets);
+            return Ok(sockets);
+        }
+    }
+
+    /// Publishes the frame to subscribers.  Ownership of the frame passes to
+    /// the host which releases it once `expires` (a library timestamp) has
+    /// passed.  The receipt reports how many clients were signalled.
+    ///
+    /// Each frame carries its own geometry and fourcc, so consecutive frames
+    /// may change resolution, see [`crate::client::Client::next_event`].  A
+    /// host may also post several representations of each frame, such as raw
+    /// and encoded, from which clients pick with
+    /// [`crate::client::ClientOptions::prefer_formats`].
+    ///
+    /// The frame must be unlocked once written, a frame still locked by
+    /// [`Frame::trylock`] is dropped without posting and
+    /// [`HostError::FrameNotReady`] returned.
+    pub fn post_frame(
+        &self,
+        frame: Frame,
+        expires: i64,
+        duration: i64,
+        pts: i64,
+        dts: i64,
+    ) -> Result<PostReceipt, VslError> {
+        return self.post(frame, true, expires, duration, pts, dts);
+    }
+
+    /// Publishes a frame as [`Host::post_frame`] once `fence`, a sync_file
+    /// signalled when the GPU has finished writing to the frame, is done.
+    ///
+    /// A sync_file is a file descriptor representing a GPU fence which becomes
+    /// readable once the fence signals, as exported by Vulkan with
+    /// `VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_SYNC_FD_BIT` or by EGL through
+    /// `EGL_ANDROID_native_fence_sync`.  The protocol cannot send the fence to
+    /// clients, so for dmabuf frames it is attached to the buffer itself as a
+    /// pending write, posting the frame without waiting, and clients retrieve
+    /// it with [`Frame::acquire_fence`].  Frames in shared memory, or kernels
+    /// older than 6.0, instead wait on the fence before posting.
+    pub fn post_frame_with_fence(
+        &self,
+        frame: Frame,
+        fence: OwnedFd,
+        expires: i64,
+        duration: i64,
+        pts: i64,
+        dts: i64,
+    ) -> Result<PostReceipt, VslError> {
+        let attached = match frame.handle() {
+            Some(handle) => sync_file::import_write_fence(handle, fence.as_fd()).is_ok(),
+            None => false,
+        };
+        if !attached {
+            sync_file::wait(fence.as_fd())?;
+        }
+        return self.post_frame(frame, expires, duration, pts, dts);
+    }
 
-    pub fn process(&self) {}
+    /// Publishes an encoded frame to subscribers, as [`Host::post_frame`].
+    /// The keyframe flag, as reported by the encoder, controls whether the
+    /// frame can be replayed to late-joining clients.
+    pub fn post_encoded_frame(
+        &self,
+        frame: Frame,
+        keyframe: bool,
+        expires: i64,
+        duration: i64,
+        pts: i64,
+        dts: i64,
+    ) -> Result<PostReceipt, VslError> {
+        return self.post(frame, keyframe, expires, duration, pts, dts);
+    }
+
+    /// Runs the host until `produce` returns None, posting each frame it
+    /// returns and servicing client connections in between.  Frames are
+    /// posted with the current time as their pts and dts, the time since the
+    /// previous frame as their duration, and expire after 100ms.  Use
+    /// [`Host::post_frame`] directly for control over the timestamps.
+    pub fn serve<F>(&self, produce: F) -> Result<(), VslError>
+    where
+        F: FnMut() -> Option<Frame>,
+    {
+        return self.serve_with_cancel(produce, Arc::new(AtomicBool::new(false)));
+    }
 
-    pub fn sockets(&self) {}
+    /// Runs the host as [`Host::serve`] until `produce` returns None or
+    /// `cancel` is set from another thread.  The flag is checked between
+    /// frames, so `produce` must return regularly for cancellation to be
+    /// observed.  Frames already posted expire as usual and clients are
+    /// disconnected when the host is dropped.
+    pub fn serve_with_cancel<F>(
+        &self,
+        mut produce: F,
+        cancel: Arc<AtomicBool>,
+    ) -> Result<(), VslError>
+    where
+        F: FnMut() -> Option<Frame>,
+    {
+        let mut previous: Option<i64> = None;
+        while !cancel.load(Ordering::Acquire) {
+            if self.poll(0)? > 0 {
+                self.process()?;
+            }
+
+            let frame = match produce() {
+                Some(frame) => frame,
+                None => break,
+            };
+            if cancel.load(Ordering::Acquire) {
+                break;
+            }
+
+            let now = crate::timestamp();
+            let duration = previous.map_or(0, |previous| now - previous);
+            previous = Some(now);
+            let expires = now + SERVE_FRAME_LIFETIME.as_nanos() as i64;
+            self.post_frame(frame, expires, duration, now, now)?;
+        }
+        return Ok(());
+    }
+
+    /// Returns a snapshot of the host's activity.  The counters are kept by
+    /// the host as frames are posted, the library does not track which
+    /// clients took each frame so no per-client counts are available.
+    pub fn stats(&self) -> Result<HostStats, VslError> {
+        return Ok(HostStats {
+            clients: self.list_sockets()?.len().saturating_sub(1),
+            frames_posted: self.frames_posted.load(Ordering::Relaxed),
+            bytes_posted: self.bytes_posted.load(Ordering::Relaxed),
+            frames_dropped_per_client: self
+                .client_drops
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(&client, &dropped)| return (client, dropped))
+                .collect(),
+            uptime: self.created.elapsed(),
+        });
+    }
+
+    /// Starts recording every frame posted from now on to the file at `path`,
+    /// in the given format, until [`Host::stop_recording`] or the host is
+    /// dropped.  A recording already in progress is stopped first.
+    ///
+    /// Frames are written on a dedicated thread from a share of their buffer,
+    /// so delivery to clients is not delayed.  Once the disk falls behind,
+    /// frames are dropped from the recording rather than holding back the
+    /// host, and counted by the [`RecordingStats`].
+    pub fn start_recording<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: RecordFormat,
+    ) -> Result<(), VslError> {
+        self.stop_recording()?;
+        let recorder = Recorder::start(path.as_ref(), format)?;
+        *self.recorder.lock().unwrap() = Some(recorder);
+        return Ok(());
+    }
+
+    /// Stops the recording started by [`Host::start_recording`], writing the
+    /// frames still queued and closing the file.  Fails with the error which
+    /// stopped the frames being written, such as a full disk.  Without a
+    /// recording in progress empty stats are returned.
+    pub fn stop_recording(&self) -> Result<RecordingStats, VslError> {
+        let recorder = match self.recorder.lock().unwrap().take() {
+            Some(recorder) => recorder,
+            None => {
+                return Ok(RecordingStats {
+                    frames_written: 0,
+                    frames_dropped: 0,
+                })
+            }
+        };
+        let (frames_written, frames_dropped) = recorder.stop()?;
+        return Ok(RecordingStats {
+            frames_written,
+            frames_dropped,
+        });
+    }
+
+    /// Signals clients that the host is alive without posting a frame, so
+    /// clients of streams with long gaps between frames can tell an idle host
+    /// from one which has gone away through
+    /// [`crate::client::Client::time_since_last_signal`].
+    ///
+    /// The protocol has no message without a frame, so a heartbeat is sent as
+    /// a one byte control frame which clients of this crate consume while
+    /// waiting for frames.  Fails with [`HostError::ControlFramesDisabled`]
+    /// unless enabled by [`HostBuilder::control_frames`].
+    pub fn send_heartbeat(&self) -> Result<(), VslError> {
+        if !self.control_frames {
+            return Err(VslError::from(HostError::ControlFramesDisabled));
+        }
+        return self.send_signal(crate::HEARTBEAT_FOURCC, &[0], HEARTBEAT_LIFETIME);
+    }
+
+    /// Tells clients why the host is going away then releases it, closing its
+    /// socket.  Clients of this crate receive the goodbye as
+    /// [`crate::client::ClientError::HostShutdown`], which reconnecting
+    /// clients and subscriptions heed by giving up on a terminated host
+    /// rather than retrying a socket which will not come back.
+    ///
+    /// As with heartbeats the goodbye is sent as a one byte control frame,
+    /// since the protocol has no message without a frame.  It reaches the
+    /// clients connected at the time, which receive it after the frames
+    /// posted before it.  Without [`HostBuilder::control_frames`] the host is
+    /// released without sending the goodbye.
+    pub fn shutdown(self, reason: ShutdownReason) -> Result<(), VslError> {
+        if !self.control_frames {
+            return Ok(());
+        }
+        let code = match reason {
+            ShutdownReason::Restart => crate::GOODBYE_RESTART,
+            ShutdownReason::Terminate => crate::GOODBYE_TERMINATE,
+        };
+        return self.send_signal(crate::GOODBYE_FOURCC, &[code], GOODBYE_LIFETIME);
+    }
+
+    /// Announces the format of the frames the host intends to post, described
+    /// by a probe from [`Frame::probe`], without posting any pixels.  Clients
+    /// receive it as [`crate::client::ClientEvent::Probe`] so they can prepare
+    /// for the stream, such as sizing their buffers, before its first frame.
+    /// The probe is also sent to each client which connects afterwards, once
+    /// accepted by [`Host::process`], until another probe replaces it.
+    ///
+    /// Probes only travel from the host to its clients as the protocol has no
+    /// messages from clients.  Like heartbeats, a probe is sent as a small
+    /// control frame holding the probe's width, height and fourcc, which
+    /// clients of this crate decode while waiting for frames.  Fails with
+    /// [`HostError::ControlFramesDisabled`] unless enabled by
+    /// [`HostBuilder::control_frames`].
+    pub fn post_probe(&self, probe: &Frame) -> Result<(), VslError> {
+        if !self.control_frames {
+            return Err(VslError::from(HostError::ControlFramesDisabled));
+        }
+        let format = probe.format();
+        *self.probe.lock().unwrap() = Some(format);
+        return self.send_probe(format);
+    }
+
+    fn send_probe(&self, format: FrameFormat) -> Result<(), VslError> {
+        let mut payload = [0; crate::PROBE_SIZE];
+        payload[..4].copy_from_slice(&format.width.to_le_bytes());
+        payload[4..8].copy_from_slice(&format.height.to_le_bytes());
+        payload[8..].copy_from_slice(&format.fourcc.to_le_bytes());
+        return self.send_signal(crate::PROBE_FOURCC, &payload, PROBE_LIFETIME);
+    }
+
+    /// Sends the metadata of the frames which follow, expiring with the first
+    /// of them.
+    fn send_metadata(&self, metadata: &Metadata, expires: i64) -> Result<(), VslError> {
+        let payload = metadata.encode();
+        return self.send_signal_until(crate::METADATA_FOURCC, &payload, expires);
+    }
+
+    /// Posts a frame of the reserved `fourcc` holding `payload`, for messages
+    /// the protocol cannot otherwise carry.
+    fn send_signal(&self, fourcc: u32, payload: &[u8], lifetime: Duration) -> Result<(), VslError> {
+        let expires = crate::timestamp() + lifetime.as_nanos() as i64;
+        return self.send_signal_until(fourcc, payload, expires);
+    }
+
+    /// Posts a signal as [`Host::send_signal`] which expires at `expires`.
+    /// The frame following a signal is posted in full, see
+    /// [`HostBuilder::partial_updates`].
+    fn send_signal_until(&self, fourcc: u32, payload: &[u8], expires: i64) -> Result<(), VslError> {
+        *self.partial_base.lock().unwrap() = None;
+        let frame = signal_frame(fourcc, payload)?;
+        let now = crate::timestamp();
+        let ptr = frame.into_raw();
+        let ret = unsafe { ffi::vsl_host_post(self.ptr, ptr, expires, 0, now, now) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { ffi::vsl_frame_release(ptr) };
+            return Err(VslError::from(HostError::Io(err)));
+        }
+        return Ok(());
+    }
+
+    fn post(
+        &self,
+        frame: Frame,
+        replay: bool,
+        expires: i64,
+        duration: i64,
+        pts: i64,
+        dts: i64,
+    ) -> Result<PostReceipt, VslError> {
+        if frame.is_locked() {
+            return Err(VslError::from(HostError::FrameNotReady));
+        }
+        if self.keep_last_frame {
+            let last = match frame.share() {
+                Ok(shared) => Some(LastFrame {
+                    frame: shared,
+                    replay,
+                    lifetime: expires - crate::timestamp(),
+                    duration,
+                    pts,
+                    dts,
+                }),
+                Err(_) => None,
+            };
+            *self.last_frame.lock().unwrap() = last;
+        }
+
+        // The buffer is shared for recording before the library takes the
+        // frame, and only recorded once posted.
+        let recorded = match self.recorder.lock().unwrap().as_mut() {
+            Some(recorder) => recorder.share(&frame, pts),
+            None => None,
+        };
+
+        if self.control_frames {
+            let metadata = frame.metadata();
+            let mut sent = self.metadata.lock().unwrap();
+            if *sent != metadata {
+                self.send_metadata(&metadata, expires)?;
+                *sent = metadata;
+            }
+        }
+
+        let format = frame.format();
+        let (frame, updates) = self.partial_update(frame);
+        let bytes = frame.buffer_size();
+        let ptr = frame.into_raw();
+        let ret = unsafe { ffi::vsl_host_post(self.ptr, ptr, expires, duration, pts, dts) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { ffi::vsl_frame_release(ptr) };
+            return Err(VslError::from(HostError::Io(err)));
+        }
+        if self.partial_updates && self.control_frames {
+            // The host owns the posted frame until it expires, so the serial
+            // it was given can still be read.
+            let serial = unsafe { ffi::vsl_frame_serial(ptr) };
+            *self.partial_base.lock().unwrap() = Some(PartialBase {
+                format,
+                serial,
+                updates,
+            });
+        }
+        self.frames_posted.fetch_add(1, Ordering::Relaxed);
+        self.bytes_posted.fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(share) = recorded {
+            if let Some(recorder) = self.recorder.lock().unwrap().as_mut() {
+                recorder.record(share);
+            }
+        }
+
+        // The first socket only accepts connections, the rest are the clients
+        // signalled with the frame.
+        let client_count = self.list_sockets()?.len().saturating_sub(1);
+        return Ok(PostReceipt {
+            client_count,
+            bytes,
+        });
+    }
+
+    /// Returns the update of the dirty regions of the frame when clients hold
+    /// the previous frame, see [`HostBuilder::partial_updates`], otherwise
+    /// the frame to post in full, along with the partial updates posted since
+    /// the last frame posted in full.
+    fn partial_update(&self, frame: Frame) -> (Frame, u32) {
+        if !self.partial_updates || !self.control_frames {
+            return (frame, 0);
+        }
+        let base = match *self.partial_base.lock().unwrap() {
+            Some(base)
+                if base.format == frame.format()
+                    && base.updates < PARTIAL_REFRESH
+                    && !frame.dirty_regions().is_empty() =>
+            {
+                base
+            }
+            _ => return (frame, 0),
+        };
+        // Frames whose planes are unknown, such as encoded frames, are posted
+        // in full.
+        let update = partial::encode(&frame, base.serial)
+            .ok()
+            .and_then(|payload| signal_frame(crate::PARTIAL_FOURCC, &payload).ok());
+        match update {
+            Some(update) => return (update, base.updates + 1),
+            None => return (frame, 0),
+        }
+    }
+
+    fn replay_last_frame(&self) -> Result<(), VslError> {
+        let last_frame = self.last_frame.lock().unwrap();
+        let last = match last_frame.as_ref() {
+            Some(last) if last.replay => last,
+            _ => return Ok(()),
+        };
+
+        let ptr = last.frame.share()?.into_raw();
+        let expires = crate::timestamp() + last.lifetime;
+        let ret = unsafe {
+            ffi::vsl_host_post(self.ptr, ptr, expires, last.duration, last.pts, last.dts)
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { ffi::vsl_frame_release(ptr) };
+            return Err(VslError::from(err));
+        }
+        return Ok(());
+    }
 }
 
 impl Drop for Host {
@@ -54,3 +946,32 @@ impl Drop for Host {
         unsafe { ffi::vsl_host_release(self.ptr) }
     }
 }
+
+/// Returns a frame of the reserved `fourcc` holding `payload`, which clients
+/// of this crate decode rather than return.
+fn signal_frame(fourcc: u32, payload: &[u8]) -> Result<Frame, VslError> {
+    let len = payload.len() as u32;
+    let mut frame = match Frame::init(len, 1, len, fourcc) {
+        Ok(frame) => frame,
+        Err(err) => {
+            return Err(VslError::from(HostError::Io(io::Error::other(
+                err.to_string(),
+            ))))
+        }
+    };
+    if let Err(err) = frame.alloc(None) {
+        return Err(VslError::from(HostError::Io(io::Error::other(
+            err.to_string(),
+        ))));
+    }
+    match frame.mmap_mut() {
+        Ok(mem) => mem[..payload.len()].copy_from_slice(payload),
+        Err(err) => {
+            return Err(VslError::from(HostError::Io(io::Error::other(
+                err.to_string(),
+            ))))
+        }
+    }
+    frame.munmap();
+    return Ok(frame);
+}
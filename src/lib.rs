@@ -9,8 +9,21 @@
 //!
 //! [`DeepView Support Portal`]: https://support.deepviewml.com
 
-use std::{error::Error, ffi::CStr, fmt};
+use std::{
+    error::Error as StdError,
+    ffi::CStr,
+    fmt,
+    path::{Path, PathBuf},
+};
 use videostream_sys as ffi;
+
+// The crate-level VslError wrapping the errors of each module.
+mod error;
+pub use error::{Result, VslError};
+
+/// The crate-level error under the name used alongside [`Result`].
+pub type Error = VslError;
+
 /// The frame module provides the common frame handling functionality.
 pub mod frame;
 
@@ -22,35 +35,270 @@ pub mod host;
 
 pub mod encoder;
 
-#[derive(Debug)]
-struct NullStringError;
+/// The color module provides the colors used by the drawing helpers.
+pub mod color;
 
-impl Error for NullStringError {}
+// The acknowledgements of dropped frames sent by clients to their host.
+mod ack;
 
-impl fmt::Display for NullStringError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "invalid null string provided")
-    }
-}
+// The token authentication of clients connecting to a host.
+mod auth;
+
+/// The format module provides the layout knowledge of pixel formats.
+pub mod format;
+
+// The background reception of frames ahead of the consumer.
+mod prefetch;
+
+// The recording of the frames posted by a host.
+mod record;
+
+// The deltas of the dirty regions of frames posted by a host.
+mod partial;
+
+// The SIMD kernels behind the frame conversions.
+mod simd;
+
+// The frame metadata sent alongside frames to clients.
+mod metadata;
+
+/// The playback module provides the replay of recorded frames as a host.
+pub mod playback;
+
+// The dmabuf fences passed between GPU producers and consumers.
+mod sync_file;
+
+/// The tee module provides the fan out of a client's frames to several
+/// consumers.
+pub mod tee;
+
+/// The subscription module provides the callback delivery of a client's
+/// frames which survives reconnecting to the host.
+pub mod subscription;
+
+/// The mux module provides the recording of encoded frames into MP4 files.
+pub mod mux;
+
+/// The timebase module provides rescaling of frame timestamps for muxers.
+pub mod timebase;
+
+/// The util module provides helpers for producer loops, such as pacing, and
+/// the configuration of the crate's threads.
+pub mod util;
+
+/// The stream module provides the async Stream of received frames, enabled by
+/// the `async` feature.
+#[cfg(feature = "async")]
+pub mod stream;
+
+/// The fd_debug module provides file descriptor accounting for tracking down
+/// leaks, enabled by the `fd-debug` feature.
+#[cfg(feature = "fd-debug")]
+pub mod fd_debug;
+
+/// The fourcc of the heartbeat frames sent by [`host::Host::send_heartbeat`],
+/// which clients consume without returning them.  This and the reserved
+/// fourccs which follow are only posted by hosts built with
+/// [`host::HostBuilder::control_frames`].
+pub(crate) const HEARTBEAT_FOURCC: u32 = format::fourcc_code(b"VSHB");
+
+/// The fourcc of the frames carrying the probes sent by
+/// [`host::Host::post_probe`], which clients decode into a probe.
+pub(crate) const PROBE_FOURCC: u32 = format::fourcc_code(b"VSPR");
+
+/// The bytes of a probe's payload, its width, height and fourcc.
+pub(crate) const PROBE_SIZE: usize = 12;
+
+/// The fourcc of the goodbye sent to clients by [`host::Host::shutdown`],
+/// whose single byte is one of the reason codes which follow.
+pub(crate) const GOODBYE_FOURCC: u32 = format::fourcc_code(b"VSBY");
+
+/// The goodbye code of a host restarting, see
+/// [`host::ShutdownReason::Restart`].
+pub(crate) const GOODBYE_RESTART: u8 = 1;
+
+/// The goodbye code of a host terminating, see
+/// [`host::ShutdownReason::Terminate`].
+pub(crate) const GOODBYE_TERMINATE: u8 = 2;
+
+/// The fourcc of the frames carrying the framerate of hosts built with
+/// [`host::HostBuilder::frame_rate`], sent to each client as it connects.
+pub(crate) const RATE_FOURCC: u32 = format::fourcc_code(b"VSFR");
+
+/// The bytes of a framerate's payload, the frames per second as an f64.
+pub(crate) const RATE_SIZE: usize = 8;
+
+/// The fourcc of the frames carrying the dirty regions of a frame and the
+/// serial of the frame they update, sent by hosts built with
+/// [`host::HostBuilder::partial_updates`].
+pub(crate) const PARTIAL_FOURCC: u32 = format::fourcc_code(b"VSPD");
+
+/// The fourcc of the frames carrying the metadata of the frames which follow
+/// them, such as their field order, sent by hosts built with
+/// [`host::HostBuilder::control_frames`] as it changes.
+pub(crate) const METADATA_FOURCC: u32 = format::fourcc_code(b"VSMD");
 
 pub fn version() -> &'static str {
     let cstr = unsafe { CStr::from_ptr(ffi::vsl_version()) };
     return cstr.to_str().unwrap();
 }
 
+/// The library version the bindings were built against.
+pub const BUILT_AGAINST: &str = ffi::BUILT_AGAINST;
+
+/// Returns the major, minor and patch numbers of the loaded library's version,
+/// with any missing or unparsable part as 0.
+pub fn version_parts() -> (u32, u32, u32) {
+    return parse_version(version());
+}
+
+fn parse_version(version: &str) -> (u32, u32, u32) {
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse::<u32>().unwrap_or(0));
+    return (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    );
+}
+
+/// The loaded library has a different major version to the one the bindings
+/// were built against, so its ABI cannot be relied upon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMismatch {
+    pub runtime: &'static str,
+    pub built_against: &'static str,
+}
+
+impl StdError for VersionMismatch {}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "libvideostream {} is incompatible with the bindings built against {}",
+            self.runtime, self.built_against
+        )
+    }
+}
+
+/// Checks that the loaded library has the same major version as the one the
+/// bindings were built against.  Clients and hosts check this when created so
+/// a mismatched library, such as one from another container image, is
+/// reported rather than crashing.
+pub fn check_version_compatibility() -> Result<()> {
+    if version_parts().0 != parse_version(BUILT_AGAINST).0 {
+        return Err(VslError::from(VersionMismatch {
+            runtime: version(),
+            built_against: BUILT_AGAINST,
+        }));
+    }
+    return Ok(());
+}
+
+/// The oldest library version this crate has been tested against.
+const TESTED_VERSION_MIN: (u32, u32, u32) = (1, 3, 0);
+
+/// The first library version beyond those this crate has been tested against.
+const TESTED_VERSION_MAX: (u32, u32, u32) = (2, 0, 0);
+
+/// Returns true if the loaded library's version is within the range this crate
+/// has been tested against.  A library outside this range may not match the
+/// ABI of the bindings.
+pub fn version_is_tested() -> bool {
+    let parsed = version_parts();
+    return parsed >= TESTED_VERSION_MIN && parsed < TESTED_VERSION_MAX;
+}
+
+/// Returns a warning for the application to report when the loaded library is
+/// outside the tested range, see [`version_is_tested`], or None when it is
+/// within it.  The crate does not print the warning itself so embedders can
+/// route it to their own logging.
+pub fn version_warning() -> Option<String> {
+    if version_is_tested() {
+        return None;
+    }
+    return Some(format!(
+        "libvideostream {} is outside the tested range {}.{}.{} to {}.{}.{}",
+        version(),
+        TESTED_VERSION_MIN.0,
+        TESTED_VERSION_MIN.1,
+        TESTED_VERSION_MIN.2,
+        TESTED_VERSION_MAX.0,
+        TESTED_VERSION_MAX.1,
+        TESTED_VERSION_MAX.2,
+    ));
+}
+
+/// The Endpoint is the address of a host's socket, given to
+/// [`host::Host::bind`] and [`client::ClientOptions::connect_endpoint`].
+///
+/// Only socket files are supported.  The library builds the socket address
+/// from a NUL terminated path, which cannot express the leading NUL of a Linux
+/// abstract socket's name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// A socket file on the filesystem.
+    Path(PathBuf),
+}
+
+impl Endpoint {
+    /// Returns the socket path given to the library.
+    pub(crate) fn path(&self) -> &Path {
+        match self {
+            Endpoint::Path(path) => return path,
+        }
+    }
+}
+
+impl From<&Path> for Endpoint {
+    fn from(path: &Path) -> Self {
+        return Endpoint::Path(path.to_path_buf());
+    }
+}
+
+impl From<PathBuf> for Endpoint {
+    fn from(path: PathBuf) -> Self {
+        return Endpoint::Path(path);
+    }
+}
+
 pub fn timestamp() -> i64 {
     return unsafe { ffi::vsl_timestamp() };
 }
 
-pub fn fourcc(code: &str) -> u32 {
-    let bytes = code.as_bytes();
-    let mut fourcc: u32 = 0;
+/// The code given to [`fourcc`] is not exactly four ASCII characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FourccError {
+    pub code: String,
+}
+
+impl StdError for FourccError {}
 
-    for i in 0..4 {
-        fourcc |= (bytes[i] as u32) << (i * 8);
+impl fmt::Display for FourccError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "fourcc must be 4 character ascii code, got {:?}",
+            self.code
+        )
     }
+}
 
-    return fourcc;
+/// Returns the fourcc of the code, which must be exactly four ASCII
+/// characters.  Codes of fewer characters are padded with spaces by
+/// convention, such as "RGB " or "Y16 ", and the padding must be given.
+pub fn fourcc(code: &str) -> Result<u32> {
+    let bytes: [u8; 4] = match code.as_bytes().try_into() {
+        Ok(bytes) if code.is_ascii() => bytes,
+        _ => {
+            return Err(VslError::from(FourccError {
+                code: code.to_string(),
+            }))
+        }
+    };
+    return Ok(u32::from_le_bytes(bytes));
 }
 
 #[cfg(test)]
@@ -63,4 +311,32 @@ mod tests {
         let c_ver = unsafe { CStr::from_ptr(vsl_version()) };
         println!("VideoStream Library {}", c_ver.to_str().unwrap());
     }
+
+    #[test]
+    fn test_version_is_tested() {
+        assert!(super::version_is_tested());
+        assert_eq!(super::version_warning(), None);
+    }
+
+    #[test]
+    fn test_version_compatibility() {
+        assert_eq!(super::parse_version("1.3.2-rc1"), (1, 3, 2));
+        assert_eq!(super::parse_version("2"), (2, 0, 0));
+        assert!(super::check_version_compatibility().is_ok());
+    }
+
+    #[test]
+    fn test_fourcc() {
+        assert_eq!(super::fourcc("NV12").unwrap(), super::format::NV12);
+        assert_eq!(
+            super::fourcc("RGB ").unwrap(),
+            super::format::fourcc_code(b"RGB ")
+        );
+        for code in ["", "NV", "NV12 ", "NV\u{e9}"] {
+            assert!(matches!(
+                super::fourcc(code),
+                Err(super::VslError::InvalidFourcc(err)) if err.code == code
+            ));
+        }
+    }
 }
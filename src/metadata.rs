@@ -0,0 +1,113 @@
+use crate::{
+    color::{ColorRange, ColorSpace, HdrMetadata},
+    frame::{Interlace, Rotation},
+};
+
+/// Bytes of the serialization of [`HdrMetadata::to_bytes`].
+const HDR_LEN: usize = 28;
+
+/// The metadata of a frame which the library's frame descriptor has no room
+/// for, sent to clients by hosts built with
+/// [`crate::host::HostBuilder::control_frames`] ahead of the frames it
+/// describes, see [`crate::frame::Frame::metadata`].  The payload only
+/// grows, so fields missing from a shorter payload keep their defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Metadata {
+    pub(crate) interlace: Interlace,
+    /// The color space and range set on the frame, None when left to their
+    /// defaults.
+    pub(crate) color_space: Option<ColorSpace>,
+    pub(crate) color_range: Option<ColorRange>,
+    pub(crate) orientation: Rotation,
+    pub(crate) hdr: Option<HdrMetadata>,
+}
+
+impl Metadata {
+    /// Returns the payload of the control frame carrying the metadata.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let interlace = match self.interlace {
+            Interlace::Progressive => 0,
+            Interlace::TopFieldFirst => 1,
+            Interlace::BottomFieldFirst => 2,
+        };
+        let color_space = match self.color_space {
+            None => 0,
+            Some(ColorSpace::Bt601) => 1,
+            Some(ColorSpace::Bt709) => 2,
+            Some(ColorSpace::Bt2020) => 3,
+        };
+        let color_range = match self.color_range {
+            None => 0,
+            Some(ColorRange::Limited) => 1,
+            Some(ColorRange::Full) => 2,
+        };
+        let orientation = match self.orientation {
+            Rotation::None => 0,
+            Rotation::Rot90 => 1,
+            Rotation::Rot180 => 2,
+            Rotation::Rot270 => 3,
+            Rotation::FlipH => 4,
+            Rotation::FlipV => 5,
+        };
+        let mut payload = vec![interlace, color_space, color_range, orientation];
+        match &self.hdr {
+            Some(hdr) => {
+                payload.push(1);
+                payload.extend_from_slice(&hdr.to_bytes());
+            }
+            None => payload.push(0),
+        }
+        return payload;
+    }
+
+    /// Decodes the payload of a control frame carrying metadata, or None
+    /// when it holds values unknown to this version.
+    pub(crate) fn decode(payload: &[u8]) -> Option<Self> {
+        let mut meta = Metadata::default();
+        if let Some(&interlace) = payload.first() {
+            meta.interlace = match interlace {
+                0 => Interlace::Progressive,
+                1 => Interlace::TopFieldFirst,
+                2 => Interlace::BottomFieldFirst,
+                _ => return None,
+            };
+        }
+        if let Some(&color_space) = payload.get(1) {
+            meta.color_space = match color_space {
+                0 => None,
+                1 => Some(ColorSpace::Bt601),
+                2 => Some(ColorSpace::Bt709),
+                3 => Some(ColorSpace::Bt2020),
+                _ => return None,
+            };
+        }
+        if let Some(&color_range) = payload.get(2) {
+            meta.color_range = match color_range {
+                0 => None,
+                1 => Some(ColorRange::Limited),
+                2 => Some(ColorRange::Full),
+                _ => return None,
+            };
+        }
+        if let Some(&orientation) = payload.get(3) {
+            meta.orientation = match orientation {
+                0 => Rotation::None,
+                1 => Rotation::Rot90,
+                2 => Rotation::Rot180,
+                3 => Rotation::Rot270,
+                4 => Rotation::FlipH,
+                5 => Rotation::FlipV,
+                _ => return None,
+            };
+        }
+        match payload.get(4) {
+            None | Some(0) => {}
+            Some(1) => {
+                let bytes = payload.get(5..5 + HDR_LEN)?;
+                meta.hdr = Some(HdrMetadata::from_bytes(bytes)?);
+            }
+            Some(_) => return None,
+        }
+        return Some(meta);
+    }
+}
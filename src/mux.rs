@@ -0,0 +1,539 @@
+use crate::{
+    encoder::{extract_parameter_sets, insert_hdr_sei, nal_units, Encoder, EncoderError, VSLRect},
+    frame::Frame,
+    timebase::Timebase,
+    VslError,
+};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+};
+
+/// The timescale of a newly created Mp4Recorder, the 90kHz clock of
+/// [`Timebase::MPEG`].
+const DEFAULT_TIMESCALE: u32 = 90_000;
+
+/// The identity matrix of the movie and track headers, in 16.16 and 2.30
+/// fixed point.
+const MATRIX: [u32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000];
+
+/// The sample flags of a keyframe, which depends on no other sample.
+const SYNC_SAMPLE: u32 = 0x0200_0000;
+
+/// The sample flags of a frame predicted from others, which decoding cannot
+/// start from.
+const NON_SYNC_SAMPLE: u32 = 0x0101_0000;
+
+/// Errors reported while recording an MP4.
+#[derive(Debug)]
+pub enum MuxError {
+    /// The frame could not be encoded, including when the recorder was
+    /// created by [`Mp4Recorder::encoded`] without an encoder.
+    Encoder(EncoderError),
+    /// Writing to the sink failed.
+    Io(io::Error),
+    /// Only H.264 and H.265 streams can be recorded, and a recording cannot
+    /// change codec part way through.
+    UnsupportedCodec(u32),
+}
+
+impl Error for MuxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MuxError::Encoder(err) => return Some(err),
+            MuxError::Io(err) => return Some(err),
+            MuxError::UnsupportedCodec(_) => return None,
+        }
+    }
+}
+
+impl fmt::Display for MuxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MuxError::Encoder(err) => write!(f, "mux error: {}", err),
+            MuxError::Io(err) => write!(f, "mux error: {}", err),
+            MuxError::UnsupportedCodec(fourcc) => write!(
+                f,
+                "cannot record {} into the MP4",
+                String::from_utf8_lossy(&fourcc.to_le_bytes())
+            ),
+        }
+    }
+}
+
+impl From<EncoderError> for MuxError {
+    fn from(err: EncoderError) -> Self {
+        return MuxError::Encoder(err);
+    }
+}
+
+impl From<io::Error> for MuxError {
+    fn from(err: io::Error) -> Self {
+        return MuxError::Io(err);
+    }
+}
+
+/// The codecs which can be recorded, told apart by their NAL unit headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    H264,
+    H265,
+}
+
+impl Codec {
+    fn from_fourcc(fourcc: u32) -> Option<Self> {
+        match &fourcc.to_le_bytes() {
+            b"H264" => return Some(Codec::H264),
+            b"H265" | b"HEVC" => return Some(Codec::H265),
+            _ => return None,
+        }
+    }
+
+    fn nal_type(&self, nal: &[u8]) -> u8 {
+        match self {
+            Codec::H264 => return nal[0] & 0x1f,
+            Codec::H265 => return (nal[0] >> 1) & 0x3f,
+        }
+    }
+
+    /// Returns true for the parameter sets and access unit delimiters, which
+    /// the sample entry's configuration replaces.
+    fn is_config(&self, nal: &[u8]) -> bool {
+        match self {
+            Codec::H264 => return matches!(self.nal_type(nal), 7..=9),
+            Codec::H265 => return matches!(self.nal_type(nal), 32..=35),
+        }
+    }
+
+    /// Returns true for the slices decoding can start from, IDR slices for
+    /// H.264 and IRAP slices for H.265.
+    fn is_keyframe(&self, nal: &[u8]) -> bool {
+        match self {
+            Codec::H264 => return self.nal_type(nal) == 5,
+            Codec::H265 => return matches!(self.nal_type(nal), 16..=21),
+        }
+    }
+}
+
+/// A sample waiting for the next one, which determines its duration.
+struct Sample {
+    data: Vec<u8>,
+    time: i64,
+    duration: i64,
+    /// How long after its decode time the sample is presented, its pts less
+    /// its dts.
+    composition_offset: i64,
+    keyframe: bool,
+}
+
+/// The track of a recording, known once its first keyframe was written.
+struct Track {
+    fourcc: u32,
+    start: i64,
+}
+
+/// The Mp4Recorder records frames into a fragmented MP4, such as to save a
+/// clip of a camera stream, written progressively to any [`Write`] sink.
+///
+/// Raw frames are encoded by the recorder's [`Encoder`], while H.264 and
+/// H.265 frames, such as those received from a host posting an encoded
+/// stream, are recorded as they are.  The track's `avcC` or `hvcC`
+/// configuration is built from the parameter sets of the first keyframe, see
+/// [`Encoder::codec_config`], so the frames before it are skipped.  Each
+/// frame is written as its own fragment, so a recording cut short, such as
+/// by a power loss, keeps the frames written before.
+///
+/// The sample times are the frames' decode timestamps rescaled to the
+/// timescale, starting from zero, and each sample lasts until the next.
+pub struct Mp4Recorder<W: Write> {
+    encoder: Option<Encoder>,
+    writer: W,
+    timebase: Timebase,
+    track: Option<Track>,
+    pending: Option<Sample>,
+    sequence: u32,
+}
+
+impl<W: Write> Mp4Recorder<W> {
+    /// Creates a recorder encoding raw frames with `encoder` into `writer`.
+    pub fn new(encoder: Encoder, writer: W) -> Self {
+        let mut recorder = Mp4Recorder::encoded(writer);
+        recorder.encoder = Some(encoder);
+        return recorder;
+    }
+
+    /// Creates a recorder for frames which are already encoded, which fails
+    /// with [`EncoderError::Unavailable`] when given a raw frame.
+    pub fn encoded(writer: W) -> Self {
+        return Mp4Recorder {
+            encoder: None,
+            writer,
+            timebase: Timebase::new(1, DEFAULT_TIMESCALE as i64),
+            track: None,
+            pending: None,
+            sequence: 0,
+        };
+    }
+
+    /// The units per second of the recording's timestamps, 90000 by default.
+    /// It must be set before the first frame is pushed.
+    pub fn timescale(mut self, timescale: u32) -> Self {
+        if timescale > 0 {
+            self.timebase = Timebase::new(1, timescale as i64);
+        }
+        return self;
+    }
+
+    /// Records the frame, encoding it first unless it is already encoded.
+    pub fn push(&mut self, frame: &Frame) -> Result<(), VslError> {
+        if frame.format_info().is_none() {
+            let size = frame.buffer_size();
+            let mem = frame
+                .mmap()
+                .map_err(|err| EncoderError::Io(io::Error::other(err)))?;
+            let bitstream = mem.get(..size).unwrap_or(mem);
+            return self.add(frame.fourcc(), bitstream, frame, None);
+        }
+
+        let encoder = match &self.encoder {
+            Some(encoder) => encoder,
+            None => return Err(VslError::from(MuxError::Encoder(EncoderError::Unavailable))),
+        };
+        let (width, height) = (frame.width(), frame.height());
+        let output = encoder
+            .new_output_frame(width, height, frame.duration(), frame.pts(), frame.dts())
+            .map_err(|_| EncoderError::Unavailable)?;
+        let encoded = encoder.encode(frame, &output, &VSLRect::new(0, 0, width, height))?;
+        let fourcc = encoder.output_fourcc();
+        let config = encoder.codec_config().ok();
+        let size = output.buffer_size();
+        let mem = output
+            .mmap()
+            .map_err(|err| EncoderError::Io(io::Error::other(err)))?;
+        let bitstream = mem.get(..size).unwrap_or(mem);
+        match encoded.hdr_metadata {
+            Some(meta) if encoded.keyframe => {
+                let bitstream = insert_hdr_sei(fourcc, bitstream, &meta);
+                return self.add(fourcc, &bitstream, frame, config);
+            }
+            _ => return self.add(fourcc, bitstream, frame, config),
+        }
+    }
+
+    /// Writes the last frame and flushes the sink, which is returned.  A
+    /// recording without a keyframe is left empty.  Frames still held by the
+    /// encoder are not recorded, as it cannot be flushed, see
+    /// [`Encoder::flush`].
+    pub fn finish(mut self) -> Result<W, VslError> {
+        if let Some(sample) = self.pending.take() {
+            self.write_fragment(sample)?;
+        }
+        self.writer.flush()?;
+        return Ok(self.writer);
+    }
+
+    /// Adds the bitstream of the frame as the next sample, writing the file
+    /// header with the first keyframe.
+    fn add(
+        &mut self,
+        fourcc: u32,
+        bitstream: &[u8],
+        frame: &Frame,
+        config: Option<Vec<u8>>,
+    ) -> Result<(), VslError> {
+        let codec = Codec::from_fourcc(fourcc).ok_or(MuxError::UnsupportedCodec(fourcc))?;
+        let mut data = Vec::with_capacity(bitstream.len());
+        let mut keyframe = false;
+        for nal in nal_units(bitstream) {
+            if codec.is_config(nal) {
+                continue;
+            }
+            keyframe |= codec.is_keyframe(nal);
+            data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            data.extend_from_slice(nal);
+        }
+
+        let time = self.timebase.dts(frame);
+        let start = match &self.track {
+            Some(track) if track.fourcc != fourcc => {
+                return Err(VslError::from(MuxError::UnsupportedCodec(fourcc)));
+            }
+            Some(track) => track.start,
+            None => {
+                // Decoding starts from a keyframe and its parameter sets.
+                let config = config.unwrap_or_else(|| extract_parameter_sets(fourcc, bitstream));
+                let (width, height) = (frame.width().max(0) as u16, frame.height().max(0) as u16);
+                let timescale = self.timebase.den as u32;
+                let header = match init_segment(codec, width, height, timescale, &config) {
+                    Some(header) if keyframe => header,
+                    _ => return Ok(()),
+                };
+                self.writer.write_all(&header)?;
+                self.track = Some(Track {
+                    fourcc,
+                    start: time,
+                });
+                time
+            }
+        };
+
+        let sample = Sample {
+            data,
+            time: (time - start).max(0),
+            duration: self.timebase.duration(frame).max(0),
+            composition_offset: (self.timebase.pts(frame) - self.timebase.dts(frame)).max(0),
+            keyframe,
+        };
+        if let Some(mut previous) = self.pending.take() {
+            if sample.time > previous.time {
+                previous.duration = sample.time - previous.time;
+            }
+            self.write_fragment(previous)?;
+        }
+        self.pending = Some(sample);
+        return Ok(());
+    }
+
+    /// Writes the sample as a movie fragment followed by its data.
+    fn write_fragment(&mut self, sample: Sample) -> Result<(), VslError> {
+        self.sequence += 1;
+        let header = movie_fragment(self.sequence, &sample, 0);
+        // The data follows the fragment and the header of its box.
+        let offset = header.len() as u32 + 8;
+        self.writer
+            .write_all(&movie_fragment(self.sequence, &sample, offset))?;
+        self.writer.write_all(&mp4_box(b"mdat", &sample.data))?;
+        return Ok(());
+    }
+}
+
+/// Returns the box of the kind holding the payload.
+fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(payload.len() + 8);
+    data.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+    data.extend_from_slice(kind);
+    data.extend_from_slice(payload);
+    return data;
+}
+
+/// Returns the box of the kind with the version and flags of a full box.
+fn full_box(kind: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = (flags & 0xff_ffff | (version as u32) << 24)
+        .to_be_bytes()
+        .to_vec();
+    data.extend_from_slice(payload);
+    return mp4_box(kind, &data);
+}
+
+/// Returns the big endian bytes of the values.
+fn words(values: &[u32]) -> Vec<u8> {
+    return values
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect();
+}
+
+/// Returns the parameter sets with a 16-bit length before each, as stored by
+/// the `avcC` and `hvcC` boxes.
+fn length_prefixed(sets: &[&[u8]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for set in sets {
+        data.extend_from_slice(&(set.len() as u16).to_be_bytes());
+        data.extend_from_slice(set);
+    }
+    return data;
+}
+
+/// Removes the emulation prevention bytes of the NAL unit, recovering the
+/// fields of its header.
+fn unescape(nal: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if byte == 3 && zeros >= 2 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        data.push(byte);
+    }
+    return data;
+}
+
+/// Returns the `avcC` box of the H.264 parameter sets, or None without both
+/// an SPS and a PPS.
+fn avc_configuration(config: &[u8]) -> Option<Vec<u8>> {
+    let units = nal_units(config);
+    let sps: Vec<&[u8]> = units
+        .iter()
+        .copied()
+        .filter(|nal| return Codec::H264.nal_type(nal) == 7 && nal.len() >= 4)
+        .collect();
+    let pps: Vec<&[u8]> = units
+        .iter()
+        .copied()
+        .filter(|nal| return Codec::H264.nal_type(nal) == 8)
+        .collect();
+    if sps.is_empty() || pps.is_empty() {
+        return None;
+    }
+
+    // The profile, compatibility and level follow the NAL header of the SPS,
+    // and the samples have four byte lengths.
+    let mut data = vec![1, sps[0][1], sps[0][2], sps[0][3], 0xff];
+    data.push(0xe0 | sps.len().min(31) as u8);
+    data.extend(length_prefixed(&sps[..sps.len().min(31)]));
+    data.push(pps.len().min(255) as u8);
+    data.extend(length_prefixed(&pps[..pps.len().min(255)]));
+    return Some(mp4_box(b"avcC", &data));
+}
+
+/// Returns the `hvcC` box of the H.265 parameter sets, or None without a VPS,
+/// an SPS and a PPS.  The chroma format and bit depth are recorded as the
+/// 8-bit 4:2:0 produced by the library's encoders.
+fn hevc_configuration(config: &[u8]) -> Option<Vec<u8>> {
+    let units = nal_units(config);
+    let sets = |kind: u8| -> Vec<&[u8]> {
+        return units
+            .iter()
+            .copied()
+            .filter(|nal| return Codec::H265.nal_type(nal) == kind)
+            .collect();
+    };
+    let (vps, sps, pps) = (sets(32), sets(33), sets(34));
+    if vps.is_empty() || sps.is_empty() || pps.is_empty() {
+        return None;
+    }
+    // The SPS begins with its two byte NAL header, the sub-layers and the
+    // general profile, tier and level.
+    let header = unescape(sps[0]);
+    if header.len() < 15 {
+        return None;
+    }
+    let sub_layers = (header[2] >> 1) & 0x7;
+    let nested = header[2] & 0x1;
+
+    let mut data = vec![1];
+    data.extend_from_slice(&header[3..15]);
+    data.extend_from_slice(&[0xf0, 0x00, 0xfc, 0xfd, 0xf8, 0xf8, 0, 0]);
+    data.push((sub_layers + 1) << 3 | nested << 2 | 0x3);
+    data.push(3);
+    for (kind, units) in [(32, &vps), (33, &sps), (34, &pps)] {
+        data.push(0x80 | kind);
+        data.extend_from_slice(&(units.len() as u16).to_be_bytes());
+        data.extend(length_prefixed(units));
+    }
+    return Some(mp4_box(b"hvcC", &data));
+}
+
+/// Returns the file type and movie boxes describing the single video track,
+/// or None when the parameter sets are incomplete.
+fn init_segment(
+    codec: Codec,
+    width: u16,
+    height: u16,
+    timescale: u32,
+    config: &[u8],
+) -> Option<Vec<u8>> {
+    let (entry, configuration) = match codec {
+        Codec::H264 => (b"avc1", avc_configuration(config)?),
+        Codec::H265 => (b"hvc1", hevc_configuration(config)?),
+    };
+
+    let mut ftyp = b"isom".to_vec();
+    ftyp.extend(words(&[0x200]));
+    ftyp.extend_from_slice(b"isomiso6mp41");
+
+    let mut mvhd = words(&[0, 0, timescale, 0, 0x10000, 0x0100_0000, 0, 0]);
+    mvhd.extend(words(&MATRIX));
+    mvhd.extend(words(&[0, 0, 0, 0, 0, 0, 2]));
+
+    let mut tkhd = words(&[0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    tkhd.extend(words(&MATRIX));
+    tkhd.extend(words(&[(width as u32) << 16, (height as u32) << 16]));
+
+    // The language is "und", packed as three five bit letters.
+    let mdhd = words(&[0, 0, timescale, 0, 0x55c4_0000]);
+    let mut hdlr = words(&[0]);
+    hdlr.extend_from_slice(b"vide");
+    hdlr.extend(words(&[0, 0, 0]));
+    hdlr.extend_from_slice(b"VideoHandler\0");
+
+    let mut sample_entry = vec![0; 6];
+    sample_entry.extend_from_slice(&1u16.to_be_bytes());
+    sample_entry.extend(vec![0; 16]);
+    sample_entry.extend_from_slice(&width.to_be_bytes());
+    sample_entry.extend_from_slice(&height.to_be_bytes());
+    sample_entry.extend(words(&[0x0048_0000, 0x0048_0000, 0]));
+    sample_entry.extend_from_slice(&1u16.to_be_bytes());
+    sample_entry.extend(vec![0; 32]);
+    sample_entry.extend_from_slice(&[0x00, 0x18, 0xff, 0xff]);
+    sample_entry.extend(configuration);
+    let mut stsd = words(&[1]);
+    stsd.extend(mp4_box(entry, &sample_entry));
+
+    let stbl = [
+        full_box(b"stsd", 0, 0, &stsd),
+        full_box(b"stts", 0, 0, &words(&[0])),
+        full_box(b"stsc", 0, 0, &words(&[0])),
+        full_box(b"stsz", 0, 0, &words(&[0, 0])),
+        full_box(b"stco", 0, 0, &words(&[0])),
+    ]
+    .concat();
+    let mut dref = words(&[1]);
+    dref.extend(full_box(b"url ", 0, 1, &[]));
+    let minf = [
+        full_box(b"vmhd", 0, 1, &[0; 8]),
+        mp4_box(b"dinf", &full_box(b"dref", 0, 0, &dref)),
+        mp4_box(b"stbl", &stbl),
+    ]
+    .concat();
+    let mdia = [
+        full_box(b"mdhd", 0, 0, &mdhd),
+        full_box(b"hdlr", 0, 0, &hdlr),
+        mp4_box(b"minf", &minf),
+    ]
+    .concat();
+    let trak = [full_box(b"tkhd", 0, 3, &tkhd), mp4_box(b"mdia", &mdia)].concat();
+    let mvex = full_box(b"trex", 0, 0, &words(&[1, 1, 0, 0, 0]));
+    let moov = [
+        full_box(b"mvhd", 0, 0, &mvhd),
+        mp4_box(b"trak", &trak),
+        mp4_box(b"mvex", &mvex),
+    ]
+    .concat();
+    return Some([mp4_box(b"ftyp", &ftyp), mp4_box(b"moov", &moov)].concat());
+}
+
+/// Returns the movie fragment of the sample, whose data starts `offset` bytes
+/// from the start of the fragment.
+fn movie_fragment(sequence: u32, sample: &Sample, offset: u32) -> Vec<u8> {
+    let flags = if sample.keyframe {
+        SYNC_SAMPLE
+    } else {
+        NON_SYNC_SAMPLE
+    };
+    // The data offset, duration, size, flags and composition offset of the
+    // sample are given.
+    let trun = words(&[
+        1,
+        offset,
+        sample.duration as u32,
+        sample.data.len() as u32,
+        flags,
+        sample.composition_offset as u32,
+    ]);
+    let traf = [
+        full_box(b"tfhd", 0, 0x02_0000, &words(&[1])),
+        full_box(b"tfdt", 1, 0, &(sample.time as u64).to_be_bytes()),
+        full_box(b"trun", 0, 0xf01, &trun),
+    ]
+    .concat();
+    let moof = [
+        full_box(b"mfhd", 0, 0, &words(&[sequence])),
+        mp4_box(b"traf", &traf),
+    ]
+    .concat();
+    return mp4_box(b"moof", &moof);
+}
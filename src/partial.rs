@@ -0,0 +1,170 @@
+use crate::{
+    encoder::VSLRect,
+    format::Plane,
+    frame::{self, Frame, FrameError, FrameFormat},
+    VslError,
+};
+use std::ops::Range;
+
+/// Bytes of the serial of the frame a delta updates, which heads the delta.
+const SERIAL_BYTES: usize = 8;
+
+/// The values following the serial: the fourcc, width, height and stride of
+/// the frame the delta updates, then the number of rectangles.
+const HEADER_VALUES: usize = 5;
+
+/// The values describing each rectangle: its x, y, width and height.
+const RECT_VALUES: usize = 4;
+
+/// Encodes the dirty regions marked on the frame, see [`Frame::mark_dirty`],
+/// into the payload of a delta updating the frame posted with `base_serial`.
+/// The header and rectangles are followed by the rows each rectangle covers
+/// in every plane, plane by plane.
+pub(crate) fn encode(frame: &Frame, base_serial: i64) -> Result<Vec<u8>, VslError> {
+    let format = frame.format();
+    let (planes, elements) = layout(frame)?;
+    let rects = frame.dirty_regions();
+    let src = frame.mmap()?;
+
+    let mut payload = base_serial.to_le_bytes().to_vec();
+    let header = [
+        format.fourcc,
+        format.width,
+        format.height,
+        format.stride,
+        rects.len() as u32,
+    ];
+    for value in header {
+        payload.extend_from_slice(&value.to_le_bytes());
+    }
+    for rect in rects {
+        for value in [
+            rect.get_x(),
+            rect.get_y(),
+            rect.get_width(),
+            rect.get_height(),
+        ] {
+            payload.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+    }
+    for (plane, bytes) in planes.iter().zip(elements) {
+        for rect in rects {
+            let (rows, cols) = span(plane, bytes, rect, &format);
+            for y in rows {
+                let start = plane.offset + y * plane.stride;
+                let row = src
+                    .get(start + cols.start..start + cols.end)
+                    .ok_or(FrameError::OutOfBounds)?;
+                payload.extend_from_slice(row);
+            }
+        }
+    }
+    return Ok(payload);
+}
+
+/// Returns the serial of the frame the delta updates, or None when the delta
+/// is too short to hold one.
+pub(crate) fn base_serial(delta: &Frame) -> Option<i64> {
+    let mem = delta.mmap().ok()?;
+    let serial = mem.get(..SERIAL_BYTES)?;
+    return Some(i64::from_le_bytes(serial.try_into().unwrap()));
+}
+
+/// Returns a copy of `base` updated by the delta, a frame received holding
+/// the payload of [`encode`], carrying the delta's timestamps.  The delta
+/// must describe the format of `base`, otherwise it updates another frame,
+/// and should be checked to update `base` through [`base_serial`].
+pub(crate) fn apply(base: &Frame, delta: &Frame) -> Result<Frame, VslError> {
+    let mem = delta.mmap()?;
+    let payload = &mem[..delta.buffer_size().min(mem.len())];
+    let (_, mut payload) = payload
+        .split_at_checked(SERIAL_BYTES)
+        .ok_or(FrameError::OutOfBounds)?;
+    let mut header = [0; HEADER_VALUES];
+    for value in header.iter_mut() {
+        *value = take_u32(&mut payload)?;
+    }
+    let format = FrameFormat {
+        fourcc: header[0],
+        width: header[1],
+        height: header[2],
+        stride: header[3],
+    };
+    if base.format() != format {
+        return Err(VslError::from(FrameError::DimensionMismatch));
+    }
+    let count = header[4] as usize;
+    if count > payload.len() / (RECT_VALUES * 4) {
+        return Err(VslError::from(FrameError::OutOfBounds));
+    }
+    let mut rects = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut values = [0; RECT_VALUES];
+        for value in values.iter_mut() {
+            *value = take_u32(&mut payload)? as i32;
+        }
+        let rect = VSLRect::new(values[0], values[1], values[2], values[3]);
+        if !rect.fits(format.width as i32, format.height as i32) {
+            return Err(VslError::from(FrameError::OutOfBounds));
+        }
+        rects.push(rect);
+    }
+
+    let mut frame = base.alloc_copy(None)?;
+    frame.copy_timing(delta);
+    let (planes, elements) = layout(&frame)?;
+    let dst = frame.mmap_mut()?;
+    for (plane, bytes) in planes.iter().zip(elements) {
+        for rect in &rects {
+            let (rows, cols) = span(plane, bytes, rect, &format);
+            for y in rows {
+                let (row, rest) = payload
+                    .split_at_checked(cols.len())
+                    .ok_or(FrameError::OutOfBounds)?;
+                let start = plane.offset + y * plane.stride;
+                dst.get_mut(start + cols.start..start + cols.end)
+                    .ok_or(FrameError::OutOfBounds)?
+                    .copy_from_slice(row);
+                payload = rest;
+            }
+        }
+    }
+    return Ok(frame);
+}
+
+/// Returns the planes of the frame with the bytes of an element of each.
+fn layout(frame: &Frame) -> Result<(Vec<Plane>, Vec<usize>), VslError> {
+    let fourcc = frame.fourcc();
+    let elements = frame::element_bytes(fourcc).ok_or(FrameError::UnsupportedFourcc(fourcc))?;
+    return Ok((frame.planes()?, elements));
+}
+
+/// Returns the rows of the plane covered by the rectangle and the bytes of
+/// each, rounded out to whole elements so subsampled planes cover every
+/// pixel of the rectangle.
+fn span(
+    plane: &Plane,
+    bytes: usize,
+    rect: &VSLRect,
+    format: &FrameFormat,
+) -> (Range<usize>, Range<usize>) {
+    let (width, height) = (format.width as usize, format.height as usize);
+    let (x, y) = (rect.get_x() as usize, rect.get_y() as usize);
+    let right = x + rect.get_width() as usize;
+    let bottom = y + rect.get_height() as usize;
+
+    let top = y * plane.rows / height;
+    let bottom = (bottom * plane.rows).div_ceil(height).min(plane.rows);
+    let left = x * plane.row_bytes / width / bytes * bytes;
+    let right = (right * plane.row_bytes)
+        .div_ceil(width)
+        .next_multiple_of(bytes)
+        .min(plane.row_bytes);
+    return (top..bottom, left..right);
+}
+
+fn take_u32(payload: &mut &[u8]) -> Result<u32, VslError> {
+    let (value, rest) = payload.split_at_checked(4).ok_or(FrameError::OutOfBounds)?;
+    *payload = rest;
+    return Ok(u32::from_le_bytes(value.try_into().unwrap()));
+}
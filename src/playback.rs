@@ -0,0 +1,126 @@
+use crate::{frame::Frame, host::Host, util::Pacer, VslError};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+/// The framerate of a newly created FileHost.
+const DEFAULT_FRAME_RATE: f64 = 30.0;
+
+/// The FileHost replays frames recorded with [`Frame::save`] through a
+/// [`Host`], so clients receive them as they would from a live source.  This
+/// gives pipelines a deterministic input for testing without a camera.
+///
+/// The frames are read from a single file of concatenated frames or from every
+/// file in a directory, in order of their names, and held in memory.  Each is
+/// posted by sharing its buffer, so replaying does not copy the frames.
+pub struct FileHost {
+    host: Host,
+    frames: Vec<Frame>,
+    fps: f64,
+    looping: bool,
+}
+
+impl FileHost {
+    /// Creates the host at `path` which will replay the frames read from
+    /// `source`, a file or a directory of files.
+    pub fn new<P: AsRef<Path>>(path: P, source: &Path) -> Result<Self, VslError> {
+        let mut files = Vec::new();
+        if source.is_dir() {
+            for entry in fs::read_dir(source)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    files.push(entry.path());
+                }
+            }
+            files.sort();
+        } else {
+            files.push(source.to_path_buf());
+        }
+
+        let mut frames = Vec::new();
+        for file in files {
+            let mut reader = BufReader::new(File::open(file)?);
+            while !reader.fill_buf()?.is_empty() {
+                frames.push(Frame::load(&mut reader)?);
+            }
+        }
+        if frames.is_empty() {
+            let message = format!("no frames found in {}", source.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+        }
+
+        return Ok(FileHost {
+            host: Host::new(path)?,
+            frames,
+            fps: DEFAULT_FRAME_RATE,
+            looping: false,
+        });
+    }
+
+    /// The rate at which frames are posted, 30 frames per second by default.
+    pub fn frame_rate(mut self, fps: f64) -> Self {
+        if fps > 0.0 {
+            self.fps = fps;
+        }
+        return self;
+    }
+
+    /// Whether to start over from the first frame once the last was posted,
+    /// otherwise [`FileHost::run`] returns after posting the last frame.
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        return self;
+    }
+
+    /// Returns the host serving the frames, to post other frames or register
+    /// for client connections.
+    pub fn host(&self) -> &Host {
+        return &self.host;
+    }
+
+    /// Returns the number of frames replayed.
+    pub fn frame_count(&self) -> usize {
+        return self.frames.len();
+    }
+
+    /// Replays the frames, through [`Host::serve`], until the last frame was
+    /// posted or forever when looping.
+    pub fn run(&self) -> Result<(), VslError> {
+        return self.run_with_cancel(Arc::new(AtomicBool::new(false)));
+    }
+
+    /// Replays the frames as [`FileHost::run`] until `cancel` is set from
+    /// another thread.
+    pub fn run_with_cancel(&self, cancel: Arc<AtomicBool>) -> Result<(), VslError> {
+        let mut index = 0;
+        let mut pacer = Pacer::new(self.fps);
+        let mut error = None;
+        let produce = || {
+            if index == self.frames.len() {
+                if !self.looping {
+                    return None;
+                }
+                index = 0;
+            }
+            pacer.wait();
+
+            let frame = match self.frames[index].share() {
+                Ok(frame) => frame,
+                Err(err) => {
+                    error = Some(err);
+                    return None;
+                }
+            };
+            index += 1;
+            return Some(frame);
+        };
+        self.host.serve_with_cancel(produce, cancel)?;
+        match error {
+            Some(err) => return Err(err),
+            None => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{client::ClientHandle, frame::Frame, util::ThreadConfig};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use videostream_sys as ffi;
+
+/// The interval between waits of a reconnecting client whose host is away.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A frame received ahead of the consumer, with the times it arrived.
+pub(crate) struct Prefetched {
+    pub frame: Frame,
+    /// The library timestamp of the arrival, for measuring jitter.
+    pub timestamp: i64,
+    pub instant: Instant,
+}
+
+/// The Prefetcher receives a client's frames on a dedicated thread into a
+/// bounded queue, so the consumer's wait returns a frame which has already
+/// arrived while the next ones are received.  The oldest frame is dropped
+/// when the queue is full, so the queue holds the latest frames.
+pub(crate) struct Prefetcher {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    // Held here rather than by the thread, so the client counts it once.
+    handle: Arc<ClientHandle>,
+    depth: usize,
+    reconnect: bool,
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+struct State {
+    frames: VecDeque<Prefetched>,
+    /// The errno of the last failed wait.
+    error: Option<i32>,
+    dropped: u64,
+    /// The thread stopped after a failed wait of a client not reconnecting.
+    finished: bool,
+    closed: bool,
+}
+
+impl Prefetcher {
+    pub fn spawn(
+        handle: Arc<ClientHandle>,
+        depth: usize,
+        reconnect: bool,
+        config: &ThreadConfig,
+    ) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            handle,
+            depth: depth.max(1),
+            reconnect,
+            state: Mutex::new(State {
+                frames: VecDeque::with_capacity(depth),
+                error: None,
+                dropped: 0,
+                finished: false,
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        });
+        let fetcher = shared.clone();
+        config.spawn("vsl-prefetch", move || fetch(fetcher))?;
+        return Ok(Prefetcher { shared });
+    }
+
+    /// Returns the next frame with a timestamp of at least `until`, waiting up
+    /// to `timeout` for one to arrive.  Fails with the errno of the library's
+    /// waits, such as ETIMEDOUT, as the client does without prefetching.
+    pub fn pop(&self, until: i64, timeout: Duration) -> io::Result<Prefetched> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            while let Some(prefetched) = state.frames.pop_front() {
+                // Older frames are released, as the library's wait does.
+                if prefetched.frame.timestamp() >= until {
+                    return Ok(prefetched);
+                }
+            }
+            if let Some(errno) = state.error {
+                // A reconnecting client only reports each failure once.
+                if !state.finished {
+                    state.error = None;
+                }
+                return Err(io::Error::from_raw_os_error(errno));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::from_raw_os_error(libc::ETIMEDOUT));
+            }
+            state = self
+                .shared
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Returns the number of frames dropped as the queue was full.
+    pub fn dropped(&self) -> u64 {
+        return self.shared.state.lock().unwrap().dropped;
+    }
+
+    /// Stops the thread, which exits once its current wait returns, and
+    /// releases the queued frames.
+    pub fn close(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.frames.clear();
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn fetch(shared: Arc<Shared>) {
+    loop {
+        if shared.state.lock().unwrap().closed {
+            return;
+        }
+        let ptr = unsafe { ffi::vsl_frame_wait(shared.handle.0, 0) };
+        if ptr.is_null() {
+            let err = io::Error::last_os_error();
+            if let io::ErrorKind::TimedOut | io::ErrorKind::Interrupted = err.kind() {
+                continue;
+            }
+            let mut state = shared.state.lock().unwrap();
+            state.error = Some(err.raw_os_error().unwrap_or(libc::EIO));
+            state.finished = !shared.reconnect;
+            shared.ready.notify_all();
+            if state.finished {
+                return;
+            }
+            drop(state);
+            thread::sleep(RETRY_INTERVAL);
+            continue;
+        }
+
+        let prefetched = Prefetched {
+            frame: Frame::from_client(ptr, shared.handle.clone()),
+            timestamp: crate::timestamp(),
+            instant: Instant::now(),
+        };
+        let mut state = shared.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        while state.frames.len() >= shared.depth {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+        state.frames.push_back(prefetched);
+        shared.ready.notify_all();
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{frame::Frame, host::RecordFormat, util::ThreadConfig};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::JoinHandle,
+};
+
+/// The frames waiting to be written before further frames are dropped from
+/// the recording, so a slow disk never holds back the host.
+const QUEUE_DEPTH: usize = 8;
+
+/// The Recorder writes the frames posted by a host to a file on a dedicated
+/// thread.  Each frame is queued by sharing its buffer, so recording does not
+/// copy frames on the posting thread.
+pub(crate) struct Recorder {
+    format: RecordFormat,
+    sender: Option<SyncSender<Frame>>,
+    thread: Option<JoinHandle<Option<io::Result<u64>>>>,
+    dropped: u64,
+}
+
+impl Recorder {
+    pub fn start(path: &Path, format: RecordFormat) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_DEPTH);
+        let thread = ThreadConfig::default().spawn("vsl-record", move || {
+            return write(writer, format, receiver);
+        })?;
+        return Ok(Recorder {
+            format,
+            sender: Some(sender),
+            thread: Some(thread),
+            dropped: 0,
+        });
+    }
+
+    /// Shares the frame about to be posted with `pts` for recording once
+    /// posted, or returns None if the format does not record it.
+    pub fn share(&mut self, frame: &Frame, pts: i64) -> Option<Frame> {
+        // Compressed frames have no known pixel layout.
+        let encoded = frame.format_info().is_none();
+        if encoded != (self.format == RecordFormat::Encoded) {
+            return None;
+        }
+        match frame.share() {
+            Ok(mut share) => {
+                share.set_pts(pts);
+                return Some(share);
+            }
+            Err(_) => {
+                self.dropped += 1;
+                return None;
+            }
+        }
+    }
+
+    /// Queues the share of a posted frame.  Frames are dropped from the
+    /// recording when the queue is full or the file could not be written.
+    pub fn record(&mut self, share: Frame) {
+        if let Some(sender) = &self.sender {
+            // The receiver is gone once writing to the file failed.
+            if sender.try_send(share).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Writes the queued frames and closes the file, returning the number of
+    /// frames written and dropped.
+    pub fn stop(mut self) -> io::Result<(u64, u64)> {
+        return Ok((self.finish()?, self.dropped));
+    }
+
+    fn finish(&mut self) -> io::Result<u64> {
+        // Closing the queue ends the thread once it is drained.
+        self.sender = None;
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(0),
+        };
+        match thread.join() {
+            Ok(Some(result)) => return result,
+            Ok(None) => return Ok(0),
+            Err(_) => return Err(io::Error::other("recording thread panicked")),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+fn write(
+    mut writer: BufWriter<File>,
+    format: RecordFormat,
+    receiver: Receiver<Frame>,
+) -> io::Result<u64> {
+    let mut written = 0;
+    for frame in receiver {
+        match format {
+            RecordFormat::Raw => writer.write_all(&frame.to_vec().map_err(io::Error::other)?)?,
+            RecordFormat::Saved => frame.save(&mut writer).map_err(io::Error::other)?,
+            RecordFormat::Encoded => {
+                let size = frame.buffer_size();
+                let mem = frame.mmap().map_err(io::Error::other)?;
+                writer.write_all(mem.get(..size).unwrap_or(mem))?;
+            }
+        }
+        written += 1;
+    }
+    writer.flush()?;
+    return Ok(written);
+}
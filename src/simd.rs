@@ -0,0 +1,171 @@
+use crate::color::YuvMatrix;
+
+/// Pixels converted by each call of a SIMD kernel.
+const BLOCK: usize = 8;
+
+/// Converts a block of pixels, from their luma and interleaved chroma, into
+/// the red, green and blue channels.
+type Kernel = unsafe fn(&[u8], &[u8], &YuvMatrix, &mut [[u8; BLOCK]; 3]);
+
+/// Returns the SIMD kernel supported by the CPU, or None to use the scalar
+/// conversion.  Building without the `simd` feature always uses the scalar
+/// conversion.
+fn kernel() -> Option<Kernel> {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        return Some(x86::nv12_avx2);
+    }
+    #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return Some(arm::nv12_neon);
+    }
+    return None;
+}
+
+/// Converts a row of NV12 pixels into separate red, green and blue rows, each
+/// the width of the `luma` row.  The `chroma` row holds the interleaved Cb and
+/// Cr shared by each pair of pixels, rounded up to cover an odd last pixel.
+pub(crate) fn nv12_row(luma: &[u8], chroma: &[u8], matrix: &YuvMatrix, rgb: [&mut [u8]; 3]) {
+    let width = luma.len();
+    let [r, g, b] = rgb;
+    let mut x = 0;
+    if let Some(kernel) = kernel() {
+        let mut block = [[0u8; BLOCK]; 3];
+        while x + BLOCK <= width {
+            unsafe {
+                kernel(
+                    &luma[x..x + BLOCK],
+                    &chroma[x..x + BLOCK],
+                    matrix,
+                    &mut block,
+                )
+            };
+            r[x..x + BLOCK].copy_from_slice(&block[0]);
+            g[x..x + BLOCK].copy_from_slice(&block[1]);
+            b[x..x + BLOCK].copy_from_slice(&block[2]);
+            x += BLOCK;
+        }
+    }
+    for x in x..width {
+        let color = matrix.convert(luma[x], chroma[x & !1], chroma[x | 1]);
+        r[x] = color.r;
+        g[x] = color.g;
+        b[x] = color.b;
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod x86 {
+    use super::BLOCK;
+    use crate::color::{YuvMatrix, YUV_SHIFT};
+    use std::arch::x86_64::*;
+
+    /// Converts a block with AVX2, computing the eight pixels in 32 bit lanes
+    /// with the same arithmetic as [`YuvMatrix::convert`].
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn nv12_avx2(
+        luma: &[u8],
+        chroma: &[u8],
+        matrix: &YuvMatrix,
+        rgb: &mut [[u8; BLOCK]; 3],
+    ) {
+        debug_assert!(luma.len() >= BLOCK && chroma.len() >= BLOCK);
+        let y = _mm256_cvtepu8_epi32(_mm_loadl_epi64(luma.as_ptr() as *const __m128i));
+        let uv = _mm256_cvtepu8_epi32(_mm_loadl_epi64(chroma.as_ptr() as *const __m128i));
+        let uv = _mm256_sub_epi32(uv, _mm256_set1_epi32(128));
+        // Each chroma sample is shared by a pair of pixels.
+        let u = _mm256_permutevar8x32_epi32(uv, _mm256_setr_epi32(0, 0, 2, 2, 4, 4, 6, 6));
+        let v = _mm256_permutevar8x32_epi32(uv, _mm256_setr_epi32(1, 1, 3, 3, 5, 5, 7, 7));
+
+        let y = _mm256_sub_epi32(y, _mm256_set1_epi32(matrix.y_offset));
+        let y = _mm256_add_epi32(
+            _mm256_mullo_epi32(y, _mm256_set1_epi32(matrix.y)),
+            _mm256_set1_epi32(1 << (YUV_SHIFT - 1)),
+        );
+        let r = _mm256_add_epi32(y, _mm256_mullo_epi32(v, _mm256_set1_epi32(matrix.rv)));
+        let g = _mm256_sub_epi32(y, _mm256_mullo_epi32(u, _mm256_set1_epi32(matrix.gu)));
+        let g = _mm256_sub_epi32(g, _mm256_mullo_epi32(v, _mm256_set1_epi32(matrix.gv)));
+        let b = _mm256_add_epi32(y, _mm256_mullo_epi32(u, _mm256_set1_epi32(matrix.bu)));
+
+        store(r, &mut rgb[0]);
+        store(g, &mut rgb[1]);
+        store(b, &mut rgb[2]);
+    }
+
+    /// Scales the fixed point channel back to bytes, saturating to 0..=255.
+    #[target_feature(enable = "avx2")]
+    unsafe fn store(channel: __m256i, out: &mut [u8; BLOCK]) {
+        let channel = _mm256_srai_epi32::<YUV_SHIFT>(channel);
+        // Packing saturates and works within each 128 bit lane, leaving bytes
+        // 0..4 of the first lane and 16..20 of the second.
+        let packed = _mm256_packus_epi32(channel, channel);
+        let packed = _mm256_packus_epi16(packed, packed);
+        let low = _mm_cvtsi128_si32(_mm256_castsi256_si128(packed));
+        let high = _mm_cvtsi128_si32(_mm256_extracti128_si256::<1>(packed));
+        out[..4].copy_from_slice(&low.to_le_bytes());
+        out[4..].copy_from_slice(&high.to_le_bytes());
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod arm {
+    use super::BLOCK;
+    use crate::color::{YuvMatrix, YUV_SHIFT};
+    use std::arch::aarch64::*;
+
+    /// Converts a block with NEON, computing each half of the eight pixels in
+    /// 32 bit lanes with the same arithmetic as [`YuvMatrix::convert`].
+    #[target_feature(enable = "neon")]
+    pub unsafe fn nv12_neon(
+        luma: &[u8],
+        chroma: &[u8],
+        matrix: &YuvMatrix,
+        rgb: &mut [[u8; BLOCK]; 3],
+    ) {
+        debug_assert!(luma.len() >= BLOCK && chroma.len() >= BLOCK);
+        let y = vmovl_u8(vld1_u8(luma.as_ptr()));
+        let uv = vld1_u8(chroma.as_ptr());
+        // Each chroma sample is shared by a pair of pixels.
+        let u = vmovl_u8(vtrn1_u8(uv, uv));
+        let v = vmovl_u8(vtrn2_u8(uv, uv));
+
+        let (r0, g0, b0) = convert(vget_low_u16(y), vget_low_u16(u), vget_low_u16(v), matrix);
+        let (r1, g1, b1) = convert(vget_high_u16(y), vget_high_u16(u), vget_high_u16(v), matrix);
+        vst1_u8(rgb[0].as_mut_ptr(), narrow(r0, r1));
+        vst1_u8(rgb[1].as_mut_ptr(), narrow(g0, g1));
+        vst1_u8(rgb[2].as_mut_ptr(), narrow(b0, b1));
+    }
+
+    /// Converts four pixels to their fixed point red, green and blue.
+    #[target_feature(enable = "neon")]
+    unsafe fn convert(
+        y: uint16x4_t,
+        u: uint16x4_t,
+        v: uint16x4_t,
+        matrix: &YuvMatrix,
+    ) -> (int32x4_t, int32x4_t, int32x4_t) {
+        let widen = |c: uint16x4_t| vreinterpretq_s32_u32(vmovl_u16(c));
+        let bias = vdupq_n_s32(128);
+        let (u, v) = (vsubq_s32(widen(u), bias), vsubq_s32(widen(v), bias));
+
+        let y = vsubq_s32(widen(y), vdupq_n_s32(matrix.y_offset));
+        let y = vaddq_s32(
+            vmulq_s32(y, vdupq_n_s32(matrix.y)),
+            vdupq_n_s32(1 << (YUV_SHIFT - 1)),
+        );
+        let r = vaddq_s32(y, vmulq_s32(v, vdupq_n_s32(matrix.rv)));
+        let g = vsubq_s32(y, vmulq_s32(u, vdupq_n_s32(matrix.gu)));
+        let g = vsubq_s32(g, vmulq_s32(v, vdupq_n_s32(matrix.gv)));
+        let b = vaddq_s32(y, vmulq_s32(u, vdupq_n_s32(matrix.bu)));
+        return (r, g, b);
+    }
+
+    /// Scales two halves of a fixed point channel back to bytes, saturating
+    /// to 0..=255.
+    #[target_feature(enable = "neon")]
+    unsafe fn narrow(low: int32x4_t, high: int32x4_t) -> uint8x8_t {
+        let low = vqmovun_s32(vshrq_n_s32::<YUV_SHIFT>(low));
+        let high = vqmovun_s32(vshrq_n_s32::<YUV_SHIFT>(high));
+        return vqmovn_u16(vcombine_u16(low, high));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    client::{Client, ClientError},
+    frame::Frame,
+    VslError,
+};
+use futures_core::Stream;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// The policy of a newly created stream, holding back the receiving thread
+/// while a single frame waits to be polled.
+const DEFAULT_POLICY: BufferPolicy = BufferPolicy::Block(1);
+
+/// How a [`FrameStream`] handles frames arriving faster than they are polled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferPolicy {
+    /// Holds a single frame, replaced by each newer frame so the stream
+    /// yields the latest one, as suits live viewers.
+    DropOldest,
+    /// Holds a single frame, discarding the frames arriving until it was
+    /// polled.
+    DropNewest,
+    /// Holds up to the given number of frames, at least one, then stops
+    /// receiving until the consumer polls, so no frame is lost as long as
+    /// the host keeps them, as suits recorders.
+    Block(usize),
+}
+
+impl BufferPolicy {
+    fn capacity(&self) -> usize {
+        match self {
+            BufferPolicy::DropOldest | BufferPolicy::DropNewest => return 1,
+            BufferPolicy::Block(capacity) => return (*capacity).max(1),
+        }
+    }
+}
+
+/// The FrameStream yields the frames received by a [`Client`] as a
+/// [`Stream`], created by [`Client::into_stream`].
+///
+/// The library does not expose the client's socket to register with a
+/// reactor, so the stream waits for frames on a dedicated thread and wakes
+/// the polling task as each one arrives.  The frames waiting to be polled are
+/// held as set by [`FrameStream::with_policy`], by default a single frame
+/// which holds back the receiving thread rather than accumulating frames.
+///
+/// The stream keeps no reference to the frames it yields, each is released as
+/// soon as the consumer drops it, so the frames locked at any time are those
+/// the consumer holds and those waiting to be polled.
+///
+/// Timeouts are not reported, the stream keeps waiting for the next frame.
+/// Any other error is yielded once, after the held frames, and then the
+/// stream ends.
+pub struct FrameStream {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+struct State {
+    frames: VecDeque<Frame>,
+    error: Option<VslError>,
+    policy: BufferPolicy,
+    dropped: u64,
+    waker: Option<Waker>,
+    done: bool,
+    closed: bool,
+}
+
+impl FrameStream {
+    pub(crate) fn new(client: Client) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                frames: VecDeque::new(),
+                error: None,
+                policy: DEFAULT_POLICY,
+                dropped: 0,
+                waker: None,
+                done: false,
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        });
+
+        let receiver = shared.clone();
+        let config = client.thread_config().clone();
+        if let Err(err) = config.spawn("vsl-stream", move || receive(client, receiver)) {
+            let mut state = shared.state.lock().unwrap();
+            state.error = Some(VslError::from(ClientError::Io(err)));
+            state.done = true;
+        }
+
+        return FrameStream { shared };
+    }
+
+    /// Sets how the stream holds frames which have yet to be polled,
+    /// [`BufferPolicy::Block`] holding a single frame by default.
+    pub fn with_policy(self, policy: BufferPolicy) -> Self {
+        self.shared.state.lock().unwrap().policy = policy;
+        // A blocked receiving thread may now have room.
+        self.shared.ready.notify_one();
+        return self;
+    }
+
+    /// Returns the policy set by [`FrameStream::with_policy`].
+    pub fn policy(&self) -> BufferPolicy {
+        return self.shared.state.lock().unwrap().policy;
+    }
+
+    /// Returns the number of frames discarded under the drop policies.
+    pub fn dropped(&self) -> u64 {
+        return self.shared.state.lock().unwrap().dropped;
+    }
+}
+
+fn receive(client: Client, shared: Arc<Shared>) {
+    loop {
+        let mut state = shared.state.lock().unwrap();
+        // The policy may change from blocking while the thread waits.
+        while matches!(state.policy, BufferPolicy::Block(_))
+            && state.frames.len() >= state.policy.capacity()
+            && !state.closed
+        {
+            state = shared.ready.wait(state).unwrap();
+        }
+        if state.closed {
+            return;
+        }
+        drop(state);
+
+        let result = match client.receive(0) {
+            Err(VslError::Client(ClientError::Timeout)) => continue,
+            result => result,
+        };
+
+        let mut state = shared.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        match result {
+            Ok(frame) => {
+                let capacity = state.policy.capacity();
+                if state.policy == BufferPolicy::DropNewest && state.frames.len() >= capacity {
+                    state.dropped += 1;
+                } else {
+                    while state.frames.len() >= capacity {
+                        state.frames.pop_front();
+                        state.dropped += 1;
+                    }
+                    state.frames.push_back(frame);
+                }
+            }
+            Err(err) => {
+                state.error = Some(err);
+                state.done = true;
+            }
+        }
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        if state.done {
+            return;
+        }
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Result<Frame, VslError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(frame) = state.frames.pop_front() {
+            self.shared.ready.notify_one();
+            return Poll::Ready(Some(Ok(frame)));
+        }
+        if let Some(err) = state.error.take() {
+            return Poll::Ready(Some(Err(err)));
+        }
+        if state.done {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        return Poll::Pending;
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        // The receiving thread exits, dropping the client, once its current
+        // wait returns.
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.frames.clear();
+        self.shared.ready.notify_one();
+    }
+}
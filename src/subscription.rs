@@ -0,0 +1,157 @@
+use crate::{
+    client::{Client, ClientError, ClientEvent, ClientOptions},
+    frame::Frame,
+    VslError,
+};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How long each wait of the subscription's thread lasts, bounding how long
+/// dropping the subscription waits for the thread to exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long after losing the host the subscription starts reconnecting.
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// A callback registered by [`Subscription::on_reconnect`].
+type ReconnectCallback = Box<dyn FnMut() + Send>;
+
+/// The Subscription invokes a callback with every frame received from a host,
+/// created by [`ClientOptions::subscribe`].
+///
+/// The frames are received on a dedicated thread which reconnects to the host
+/// whenever the connection is lost, such as when the host restarts, and then
+/// resumes invoking the same callback.  Each reconnection is notified through
+/// [`Subscription::on_reconnect`] before the first frame of the new
+/// connection, so callers can reset state tied to the previous stream.  The
+/// thread stops once the subscription is dropped.
+pub struct Subscription {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Option<()>>>,
+}
+
+struct Shared {
+    closed: AtomicBool,
+    connected: AtomicBool,
+    reconnects: AtomicU64,
+    on_reconnect: Mutex<Vec<ReconnectCallback>>,
+}
+
+/// Starts the thread receiving frames for the subscription.
+pub(crate) fn spawn<F>(
+    options: ClientOptions,
+    path: PathBuf,
+    client: Client,
+    on_frame: F,
+) -> Result<Subscription, VslError>
+where
+    F: FnMut(Frame) + Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        closed: AtomicBool::new(false),
+        connected: AtomicBool::new(true),
+        reconnects: AtomicU64::new(0),
+        on_reconnect: Mutex::new(Vec::new()),
+    });
+    let receiver = shared.clone();
+    let config = client.thread_config().clone();
+    let thread = config.spawn("vsl-subscribe", move || {
+        receive(options, path, client, on_frame, receiver)
+    })?;
+    return Ok(Subscription {
+        shared,
+        thread: Some(thread),
+    });
+}
+
+fn receive<F>(
+    options: ClientOptions,
+    path: PathBuf,
+    client: Client,
+    mut on_frame: F,
+    shared: Arc<Shared>,
+) where
+    F: FnMut(Frame),
+{
+    let mut client = Some(client);
+    while !shared.closed.load(Ordering::Relaxed) {
+        let current = match &client {
+            Some(current) => current,
+            None => {
+                // Waits are short so dropping the subscription is not held
+                // back by an absent host.
+                if let Ok(reconnected) = options.clone().connect_wait(&path, POLL_INTERVAL) {
+                    client = Some(reconnected);
+                    shared.connected.store(true, Ordering::Relaxed);
+                    shared.reconnects.fetch_add(1, Ordering::Relaxed);
+                    for callback in shared.on_reconnect.lock().unwrap().iter_mut() {
+                        callback();
+                    }
+                }
+                continue;
+            }
+        };
+
+        match current.next_event(POLL_INTERVAL) {
+            Ok(ClientEvent::Frame(frame)) => on_frame(frame),
+            // A terminated host will not be back, so the subscription stops.
+            Err(VslError::Client(ClientError::HostShutdown {
+                reconnectable: false,
+            })) => {
+                shared.connected.store(false, Ordering::Relaxed);
+                return;
+            }
+            Ok(ClientEvent::Disconnected)
+            | Err(VslError::Client(ClientError::Io(_)))
+            | Err(VslError::Client(ClientError::HostShutdown { .. })) => {
+                client = None;
+                shared.connected.store(false, Ordering::Relaxed);
+                // A host shutting down may still accept connections it will
+                // never serve, so it is given time to close its socket.
+                thread::sleep(RECONNECT_DELAY);
+            }
+            // Frames which could not be delivered are skipped, as are the
+            // other events.
+            Ok(_) | Err(_) => {}
+        }
+    }
+}
+
+impl Subscription {
+    /// Registers a callback invoked, on the subscription's thread, each time
+    /// the connection to the host is re-established.
+    pub fn on_reconnect<F: FnMut() + Send + 'static>(&self, callback: F) {
+        self.shared
+            .on_reconnect
+            .lock()
+            .unwrap()
+            .push(Box::new(callback));
+    }
+
+    /// Returns true unless the connection was lost and has yet to be
+    /// re-established.
+    pub fn is_connected(&self) -> bool {
+        return self.shared.connected.load(Ordering::Relaxed);
+    }
+
+    /// Returns the number of times the connection was re-established.
+    pub fn reconnects(&self) -> u64 {
+        return self.shared.reconnects.load(Ordering::Relaxed);
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
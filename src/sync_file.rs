@@ -0,0 +1,87 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    time::Instant,
+};
+
+/// The argument of the dmabuf sync_file ioctls, from linux/dma-buf.h.
+#[repr(C)]
+struct DmaBufSyncFile {
+    flags: u32,
+    fd: i32,
+}
+
+const DMA_BUF_SYNC_READ: u32 = 1 << 0;
+const DMA_BUF_SYNC_WRITE: u32 = 1 << 1;
+
+/// _IOWR('b', 2, struct dma_buf_export_sync_file)
+const DMA_BUF_IOCTL_EXPORT_SYNC_FILE: libc::c_ulong = 0xc008_6202;
+/// _IOW('b', 3, struct dma_buf_import_sync_file)
+const DMA_BUF_IOCTL_IMPORT_SYNC_FILE: libc::c_ulong = 0x4008_6203;
+
+/// Attaches the fence to the dmabuf as a pending write, so readers of the
+/// buffer wait for it through [`export_read_fence`] or implicit sync.  Fails
+/// for buffers which are not dmabufs and on kernels before 6.0.
+pub(crate) fn import_write_fence(dmabuf: RawFd, fence: BorrowedFd) -> io::Result<()> {
+    let mut arg = DmaBufSyncFile {
+        flags: DMA_BUF_SYNC_WRITE,
+        fd: fence.as_raw_fd(),
+    };
+    let ret = unsafe { libc::ioctl(dmabuf, DMA_BUF_IOCTL_IMPORT_SYNC_FILE as _, &mut arg) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    return Ok(());
+}
+
+/// Returns a sync_file which signals once the writes pending on the dmabuf
+/// have completed, already signalled when there are none.
+pub(crate) fn export_read_fence(dmabuf: RawFd) -> io::Result<OwnedFd> {
+    let mut arg = DmaBufSyncFile {
+        flags: DMA_BUF_SYNC_READ,
+        fd: -1,
+    };
+    let ret = unsafe { libc::ioctl(dmabuf, DMA_BUF_IOCTL_EXPORT_SYNC_FILE as _, &mut arg) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    return Ok(unsafe { OwnedFd::from_raw_fd(arg.fd) });
+}
+
+/// Blocks until the sync_file signals, which it reports by becoming readable.
+pub(crate) fn wait(fence: BorrowedFd) -> io::Result<()> {
+    wait_until(fence, None)?;
+    return Ok(());
+}
+
+/// Blocks until the sync_file signals or the deadline passes, returning false
+/// if it had not signalled by the deadline.
+pub(crate) fn wait_until(fence: BorrowedFd, deadline: Option<Instant>) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd: fence.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                // Rounded up so the deadline is never missed by less than one
+                // millisecond, poll's resolution.
+                remaining.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+            }
+            None => -1,
+        };
+        let ready = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+        if ready >= 0 {
+            if pollfd.revents & libc::POLLNVAL != 0 {
+                return Err(io::Error::from_raw_os_error(libc::EBADF));
+            }
+            return Ok(ready > 0);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
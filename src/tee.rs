@@ -0,0 +1,258 @@
+use crate::{
+    client::{Client, ClientError},
+    frame::Frame,
+    VslError,
+};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The policy of a newly created branch, holding only the latest frame so a
+/// slow branch neither stalls the others nor holds back the host's buffers.
+const DEFAULT_POLICY: TeePolicy = TeePolicy::DropOldest(1);
+
+/// How a branch of a [`Client::tee`] handles frames arriving faster than its
+/// consumer receives them.  Each holds up to the given number of frames, at
+/// least one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeePolicy {
+    /// Discards the oldest held frame to make room for a new one, counted by
+    /// [`FrameReceiver::dropped`].
+    DropOldest(usize),
+    /// Waits for the consumer to make room, which holds back every branch
+    /// as frames are received once for all of them.
+    Block(usize),
+}
+
+impl TeePolicy {
+    fn capacity(&self) -> usize {
+        match self {
+            TeePolicy::DropOldest(capacity) | TeePolicy::Block(capacity) => {
+                return (*capacity).max(1)
+            }
+        }
+    }
+}
+
+/// The FrameReceiver receives the frames of one branch of a [`Client::tee`].
+///
+/// Each branch receives every frame, sharing the buffer of the frame received
+/// by the client, which stays locked until every branch has dropped it.  The
+/// receiving thread exits, dropping the client, once every receiver has been
+/// dropped.
+pub struct FrameReceiver {
+    branch: Arc<Branch>,
+}
+
+struct Branch {
+    state: Mutex<BranchState>,
+    ready: Condvar,
+}
+
+struct BranchState {
+    frames: VecDeque<Frame>,
+    policy: TeePolicy,
+    error: Option<VslError>,
+    dropped: u64,
+    closed: bool,
+}
+
+/// Starts the thread receiving the client's frames for `count` branches.
+pub(crate) fn spawn(client: Client, count: usize) -> Vec<FrameReceiver> {
+    let branches: Vec<Arc<Branch>> = (0..count)
+        .map(|_| {
+            return Arc::new(Branch {
+                state: Mutex::new(BranchState {
+                    frames: VecDeque::new(),
+                    policy: DEFAULT_POLICY,
+                    error: None,
+                    dropped: 0,
+                    closed: false,
+                }),
+                ready: Condvar::new(),
+            });
+        })
+        .collect();
+
+    let receivers = branches
+        .iter()
+        .map(|branch| {
+            return FrameReceiver {
+                branch: branch.clone(),
+            };
+        })
+        .collect();
+    if count > 0 {
+        let config = client.thread_config().clone();
+        let failed = branches.clone();
+        if let Err(err) = config.spawn("vsl-tee", move || receive(client, branches)) {
+            let err = VslError::from(ClientError::Io(err));
+            for branch in &failed {
+                branch.state.lock().unwrap().error = Some(duplicate(&err));
+            }
+        }
+    }
+    return receivers;
+}
+
+fn receive(client: Client, branches: Vec<Arc<Branch>>) {
+    loop {
+        let open: Vec<&Arc<Branch>> = branches
+            .iter()
+            .filter(|branch| !branch.state.lock().unwrap().closed)
+            .collect();
+        if open.is_empty() {
+            return;
+        }
+
+        let frame = match client.receive(0) {
+            Ok(frame) => frame,
+            Err(VslError::Client(ClientError::Timeout)) => continue,
+            Err(VslError::Client(ClientError::Convert(_))) => {
+                for branch in &open {
+                    branch.state.lock().unwrap().dropped += 1;
+                }
+                continue;
+            }
+            Err(err) => {
+                for branch in &branches {
+                    branch.state.lock().unwrap().error = Some(duplicate(&err));
+                    branch.ready.notify_all();
+                }
+                return;
+            }
+        };
+
+        match frame.split(open.len()) {
+            Ok(shares) => {
+                for (branch, share) in open.iter().zip(shares) {
+                    branch.push(share);
+                }
+            }
+            // The frame is missed by every branch, for instance when the
+            // process is out of descriptors.
+            Err(_) => {
+                for branch in &open {
+                    branch.state.lock().unwrap().dropped += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Copies the error for each branch, as errors of the client cannot be
+/// cloned.
+fn duplicate(err: &VslError) -> VslError {
+    match err {
+        VslError::Client(err) => return VslError::Client(duplicate_client(err)),
+        VslError::Os(err) => return VslError::Os(io::Error::new(err.kind(), err.to_string())),
+        err => return VslError::Os(io::Error::other(err.to_string())),
+    }
+}
+
+/// Copies the ClientError of [`duplicate`].
+fn duplicate_client(err: &ClientError) -> ClientError {
+    match err {
+        ClientError::Timeout => return ClientError::Timeout,
+        ClientError::Io(err) => {
+            return ClientError::Io(io::Error::new(err.kind(), err.to_string()))
+        }
+        ClientError::Malformed => return ClientError::Malformed,
+        ClientError::FramesOutstanding(count) => return ClientError::FramesOutstanding(*count),
+        ClientError::AuthFailed => return ClientError::AuthFailed,
+        ClientError::UnsupportedCodec(fourcc) => return ClientError::UnsupportedCodec(*fourcc),
+        ClientError::HostShutdown { reconnectable } => {
+            return ClientError::HostShutdown {
+                reconnectable: *reconnectable,
+            }
+        }
+        ClientError::Convert(err) => return ClientError::Io(io::Error::other(err.to_string())),
+        ClientError::BufferTooSmall { required, len } => {
+            return ClientError::BufferTooSmall {
+                required: *required,
+                len: *len,
+            }
+        }
+    }
+}
+
+impl Branch {
+    fn push(&self, frame: Frame) {
+        let mut state = self.state.lock().unwrap();
+        if let TeePolicy::Block(_) = state.policy {
+            while state.frames.len() >= state.policy.capacity() && !state.closed {
+                state = self.ready.wait(state).unwrap();
+            }
+        }
+        if state.closed {
+            return;
+        }
+        while state.frames.len() >= state.policy.capacity() {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+        state.frames.push_back(frame);
+        self.ready.notify_all();
+    }
+}
+
+impl FrameReceiver {
+    /// Sets how the branch handles frames its consumer has yet to receive,
+    /// [`TeePolicy::DropOldest`] holding a single frame by default.
+    pub fn set_policy(&self, policy: TeePolicy) {
+        self.branch.state.lock().unwrap().policy = policy;
+        // A blocked receiving thread may now have room.
+        self.branch.ready.notify_all();
+    }
+
+    /// Returns the policy set by [`FrameReceiver::set_policy`].
+    pub fn policy(&self) -> TeePolicy {
+        return self.branch.state.lock().unwrap().policy;
+    }
+
+    /// Waits up to `timeout` for the branch's next frame, returning
+    /// [`ClientError::Timeout`] when none arrived.  Once the client failed,
+    /// such as when the host went away, the held frames are returned first
+    /// and then the client's error.
+    pub fn recv(&self, timeout: Duration) -> Result<Frame, VslError> {
+        let until = Instant::now() + timeout;
+        let mut state = self.branch.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.branch.ready.notify_all();
+                return Ok(frame);
+            }
+            if let Some(err) = &state.error {
+                return Err(duplicate(err));
+            }
+            let now = Instant::now();
+            if now >= until {
+                return Err(VslError::from(ClientError::Timeout));
+            }
+            state = self
+                .branch
+                .ready
+                .wait_timeout(state, until - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Returns the number of frames the branch discarded, to make room under
+    /// [`TeePolicy::DropOldest`] or as they could not be shared or converted.
+    pub fn dropped(&self) -> u64 {
+        return self.branch.state.lock().unwrap().dropped;
+    }
+}
+
+impl Drop for FrameReceiver {
+    fn drop(&mut self) {
+        let mut state = self.branch.state.lock().unwrap();
+        state.closed = true;
+        state.frames.clear();
+        self.branch.ready.notify_all();
+    }
+}
@@ -0,0 +1,58 @@
+use crate::frame::Frame;
+
+/// A Timebase is the unit of time `num/den` seconds used by muxers and codecs,
+/// for example MPEG-TS uses 1/90000.  It rescales the nanosecond values used
+/// by the library, such as a frame's pts, dts, and duration, into this unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timebase {
+    pub num: i64,
+    pub den: i64,
+}
+
+impl Timebase {
+    /// The 90kHz timebase used by MPEG-TS and RTP video.
+    pub const MPEG: Timebase = Timebase::new(1, 90_000);
+    /// The nanosecond timebase used by the library.
+    pub const NANOSECONDS: Timebase = Timebase::new(1, 1_000_000_000);
+
+    /// Creates a timebase of `num/den` seconds.  Panics if either is zero.
+    pub const fn new(num: i64, den: i64) -> Self {
+        assert!(num != 0 && den != 0, "timebase must be non-zero");
+        return Timebase { num, den };
+    }
+
+    /// Rescales a nanosecond value into this timebase, rounding to the nearest
+    /// unit with halves rounded away from zero.
+    pub fn from_nanos(&self, nanos: i64) -> i64 {
+        return rescale(nanos, self.den, self.num * 1_000_000_000);
+    }
+
+    /// Rescales a value in this timebase back into nanoseconds, rounding as
+    /// [`Timebase::from_nanos`].
+    pub fn to_nanos(&self, value: i64) -> i64 {
+        return rescale(value, self.num * 1_000_000_000, self.den);
+    }
+
+    /// Returns the frame's presentation timestamp in this timebase.
+    pub fn pts(&self, frame: &Frame) -> i64 {
+        return self.from_nanos(frame.pts());
+    }
+
+    /// Returns the frame's decode timestamp in this timebase.
+    pub fn dts(&self, frame: &Frame) -> i64 {
+        return self.from_nanos(frame.dts());
+    }
+
+    /// Returns the frame's duration in this timebase.
+    pub fn duration(&self, frame: &Frame) -> i64 {
+        return self.from_nanos(frame.duration());
+    }
+}
+
+/// Computes `value * mul / div` rounded to nearest without overflowing.
+fn rescale(value: i64, mul: i64, div: i64) -> i64 {
+    let n = value as i128 * mul as i128;
+    let d = div as i128;
+    let rounded = (n.abs() + d.abs() / 2) / d.abs();
+    return (rounded * n.signum() * d.signum()) as i64;
+}
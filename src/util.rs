@@ -0,0 +1,249 @@
+use std::{
+    collections::VecDeque,
+    io, mem,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The Pacer limits a producer's loop to a target framerate, measured with the
+/// library clock of [`crate::timestamp`] so its deadlines share the domain of
+/// the timestamps the host gives posted frames.
+///
+/// Each deadline follows the previous one rather than the time the caller
+/// woke, so oversleeping does not accumulate into drift.  A producer which
+/// falls behind resumes from the current time instead of posting a burst of
+/// frames to catch up.
+pub struct Pacer {
+    interval: i64,
+    next: Option<i64>,
+}
+
+impl Pacer {
+    /// Creates a pacer for `fps` frames per second, which must be positive.
+    pub fn new(fps: f64) -> Self {
+        assert!(fps > 0.0, "pacer framerate must be positive");
+        return Pacer {
+            interval: (1e9 / fps).round() as i64,
+            next: None,
+        };
+    }
+
+    /// Returns the time between frames.
+    pub fn interval(&self) -> Duration {
+        return Duration::from_nanos(self.interval as u64);
+    }
+
+    /// Sleeps until the next frame is due, returning immediately the first
+    /// time.  Called once per iteration before posting the frame.
+    pub fn wait(&mut self) {
+        let now = crate::timestamp();
+        let next = match self.next {
+            Some(next) => next,
+            None => now,
+        };
+        if next > now {
+            thread::sleep(Duration::from_nanos((next - now) as u64));
+        }
+        // Late frames are due immediately without delaying the rest.
+        self.next = Some(next.max(now) + self.interval);
+    }
+
+    /// Restarts pacing, so the next wait returns immediately, such as after
+    /// the producer was paused.
+    pub fn reset(&mut self) {
+        self.next = None;
+    }
+}
+
+/// The JitterStats summarizes the intervals measured by a [`JitterMeter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JitterStats {
+    /// Number of intervals in the window.
+    pub samples: usize,
+    pub mean_interval: Duration,
+    /// Standard deviation of the intervals, the jitter.
+    pub stddev: Duration,
+    /// The longest interval in the window.
+    pub max_gap: Duration,
+}
+
+/// The JitterMeter measures the intervals between events, such as frame
+/// arrivals, over a rolling window of the most recent ones.
+///
+/// Times are library timestamps, from [`crate::timestamp`] or
+/// [`crate::frame::Frame::timestamp`], so the meter can be fed either.  The
+/// host stamps frames as they are posted, so the jitter of frame timestamps is
+/// that of the producer while the jitter of arrival times adds the transport
+/// and the consumer's own scheduling, which
+/// [`crate::client::ClientOptions::measure_jitter`] measures.
+pub struct JitterMeter {
+    window: usize,
+    last: Option<i64>,
+    intervals: VecDeque<i64>,
+}
+
+impl JitterMeter {
+    /// Creates a meter over the last `window` intervals, at least one.
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        return JitterMeter {
+            window,
+            last: None,
+            intervals: VecDeque::with_capacity(window),
+        };
+    }
+
+    /// Records an event at `time`, a library timestamp in nanoseconds.
+    /// Events earlier than the previous one restart the measurement.
+    pub fn record(&mut self, time: i64) {
+        if let Some(last) = self.last {
+            if time < last {
+                self.intervals.clear();
+            } else {
+                if self.intervals.len() == self.window {
+                    self.intervals.pop_front();
+                }
+                self.intervals.push_back(time - last);
+            }
+        }
+        self.last = Some(time);
+    }
+
+    /// Returns the statistics of the intervals in the window, or None until
+    /// two events have been recorded.
+    pub fn stats(&self) -> Option<JitterStats> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let count = self.intervals.len() as f64;
+        let mean = self.intervals.iter().sum::<i64>() as f64 / count;
+        let variance = self
+            .intervals
+            .iter()
+            .map(|&interval| (interval as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let nanos = |value: f64| Duration::from_nanos(value.round() as u64);
+        return Some(JitterStats {
+            samples: self.intervals.len(),
+            mean_interval: nanos(mean),
+            stddev: nanos(variance.sqrt()),
+            max_gap: nanos(*self.intervals.iter().max().unwrap() as f64),
+        });
+    }
+}
+
+/// The scheduling priority of a thread configured by a [`ThreadConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadPriority {
+    /// The SCHED_FIFO real-time policy at a priority from 1 to 99, which
+    /// needs CAP_SYS_NICE or an RLIMIT_RTPRIO allowance.
+    RealTime(i32),
+    /// The default policy at a nice value from -20 to 19, lower values being
+    /// favoured.  Lowering the nice value needs CAP_SYS_NICE.
+    Nice(i32),
+}
+
+/// The CpuSet is the set of CPUs a thread may run on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuSet {
+    cpus: Vec<usize>,
+}
+
+impl CpuSet {
+    pub fn new(cpus: &[usize]) -> Self {
+        return CpuSet {
+            cpus: cpus.to_vec(),
+        };
+    }
+
+    pub fn cpus(&self) -> &[usize] {
+        return &self.cpus;
+    }
+}
+
+/// The ThreadConfig sets up the threads the crate spawns, such as those of
+/// [`crate::client::ClientOptions::prefetch`] and
+/// [`crate::client::Client::tee`], for pipelines needing deterministic
+/// latency on embedded multi-core SoCs.  Settings left as None are
+/// inherited from the thread spawning them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadConfig {
+    /// The name of the threads, otherwise named after their role.
+    pub name: Option<String>,
+    pub priority: Option<ThreadPriority>,
+    pub affinity: Option<CpuSet>,
+}
+
+impl ThreadConfig {
+    /// Spawns a thread running `f` with the configuration applied, named
+    /// `role` when no name is configured.  A priority or affinity the thread
+    /// is not permitted to take fails the spawn with the error, the thread
+    /// then ending without running `f`, which is why the thread returns None.
+    pub fn spawn<F, T>(&self, role: &str, f: F) -> io::Result<JoinHandle<Option<T>>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let name = self.name.clone().unwrap_or_else(|| role.to_string());
+        let config = self.clone();
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let thread = thread::Builder::new().name(name).spawn(move || {
+            let applied = config.apply();
+            let failed = applied.is_err();
+            let _ = sender.send(applied);
+            if failed {
+                return None;
+            }
+            return Some(f());
+        })?;
+        match receiver.recv() {
+            Ok(Ok(())) => return Ok(thread),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                return Err(err);
+            }
+            Err(_) => return Err(io::Error::other("thread ended before it was configured")),
+        }
+    }
+
+    /// Applies the priority and affinity to the calling thread.
+    fn apply(&self) -> io::Result<()> {
+        if let Some(affinity) = &self.affinity {
+            let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+            for &cpu in affinity.cpus() {
+                if cpu >= libc::CPU_SETSIZE as usize {
+                    return Err(io::Error::from_raw_os_error(libc::EINVAL));
+                }
+                unsafe { libc::CPU_SET(cpu, &mut set) };
+            }
+            let ret = unsafe { libc::sched_setaffinity(0, mem::size_of_val(&set), &set) };
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        match self.priority {
+            Some(ThreadPriority::RealTime(priority)) => {
+                let param = libc::sched_param {
+                    sched_priority: priority,
+                };
+                let ret = unsafe {
+                    libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param)
+                };
+                if ret != 0 {
+                    return Err(io::Error::from_raw_os_error(ret));
+                }
+            }
+            // The nice value is per thread on Linux, set for the caller by 0.
+            Some(ThreadPriority::Nice(nice)) => {
+                let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+                if ret < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            None => {}
+        }
+        return Ok(());
+    }
+}
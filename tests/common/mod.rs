@@ -0,0 +1,38 @@
+//! Helpers shared by the tests which connect clients to a host.
+
+use std::time::Duration;
+use videostream::{
+    client::{Client, ClientOptions},
+    host::Host,
+};
+
+/// How long tests wait for a frame which is expected to arrive.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns the socket path of the test named `name`.
+pub fn socket_path(name: &str) -> String {
+    return format!("/tmp/test_{}.vsl", name);
+}
+
+/// Accepts the clients waiting to connect to the host, so the frames posted
+/// next reach them.
+pub fn accept(host: &Host) {
+    host.poll(100).unwrap();
+    host.process().unwrap();
+}
+
+/// Connects a client with the options to the host listening at `path` and
+/// accepts it.
+pub fn connect(host: &Host, path: &str, options: ClientOptions) -> Client {
+    let client = options.connect(path).unwrap();
+    accept(host);
+    return client;
+}
+
+/// Creates a host for the test named `name` and connects a client to it.
+pub fn host_and_client(name: &str) -> (Host, Client) {
+    let path = socket_path(name);
+    let host = Host::new(&path).unwrap();
+    let client = connect(&host, &path, ClientOptions::new());
+    return (host, client);
+}
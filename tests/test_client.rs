@@ -1 +1,1070 @@
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+use videostream::{
+    client::{Client, ClientError, ClientEvent, ClientOptions, FrameNumbering, RecvMode},
+    color::{ColorRange, ColorSpace, HdrMetadata, MasteringDisplay},
+    encoder::VSLRect,
+    format,
+    frame::{Frame, FrameError, Interlace, Rotation},
+    host::{Host, HostBuilder, ShutdownReason},
+    tee::TeePolicy,
+    timestamp, VslError,
+};
 
+mod common;
+
+use common::TIMEOUT;
+
+#[test]
+fn test_decimate() {
+    let path = common::socket_path("decimate");
+    let host = Host::new(&path).unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new().decimate(3));
+    client.set_timeout(5.0);
+
+    for i in 0..9 {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, i, i)
+            .unwrap();
+    }
+
+    for i in 1..=3 {
+        let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+        assert_eq!(frame.pts(), i * 3 - 1);
+    }
+    let stats = client.stats();
+    assert_eq!((stats.frames_received, stats.frames_skipped), (3, 6));
+    assert_eq!(stats.bytes_received, 3 * 64 * 64 * 3);
+    assert!(client.measured_fps().is_some());
+}
+
+#[test]
+fn test_host_owned() {
+    let (host, client) = common::host_and_client("host_owned");
+    client.set_timeout(5.0);
+
+    let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    assert!(!frame.is_host_owned());
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0)
+        .unwrap();
+
+    let mut frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert!(frame.is_host_owned());
+    assert!(frame.mmap().is_ok());
+    assert!(matches!(
+        frame.mmap_mut(),
+        Err(VslError::Frame(FrameError::ReadOnly))
+    ));
+    let err = frame.alloc(None).unwrap_err();
+    assert!(matches!(err, VslError::Frame(FrameError::NotOwned)));
+}
+
+#[test]
+fn test_reorder_window() {
+    let path = common::socket_path("reorder_window");
+    let host = Host::new(&path).unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new().reorder_window(3));
+    client.set_timeout(0.5);
+
+    // Posted in decode order, the two frames with pts 5 keep arrival order.
+    let posted = [(0, 0), (3, 1), (1, 2), (2, 3), (5, 4), (4, 5), (5, 6)];
+    for (pts, dts) in posted {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, dts)
+            .unwrap();
+    }
+
+    let mut received = Vec::new();
+    while let Ok(frame) = client.recv(RecvMode::Blocking(Duration::from_millis(500))) {
+        received.push((frame.pts(), frame.dts()));
+    }
+    assert_eq!(
+        received,
+        [(0, 0), (1, 2), (2, 3), (3, 1), (4, 5), (5, 4), (5, 6)]
+    );
+}
+
+#[test]
+fn test_close() {
+    let path = common::socket_path("close");
+    let host = Host::new(&path).unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new());
+    client.set_timeout(5.0);
+
+    let mut frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    frame.mmap_mut().unwrap()[0] = 42;
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0)
+        .unwrap();
+
+    // The held frame outlives the client and remains readable.
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert!(matches!(
+        client.close(),
+        Err(VslError::Client(ClientError::FramesOutstanding(1)))
+    ));
+    assert_eq!(frame.mmap().unwrap()[0], 42);
+    drop(frame);
+
+    let client = Client::new(&path, false).unwrap();
+    client.close().unwrap();
+}
+
+#[test]
+fn test_recv_modes() {
+    let (host, client) = common::host_and_client("recv_modes");
+    client.set_timeout(5.0);
+
+    assert!(matches!(
+        client.recv(RecvMode::NonBlocking),
+        Err(VslError::Client(ClientError::Timeout))
+    ));
+    let start = Instant::now();
+    assert!(matches!(
+        client.recv(RecvMode::Blocking(Duration::from_millis(100))),
+        Err(VslError::Client(ClientError::Timeout))
+    ));
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    for i in 0..4 {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, i, i)
+            .unwrap();
+    }
+    let timeout = Duration::from_millis(500);
+    assert_eq!(client.recv(RecvMode::Blocking(timeout)).unwrap().pts(), 0);
+    assert_eq!(client.recv(RecvMode::NonBlocking).unwrap().pts(), 1);
+    assert_eq!(client.recv(RecvMode::Latest).unwrap().pts(), 3);
+    assert!(matches!(
+        client.recv(RecvMode::Latest),
+        Err(VslError::Client(ClientError::Timeout))
+    ));
+}
+
+#[test]
+fn test_frames_iterator() {
+    let (host, client) = common::host_and_client("frames_iterator");
+    client.set_timeout(5.0);
+
+    for i in 0..3 {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, i, i)
+            .unwrap();
+    }
+    // The frame of the previous iteration is released before the next one
+    // is received, so the consumer only ever holds one of the host's frames.
+    assert_eq!(client.frames_outstanding(), 0);
+    for (index, frame) in client.frames().take(3).enumerate() {
+        let frame = frame.unwrap();
+        assert_eq!(frame.pts(), index as i64);
+        assert_eq!(client.frames_outstanding(), 1);
+    }
+    assert_eq!(client.frames_outstanding(), 0);
+
+    drop(host);
+    let mut frames = client.frames();
+    assert!(matches!(
+        frames.next(),
+        Some(Err(VslError::Client(ClientError::Io(_))))
+    ));
+    assert!(frames.next().is_none());
+}
+
+#[test]
+fn test_connect_wait() {
+    let path = Path::new("/tmp/test_connect_wait.vsl");
+    let _ = std::fs::remove_file(path);
+
+    let host = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        let host = Host::new(&path).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        drop(host);
+    });
+    Client::connect_wait(path, Duration::from_secs(5)).unwrap();
+    host.join().unwrap();
+
+    let missing = Path::new("/tmp/test_connect_wait_missing.vsl");
+    assert!(matches!(
+        Client::connect_wait(missing, Duration::from_millis(100)),
+        Err(VslError::Client(ClientError::Timeout))
+    ));
+}
+
+#[test]
+fn test_heartbeat() {
+    let path = common::socket_path("heartbeat");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .build()
+        .unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new());
+    client.set_timeout(0.5);
+
+    assert!(client.time_since_last_signal().is_none());
+
+    host.send_heartbeat().unwrap();
+    let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 7, 7)
+        .unwrap();
+    host.send_heartbeat().unwrap();
+
+    // The heartbeats are consumed, only the frame is returned.
+    assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 7);
+    let timeout = Duration::from_millis(200);
+    assert!(matches!(
+        client.recv(RecvMode::Blocking(timeout)),
+        Err(VslError::Client(ClientError::Timeout))
+    ));
+    assert!(client.time_since_last_signal().unwrap() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_format_changed() {
+    let (host, client) = common::host_and_client("format_changed");
+    client.set_timeout(5.0);
+
+    for (pts, (width, height)) in [(320, 240), (320, 240), (640, 480)].iter().enumerate() {
+        let frame = Frame::new(*width, *height, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        let pts = pts as i64;
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+
+    let mut events = Vec::new();
+    for _ in 0..5 {
+        match client.next_event(Duration::from_secs(5)).unwrap() {
+            ClientEvent::FormatChanged(format) => events.push((format.width, format.height, -1)),
+            ClientEvent::Frame(frame) => {
+                assert!(frame.mmap().unwrap().len() >= frame.width() as usize * 3);
+                events.push((frame.width() as u32, frame.height() as u32, frame.pts()));
+            }
+            _ => panic!("unexpected event"),
+        }
+    }
+    assert_eq!(
+        events,
+        [
+            (320, 240, -1),
+            (320, 240, 0),
+            (320, 240, 1),
+            (640, 480, -1),
+            (640, 480, 2)
+        ]
+    );
+    let format = Frame::new(640, 480, 0, "RGB3").unwrap().format();
+    assert_eq!((format.stride, format.fourcc), (1920, 0x33424752));
+}
+
+#[test]
+fn test_next_event() {
+    let path = common::socket_path("next_event");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .build()
+        .unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new());
+
+    host.send_heartbeat().unwrap();
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 7, 7)
+        .unwrap();
+
+    let timeout = Duration::from_secs(5);
+    assert!(matches!(
+        client.next_event(timeout).unwrap(),
+        ClientEvent::Heartbeat
+    ));
+    assert!(matches!(
+        client.next_event(timeout).unwrap(),
+        ClientEvent::FormatChanged(format) if format.width == 64
+    ));
+    assert!(matches!(
+        client.next_event(timeout).unwrap(),
+        ClientEvent::Frame(frame) if frame.pts() == 7
+    ));
+    assert!(matches!(
+        client.next_event(Duration::from_millis(50)),
+        Err(VslError::Client(ClientError::Timeout))
+    ));
+
+    drop(host);
+    assert!(matches!(
+        client.next_event(timeout).unwrap(),
+        ClientEvent::Disconnected
+    ));
+}
+
+#[test]
+fn test_probe() {
+    let path = common::socket_path("probe");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .build()
+        .unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new());
+
+    host.post_probe(&Frame::probe("NV12", 1280, 720).unwrap())
+        .unwrap();
+    let timeout = Duration::from_secs(5);
+    match client.next_event(timeout).unwrap() {
+        ClientEvent::Probe(probe) => {
+            assert!(probe.is_probe());
+            assert_eq!((probe.width(), probe.height()), (1280, 720));
+            assert_eq!(probe.fourcc(), format::fourcc_code(b"NV12"));
+        }
+        _ => panic!("expected a probe"),
+    }
+
+    // Clients connecting later receive the probe once accepted.
+    let late = common::connect(&host, &path, ClientOptions::new());
+    assert!(matches!(
+        late.next_event(timeout).unwrap(),
+        ClientEvent::Probe(probe) if probe.width() == 1280
+    ));
+
+    // Receiving frames skips probes.
+    host.post_probe(&Frame::probe("RGB3", 64, 48).unwrap())
+        .unwrap();
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 3, 3)
+        .unwrap();
+    assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 3);
+}
+
+#[test]
+fn test_frame_metadata() {
+    let path = common::socket_path("frame_metadata");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .build()
+        .unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new());
+
+    let post = |interlace: Interlace, pts: i64| {
+        let mut frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.set_interlaced(interlace);
+        if interlace == Interlace::TopFieldFirst {
+            frame.set_orientation(Rotation::Rot90);
+        }
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    };
+
+    // The metadata applies to the frames which follow until it changes.
+    post(Interlace::TopFieldFirst, 1);
+    post(Interlace::TopFieldFirst, 2);
+    post(Interlace::Progressive, 3);
+    for (pts, interlace) in [
+        (1, Interlace::TopFieldFirst),
+        (2, Interlace::TopFieldFirst),
+        (3, Interlace::Progressive),
+    ] {
+        let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+        assert_eq!((frame.pts(), frame.interlaced()), (pts, interlace));
+        let orientation = match interlace {
+            Interlace::TopFieldFirst => Rotation::Rot90,
+            _ => Rotation::None,
+        };
+        assert_eq!(frame.orientation(), orientation);
+    }
+
+    // Clients connecting later are sent the metadata once accepted.
+    post(Interlace::BottomFieldFirst, 4);
+    assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 4);
+    let late = common::connect(&host, &path, ClientOptions::new());
+    post(Interlace::BottomFieldFirst, 5);
+    let frame = late.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(
+        (frame.pts(), frame.interlaced()),
+        (5, Interlace::BottomFieldFirst)
+    );
+}
+
+#[test]
+fn test_color_metadata() {
+    let path = common::socket_path("color_metadata");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .build()
+        .unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new());
+
+    let mut frame = Frame::new(64, 48, 0, "NV12").unwrap();
+    frame.alloc(None).unwrap();
+    frame.set_color_space(ColorSpace::Bt2020);
+    frame.set_color_range(ColorRange::Full);
+    let hdr = HdrMetadata {
+        mastering_display: MasteringDisplay {
+            primaries: [(35400, 14600), (8500, 39850), (6550, 2300)],
+            white_point: (15635, 16450),
+            max_luminance: 10_000_000,
+            min_luminance: 50,
+        },
+        max_cll: 1000,
+        max_fall: 400,
+    };
+    frame.set_hdr_metadata(hdr);
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 1, 1)
+        .unwrap();
+    let frame = Frame::new(64, 48, 0, "NV12").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 2, 2)
+        .unwrap();
+
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(
+        (frame.color_space(), frame.color_range()),
+        (ColorSpace::Bt2020, ColorRange::Full)
+    );
+    assert_eq!(frame.hdr_metadata(), Some(hdr));
+    // Frames which leave them unset report the defaults.
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(
+        (frame.color_space(), frame.color_range()),
+        (ColorSpace::Bt601, ColorRange::Limited)
+    );
+    assert_eq!(frame.hdr_metadata(), None);
+}
+
+#[test]
+fn test_frame_rates() {
+    let path = common::socket_path("frame_rates");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .frame_rate(50.0)
+        .build()
+        .unwrap();
+    assert_eq!(host.configured_fps(), Some(50.0));
+    let client = Client::new(&path, false).unwrap();
+    client.set_timeout(5.0);
+    assert_eq!(client.target_fps(), None);
+
+    common::accept(&host);
+    for i in 0..3 {
+        thread::sleep(Duration::from_millis(10));
+        let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, i, i)
+            .unwrap();
+    }
+
+    // The framerate sent on connecting precedes the first frame.
+    assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 0);
+    assert_eq!(client.target_fps(), Some(50.0));
+    assert_eq!(client.measured_fps(), None);
+    client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    let fps = client.measured_fps().unwrap();
+    assert!(fps > 0.0 && fps < 100.0, "{}", fps);
+
+    let host = HostBuilder::new("/tmp/test_frame_rates_unset.vsl")
+        .frame_rate(-1.0)
+        .build()
+        .unwrap();
+    assert_eq!(host.configured_fps(), None);
+}
+
+/// Posts an encoded frame holding the bitstream, the rest of its buffer being
+/// zeros.
+fn post_encoded(host: &Host, fourcc: &str, bitstream: &[u8], pts: i64) {
+    let mut frame = Frame::new(64, 1, 64, fourcc).unwrap();
+    frame.alloc(None).unwrap();
+    frame.mmap_mut().unwrap()[..bitstream.len()].copy_from_slice(bitstream);
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+        .unwrap();
+}
+
+#[test]
+fn test_frame_reader() {
+    let (host, client) = common::host_and_client("frame_reader");
+    client.set_timeout(5.0);
+
+    // The PPS follows a three byte start code.
+    post_encoded(
+        &host,
+        "H264",
+        &[0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce],
+        0,
+    );
+    post_encoded(&host, "H264", &[0, 0, 0, 1, 0x41, 0x9a], 1);
+    drop(host);
+
+    let mut stream = Vec::new();
+    io::copy(&mut client.into_reader(), &mut stream).unwrap();
+    assert_eq!(
+        stream,
+        [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x41, 0x9a]
+    );
+
+    let (host, client) = common::host_and_client("frame_reader_raw");
+    client.set_timeout(5.0);
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0)
+        .unwrap();
+    let err = io::copy(&mut client.into_reader(), &mut io::sink()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(matches!(
+        err.get_ref().unwrap().downcast_ref::<ClientError>(),
+        Some(ClientError::UnsupportedCodec(fourcc)) if *fourcc == format::fourcc_code(b"RGB3")
+    ));
+}
+
+#[test]
+fn test_partial_updates() {
+    let path = common::socket_path("partial_updates");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .partial_updates(true)
+        .build()
+        .unwrap();
+    let client = ClientOptions::new()
+        .assemble_partial(true)
+        .connect(&path)
+        .unwrap();
+    let plain = Client::new(&path, false).unwrap();
+    common::accept(&host);
+
+    let mut full = Frame::new(16, 16, 0, "NV12").unwrap();
+    full.alloc(None).unwrap();
+    let mut content: Vec<u8> = (0..full.size() as usize).map(|i| i as u8).collect();
+    full.mmap_mut().unwrap().copy_from_slice(&content);
+    let expires = timestamp() + 10_000_000_000;
+    let receipt = host.post_frame(full, expires, 0, 0, 0).unwrap();
+    assert_eq!(receipt.bytes, 384);
+
+    // Only the marked 4x4 block is sent, with its 4x2 block of chroma.
+    for y in 4..8 {
+        content[y * 16 + 4..y * 16 + 8].fill(255);
+    }
+    for y in 2..4 {
+        content[256 + y * 16 + 4..256 + y * 16 + 8].fill(128);
+    }
+    let mut update = Frame::new(16, 16, 0, "NV12").unwrap();
+    update.alloc(None).unwrap();
+    update.mmap_mut().unwrap().copy_from_slice(&content);
+    update.mark_dirty(VSLRect::new(4, 4, 4, 4));
+    update.mark_dirty(VSLRect::new(20, 20, 4, 4));
+    assert_eq!(update.dirty_regions().len(), 1);
+    let receipt = host.post_frame(update, expires, 0, 1, 1).unwrap();
+    assert!(receipt.bytes < 384);
+
+    assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 0);
+    let assembled = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(assembled.pts(), 1);
+    assert_eq!(assembled.mmap().unwrap(), &content[..]);
+
+    // Clients which do not assemble frames skip the update.
+    assert_eq!(plain.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 0);
+    let frame = Frame::new(16, 16, 0, "NV12").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, expires, 0, 2, 2).unwrap();
+    assert_eq!(plain.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 2);
+    assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 2);
+
+    // The frame following a heartbeat is posted in full and the updates which
+    // follow it are assembled.
+    host.send_heartbeat().unwrap();
+    let marked = |pts: i64| {
+        let mut frame = Frame::new(16, 16, 0, "NV12").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mark_dirty(VSLRect::new(0, 0, 4, 4));
+        return host.post_frame(frame, expires, 0, pts, pts).unwrap().bytes;
+    };
+    assert_eq!(marked(3), 384);
+    assert!(marked(4) < 384);
+    for pts in 3..5 {
+        assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), pts);
+    }
+}
+
+#[test]
+fn test_partial_updates_need_control_frames() {
+    let path = common::socket_path("partial_updates_need_control_frames");
+    let host = HostBuilder::new(&path)
+        .partial_updates(true)
+        .build()
+        .unwrap();
+    let expires = timestamp() + 10_000_000_000;
+    for pts in 0..2 {
+        let mut frame = Frame::new(16, 16, 0, "NV12").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mark_dirty(VSLRect::new(0, 0, 4, 4));
+        let receipt = host.post_frame(frame, expires, 0, pts, pts).unwrap();
+        assert_eq!(receipt.bytes, 384);
+    }
+}
+
+#[test]
+fn test_frame_numbering() {
+    let (host, client) = common::host_and_client("frame_numbering");
+
+    for pts in 0..4 {
+        let frame = Frame::new(16, 16, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+
+    let first = client.get_numbered_frame(0).unwrap();
+    assert_eq!(first.number, first.frame.serial() as u64);
+    client.set_frame_numbering(FrameNumbering::ClientMonotonic);
+    let numbers: Vec<(u64, i64)> = (0..3)
+        .map(|_| {
+            let numbered = client.get_numbered_frame(0).unwrap();
+            return (numbered.number, numbered.frame.pts());
+        })
+        .collect();
+    assert_eq!(numbers, [(0, 1), (1, 2), (2, 3)]);
+}
+
+#[test]
+fn test_host_shutdown() {
+    let path = common::socket_path("host_shutdown");
+    let mut client = None;
+    for (reason, reconnectable) in [
+        (ShutdownReason::Restart, true),
+        (ShutdownReason::Terminate, false),
+    ] {
+        let host = HostBuilder::new(&path)
+            .control_frames(true)
+            .build()
+            .unwrap();
+        let current = client.insert(Client::new(&path, false).unwrap());
+        common::accept(&host);
+
+        let frame = Frame::new(16, 16, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, 5, 5)
+            .unwrap();
+        host.shutdown(reason).unwrap();
+
+        // Frames posted before the goodbye are still received.
+        assert_eq!(current.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), 5);
+        let err = current.recv(RecvMode::Blocking(TIMEOUT));
+        assert!(matches!(
+            err,
+            Err(VslError::Client(ClientError::HostShutdown { reconnectable: r })) if r == reconnectable
+        ));
+    }
+
+    // A terminated host is not waited on again.
+    let started = Instant::now();
+    assert!(matches!(
+        client.unwrap().recv(RecvMode::Blocking(TIMEOUT)),
+        Err(VslError::Client(ClientError::HostShutdown {
+            reconnectable: false
+        }))
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_copy_received() {
+    let (host, client) = common::host_and_client("copy_received");
+
+    // Rows are padded from 30 to 32 bytes by the host.
+    for pts in 0..2 {
+        let mut frame = Frame::new(10, 4, 32, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap().fill(pts as u8 + 1);
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+
+    let mut small = [0u8; 100];
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert!(matches!(
+        frame.copy_to_slice(&mut small),
+        Err(VslError::Frame(FrameError::BufferTooSmall {
+            required: 120,
+            len: 100
+        }))
+    ));
+
+    let mut slot = [0u8; 128];
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(frame.copy_to_slice(&mut slot).unwrap(), 120);
+    assert_eq!(frame.pts(), 1);
+    assert!(slot[..120].iter().all(|&v| v == 2));
+    assert_eq!(slot[120..], [0; 8]);
+}
+
+#[test]
+fn test_jitter() {
+    let path = common::socket_path("jitter");
+    let host = Host::new(&path).unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new().measure_jitter(8));
+    client.set_timeout(5.0);
+
+    for pts in 0..3 {
+        let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+    assert!(client.jitter().is_none());
+    for _ in 0..3 {
+        client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    }
+    assert_eq!(client.jitter().unwrap().samples, 2);
+}
+
+#[test]
+fn test_prefer_formats() {
+    let path = common::socket_path("prefer_formats");
+    let host = Host::new(&path).unwrap();
+    let grey = ClientOptions::new()
+        .prefer_formats(&[format::NV12, format::GREY])
+        .connect(&path)
+        .unwrap();
+    let fallback = ClientOptions::new()
+        .prefer_formats(&[format::NV12])
+        .connect(&path)
+        .unwrap();
+    grey.set_timeout(0.5);
+    fallback.set_timeout(0.5);
+
+    common::accept(&host);
+
+    // Each instant is posted as both RGB3 and GREY, sharing its pts.
+    for pts in 0..3 {
+        for fourcc in ["RGB3", "GREY"] {
+            let frame = Frame::new(64, 64, 0, fourcc).unwrap();
+            frame.alloc(None).unwrap();
+            host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+                .unwrap();
+        }
+    }
+
+    // The first RGB3 frame is returned before the host is seen to post GREY.
+    let received = |client: &Client, count| {
+        return (0..count)
+            .map(|_| {
+                let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+                return (frame.fourcc(), frame.pts());
+            })
+            .collect::<Vec<_>>();
+    };
+    assert_eq!(
+        received(&grey, 4),
+        [
+            (format::RGB3, 0),
+            (format::GREY, 0),
+            (format::GREY, 1),
+            (format::GREY, 2)
+        ]
+    );
+    assert!(grey
+        .recv(RecvMode::Blocking(Duration::from_millis(500)))
+        .is_err());
+
+    // Without any preferred format posted, every frame is returned.
+    assert_eq!(received(&fallback, 6).len(), 6);
+
+    // Once the host stops posting GREY for a whole instant the client falls
+    // back, and switches to NV12 as soon as it is posted.
+    for (pts, fourcc) in [
+        (3, "RGB3"),
+        (4, "RGB3"),
+        (5, "GREY"),
+        (5, "NV12"),
+        (6, "GREY"),
+    ] {
+        let frame = Frame::new(64, 64, 0, fourcc).unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+    assert_eq!(
+        received(&grey, 3),
+        [(format::RGB3, 4), (format::GREY, 5), (format::NV12, 5)]
+    );
+    assert!(grey
+        .recv(RecvMode::Blocking(Duration::from_millis(500)))
+        .is_err());
+}
+
+#[test]
+fn test_auth_token() {
+    let path = common::socket_path("auth_token");
+    let host = HostBuilder::new(&path)
+        .auth_token(b"secret")
+        .build()
+        .unwrap();
+
+    let client = thread::spawn(move || {
+        let err = ClientOptions::new()
+            .auth_token(b"wrong")
+            .connect(&path)
+            .err()
+            .unwrap();
+        assert!(matches!(err, VslError::Client(ClientError::AuthFailed)));
+        ClientOptions::new()
+            .auth_token(b"secret")
+            .connect(&path)
+            .unwrap();
+    });
+    while !client.is_finished() {
+        host.poll(10).unwrap();
+        host.process().unwrap();
+    }
+    client.join().unwrap();
+}
+
+#[test]
+fn test_auth_silent_peer() {
+    let path = common::socket_path("auth_silent_peer");
+    let host = HostBuilder::new(&path)
+        .auth_token(b"secret")
+        .build()
+        .unwrap();
+
+    // A peer which is slow to send its token does not hold up the host.
+    let mut peer = UnixStream::connect(format!("{}.auth", path)).unwrap();
+    let started = Instant::now();
+    host.process().unwrap();
+    let frame = Frame::new(16, 16, 0, "GREY").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0)
+        .unwrap();
+    host.process().unwrap();
+    assert!(started.elapsed() < Duration::from_millis(50));
+
+    // The token is read in pieces across calls.
+    peer.write_all(&6u32.to_le_bytes()).unwrap();
+    host.process().unwrap();
+    peer.write_all(b"secret").unwrap();
+    host.process().unwrap();
+    let mut answer = [0u8; 1];
+    peer.read_exact(&mut answer).unwrap();
+    assert_eq!(answer, [1]);
+}
+
+#[test]
+fn test_tee() {
+    let (host, client) = common::host_and_client("tee");
+    client.set_timeout(5.0);
+
+    let branches = client.tee(2);
+    assert_eq!(branches.len(), 2);
+    assert_eq!(branches[1].policy(), TeePolicy::DropOldest(1));
+    branches[0].set_policy(TeePolicy::Block(4));
+
+    for pts in 0..3 {
+        let mut frame = Frame::new(8, 2, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap().fill(pts as u8 + 1);
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+
+    let timeout = Duration::from_secs(5);
+    for pts in 0..3 {
+        let frame = branches[0].recv(timeout).unwrap();
+        assert_eq!(frame.pts(), pts);
+        assert!(frame.is_host_owned());
+        assert!(frame.mmap().unwrap().iter().all(|&v| v == pts as u8 + 1));
+    }
+    assert_eq!(branches[0].dropped(), 0);
+
+    // The second branch only holds the latest frame.
+    let mut received = 0;
+    loop {
+        let frame = branches[1].recv(timeout).unwrap();
+        received += 1;
+        if frame.pts() == 2 {
+            assert!(frame.mmap().unwrap().iter().all(|&v| v == 3));
+            break;
+        }
+    }
+    assert!(branches[1].dropped() >= 1);
+    assert_eq!(received + branches[1].dropped(), 3);
+
+    drop(host);
+    assert!(matches!(
+        branches[0].recv(timeout),
+        Err(VslError::Client(ClientError::Io(_)))
+    ));
+}
+
+#[test]
+fn test_prefetch() {
+    let path = common::socket_path("prefetch");
+    let host = Host::new(&path).unwrap();
+    let client = common::connect(&host, &path, ClientOptions::new().prefetch(2));
+    client.set_timeout(5.0);
+
+    for pts in 0..3 {
+        let frame = Frame::new(8, 2, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+
+    // The oldest frame is dropped once the queue is full.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.stats().frames_skipped == 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(client.stats().frames_skipped, 1);
+    for pts in 1..3 {
+        assert_eq!(client.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(), pts);
+    }
+    assert_eq!(client.stats().frames_received, 2);
+
+    drop(host);
+    let err = client.recv(RecvMode::Blocking(TIMEOUT)).err().unwrap();
+    assert!(matches!(err, VslError::Client(ClientError::Io(_))));
+}
+
+#[test]
+fn test_deliver_as() {
+    let path = common::socket_path("deliver_as");
+    let host = Host::new(&path).unwrap();
+    let client = ClientOptions::new()
+        .deliver_as(format::RGB3)
+        .connect(&path)
+        .unwrap();
+    client.set_timeout(5.0);
+    assert!(ClientOptions::new()
+        .deliver_as(format::NV12)
+        .connect(&path)
+        .is_err());
+
+    common::accept(&host);
+
+    // White in limited range YUV.
+    let mut frame = Frame::new(4, 2, 4, "NV12").unwrap();
+    frame.alloc(None).unwrap();
+    let mem = frame.mmap_mut().unwrap();
+    mem[..8].fill(235);
+    mem[8..12].fill(128);
+    frame.munmap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 7, 7)
+        .unwrap();
+    let encoded = Frame::new(16, 1, 16, "H264").unwrap();
+    encoded.alloc(None).unwrap();
+    host.post_frame(encoded, timestamp() + 10_000_000_000, 0, 8, 8)
+        .unwrap();
+
+    let rgb = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(rgb.fourcc(), format::RGB3);
+    assert_eq!((rgb.width(), rgb.height(), rgb.pts()), (4, 2, 7));
+    assert!(rgb.mmap().unwrap()[..24].iter().all(|&v| v == 255));
+    let err = client.recv(RecvMode::Blocking(TIMEOUT)).err().unwrap();
+    assert!(matches!(
+        err,
+        VslError::Client(ClientError::Convert(FrameError::UnsupportedFourcc(_)))
+    ));
+
+    // A failure while draining is returned by the next call, after the latest
+    // frame received before it.
+    for (fourcc, pts) in [("NV12", 9), ("H264", 10), ("NV12", 11)] {
+        let frame = Frame::new(16, 2, 16, fourcc).unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(client.recv(RecvMode::Latest).unwrap().pts(), 9);
+    assert!(matches!(
+        client.recv(RecvMode::NonBlocking),
+        Err(VslError::Client(ClientError::Convert(_)))
+    ));
+    assert_eq!(client.recv(RecvMode::NonBlocking).unwrap().pts(), 11);
+}
+
+#[test]
+fn test_wait_deadline() {
+    let (host, client) = common::host_and_client("wait_deadline");
+
+    // Nothing arrives, so the wait lasts the whole timeout.
+    let start = Instant::now();
+    let err = client
+        .recv(RecvMode::Blocking(Duration::from_millis(300)))
+        .err()
+        .unwrap();
+    assert!(matches!(err, VslError::Client(ClientError::Timeout)));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(290), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+    // A frame arriving part way through the wait is returned.
+    thread::scope(|scope| {
+        let waiter = scope.spawn(|| {
+            let start = Instant::now();
+            let frame = client
+                .recv(RecvMode::Blocking(Duration::from_secs(2)))
+                .unwrap();
+            return (frame.pts(), start.elapsed());
+        });
+        thread::sleep(Duration::from_millis(100));
+        let frame = Frame::new(8, 2, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, 5, 5)
+            .unwrap();
+        let (pts, elapsed) = waiter.join().unwrap();
+        assert_eq!(pts, 5);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    });
+}
+
+#[test]
+fn test_subscribe_reconnect() {
+    let path = Path::new("/tmp/test_subscribe_reconnect.vsl");
+    let post = |host: &Host, pts: i64| {
+        let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    };
+    let host = Host::new(&path).unwrap();
+    let (frames, received) = mpsc::channel();
+    let subscription = ClientOptions::new()
+        .subscribe(path, Duration::from_secs(1), move |frame| {
+            let _ = frames.send(frame.pts());
+        })
+        .unwrap();
+    let (reconnected, notified) = mpsc::channel();
+    subscription.on_reconnect(move || {
+        let _ = reconnected.send(());
+    });
+    common::accept(&host);
+    post(&host, 1);
+    assert_eq!(received.recv_timeout(Duration::from_secs(5)), Ok(1));
+
+    drop(host);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while subscription.is_connected() {
+        assert!(Instant::now() < deadline);
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let host = Host::new(&path).unwrap();
+    while subscription.reconnects() == 0 {
+        assert!(Instant::now() < deadline);
+        host.poll(10).unwrap();
+        host.process().unwrap();
+    }
+    assert_eq!(notified.recv_timeout(Duration::from_secs(5)), Ok(()));
+    post(&host, 2);
+    assert_eq!(received.recv_timeout(Duration::from_secs(5)), Ok(2));
+}
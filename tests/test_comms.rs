@@ -1 +1,109 @@
+//! End to end round trips of frames from a host to its clients.
+//!
+//! The tests need libvideostream to link and run, and skip themselves when the
+//! host's socket or buffers cannot be created, such as in sandboxes without a
+//! writable temporary directory or /dev/shm.
 
+use std::{env, path::PathBuf, time::Duration};
+use videostream::{
+    client::{Client, RecvMode},
+    frame::Frame,
+    host::Host,
+    timestamp,
+};
+
+/// Returns a socket path unique to the test and process.
+fn socket_path(name: &str) -> PathBuf {
+    return env::temp_dir().join(format!("{}-{}.vsl", name, std::process::id()));
+}
+
+/// The value of the pixel byte at `x`, `y` of the `index`th frame.
+fn pattern(index: usize, x: usize, y: usize) -> u8 {
+    return (x * 7 + y * 13 + index * 31) as u8;
+}
+
+/// Fowler-Noll-Vo hash of the bytes, as a checksum of the frame's pixels.
+fn checksum<'a>(rows: impl Iterator<Item = &'a [u8]>) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for row in rows {
+        for &byte in row {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+    return hash;
+}
+
+/// Returns a frame filled with the pattern of the `index`th frame, or None if
+/// its buffer cannot be allocated.
+fn patterned_frame(index: usize, width: u32, height: u32) -> Option<Frame> {
+    let stride = width as usize * 3;
+    let mut frame = Frame::new(width, height, stride as u32, "RGB3").unwrap();
+    if frame.alloc(None).is_err() {
+        return None;
+    }
+    let mem = frame.mmap_mut().unwrap();
+    for (y, row) in mem.chunks_mut(stride).take(height as usize).enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = pattern(index, x, y);
+        }
+    }
+    frame.munmap();
+    return Some(frame);
+}
+
+#[test]
+fn test_round_trip() {
+    const FRAMES: usize = 3;
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    let path = socket_path("test_round_trip");
+    let host = match Host::new(&path) {
+        Ok(host) => host,
+        Err(err) => {
+            eprintln!("skipping test_round_trip: cannot create host: {}", err);
+            return;
+        }
+    };
+    let client = Client::new(path.to_str().unwrap(), false).unwrap();
+    client.set_timeout(5.0);
+    host.poll(100).unwrap();
+    host.process().unwrap();
+
+    let mut sent = Vec::new();
+    for index in 0..FRAMES {
+        let frame = match patterned_frame(index, WIDTH, HEIGHT) {
+            Some(frame) => frame,
+            None => {
+                eprintln!("skipping test_round_trip: cannot allocate frames");
+                return;
+            }
+        };
+        sent.push(checksum((0..HEIGHT).map(|y| frame.row(0, y).unwrap())));
+        let pts = index as i64;
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+
+    for (index, expected) in sent.into_iter().enumerate() {
+        let frame = client
+            .recv(RecvMode::Blocking(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(
+            (frame.width(), frame.height()),
+            (WIDTH as i32, HEIGHT as i32)
+        );
+        assert_eq!(frame.pts(), index as i64);
+        for y in [0, HEIGHT / 2, HEIGHT - 1] {
+            let row = frame.row(0, y).unwrap();
+            assert!(row
+                .iter()
+                .enumerate()
+                .all(|(x, &value)| value == pattern(index, x, y as usize)));
+        }
+        assert_eq!(
+            checksum((0..HEIGHT).map(|y| frame.row(0, y).unwrap())),
+            expected
+        );
+    }
+}
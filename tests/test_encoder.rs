@@ -0,0 +1,196 @@
+use videostream::{
+    color::{HdrMetadata, MasteringDisplay},
+    encoder::{extract_parameter_sets, insert_hdr_sei, Encoder, EncoderError, VSLRect},
+    fourcc,
+    frame::Frame,
+    VslError,
+};
+
+#[test]
+fn test_flush() {
+    let encoder = Encoder::create(0, fourcc("H264").unwrap(), 30);
+    let source = Frame::new(640, 480, 0, "NV12").unwrap();
+    source.alloc(None).unwrap();
+
+    // Skip the rest of the test on platforms without a hardware encoder.
+    let output = match encoder.new_output_frame(640, 480, 33, 0, 0) {
+        Ok(output) => output,
+        Err(_) => {
+            assert!(matches!(
+                encoder.flush(),
+                Err(VslError::Encoder(EncoderError::Unavailable))
+            ));
+            return;
+        }
+    };
+    let mut crop = VSLRect::new(0, 0, 640, 480);
+    let ret = encoder.frame(&source, &output, &mut crop, std::ptr::null_mut());
+    assert_eq!(ret, 0);
+    assert!(matches!(
+        encoder.flush(),
+        Err(VslError::Encoder(EncoderError::FlushUnsupported))
+    ));
+}
+
+#[test]
+fn test_invalid_crop() {
+    let encoder = Encoder::create(0, fourcc("H264").unwrap(), 30);
+    let source = Frame::new(64, 48, 0, "NV12").unwrap();
+    source.alloc(None).unwrap();
+    let destination = Frame::new(64, 48, 0, "NV12").unwrap();
+
+    for crop in [
+        VSLRect::new(32, 32, 64, 48),
+        VSLRect::new(-1, 0, 16, 16),
+        VSLRect::new(0, 0, 0, 16),
+    ] {
+        match encoder.encode(&source, &destination, &crop) {
+            Err(VslError::Encoder(EncoderError::InvalidCrop { rect, source_size })) => {
+                assert_eq!(rect.get_x(), crop.get_x());
+                assert_eq!(source_size, (64, 48));
+            }
+            _ => panic!("crop was not rejected"),
+        }
+    }
+
+    let outside = VSLRect::new(100, 100, 16, 16);
+    assert!(matches!(
+        encoder.encode_clamped(&source, &destination, &outside),
+        Err(VslError::Encoder(EncoderError::InvalidCrop { .. }))
+    ));
+
+    // Skip the rest of the test on platforms without a hardware encoder.
+    let output = match encoder.new_output_frame(32, 16, 33, 0, 0) {
+        Ok(output) => output,
+        Err(_) => return,
+    };
+    // Clamping keeps the part of the region within the source.
+    let partial = VSLRect::new(32, 32, 64, 48);
+    encoder.encode_clamped(&source, &output, &partial).unwrap();
+}
+
+#[test]
+fn test_thumbnail() {
+    let encoder = Encoder::create(0, fourcc("H264").unwrap(), 30).with_thumbnail(160, 120);
+    let source = Frame::new(640, 480, 0, "NV12").unwrap();
+    source.alloc(None).unwrap();
+
+    // Skip the test on platforms without a hardware encoder.
+    let output = match encoder.new_output_frame(640, 480, 33, 0, 0) {
+        Ok(output) => output,
+        Err(_) => return,
+    };
+    let crop = VSLRect::new(0, 0, 640, 480);
+    let thumbnail = encoder
+        .encode(&source, &output, &crop)
+        .unwrap()
+        .thumbnail
+        .unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (160, 120));
+    assert_eq!(thumbnail.fourcc(), fourcc("RGB3").unwrap());
+}
+
+#[test]
+fn test_extract_parameter_sets() {
+    // An SPS and PPS, with a four and a three byte start code, then a slice.
+    let h264 = [
+        0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1f, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80, 0, 0, 0, 1, 0x65,
+        0x88, 0x84,
+    ];
+    assert_eq!(
+        extract_parameter_sets(fourcc("H264").unwrap(), &h264),
+        [0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1f, 0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80]
+    );
+    assert!(extract_parameter_sets(fourcc("H264").unwrap(), &h264[15..]).is_empty());
+
+    // A VPS, SPS and PPS then an IDR slice.
+    let hevc = [
+        0, 0, 0, 1, 0x40, 0x01, 0x0c, 0, 0, 0, 1, 0x42, 0x01, 0x01, 0, 0, 0, 1, 0x44, 0x01, 0xc1,
+        0, 0, 0, 1, 0x26, 0x01, 0xaf,
+    ];
+    let sets = extract_parameter_sets(fourcc("HEVC").unwrap(), &hevc);
+    assert_eq!(sets, hevc[..21]);
+    assert_eq!(extract_parameter_sets(fourcc("H265").unwrap(), &hevc), sets);
+    assert!(extract_parameter_sets(fourcc("MJPG").unwrap(), &hevc).is_empty());
+}
+
+#[test]
+fn test_insert_hdr_sei() {
+    let meta = HdrMetadata {
+        mastering_display: MasteringDisplay {
+            primaries: [(35400, 14600), (8500, 39850), (6550, 2300)],
+            white_point: (15635, 16450),
+            max_luminance: 10_000_000,
+            min_luminance: 50,
+        },
+        max_cll: 1000,
+        max_fall: 400,
+    };
+    // The SEI messages, with an emulation prevention byte keeping the zeros
+    // of the minimum luminance from forming a start code.
+    let mut messages = vec![137, 24];
+    messages.extend_from_slice(&meta.mastering_display_sei()[..20]);
+    messages.extend_from_slice(&[0, 0, 3, 0, 0x32, 144, 4, 0x03, 0xe8, 0x01, 0x90, 0x80]);
+
+    // The SEI NAL unit goes between the PPS and the slice.
+    let h264 = [
+        0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1f, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80, 0, 0, 0, 1, 0x65,
+        0x88, 0x84,
+    ];
+    let mut expected = h264[..15].to_vec();
+    expected.extend_from_slice(&[0, 0, 0, 1, 0x06]);
+    expected.extend_from_slice(&messages);
+    expected.extend_from_slice(&h264[15..]);
+    assert_eq!(
+        insert_hdr_sei(fourcc("H264").unwrap(), &h264, &meta),
+        expected
+    );
+
+    // H.265 carries them in a prefix SEI NAL unit ahead of the IDR slice.
+    let hevc = [
+        0, 0, 0, 1, 0x40, 0x01, 0x0c, 0, 0, 0, 1, 0x42, 0x01, 0x01, 0, 0, 0, 1, 0x44, 0x01, 0xc1,
+        0, 0, 0, 1, 0x26, 0x01, 0xaf,
+    ];
+    let mut expected = hevc[..21].to_vec();
+    expected.extend_from_slice(&[0, 0, 0, 1, 0x4e, 0x01]);
+    expected.extend_from_slice(&messages);
+    expected.extend_from_slice(&hevc[21..]);
+    assert_eq!(
+        insert_hdr_sei(fourcc("HEVC").unwrap(), &hevc, &meta),
+        expected
+    );
+
+    // Bitstreams without a slice or of other codecs are left unchanged.
+    assert_eq!(
+        insert_hdr_sei(fourcc("H264").unwrap(), &h264[..15], &meta),
+        h264[..15]
+    );
+    assert_eq!(insert_hdr_sei(fourcc("MJPG").unwrap(), &h264, &meta), h264);
+}
+
+#[test]
+fn test_codec_config() {
+    let encoder = Encoder::create(0, fourcc("H264").unwrap(), 30);
+    let source = Frame::new(640, 480, 0, "NV12").unwrap();
+    source.alloc(None).unwrap();
+    // Skip the rest of the test on platforms without a hardware encoder.
+    let output = match encoder.new_output_frame(640, 480, 33, 0, 0) {
+        Ok(output) => output,
+        Err(_) => {
+            assert!(matches!(
+                encoder.codec_config(),
+                Err(VslError::Encoder(EncoderError::Unavailable))
+            ));
+            return;
+        }
+    };
+    assert!(matches!(
+        encoder.codec_config(),
+        Err(VslError::Encoder(EncoderError::NoCodecConfig))
+    ));
+    let encoded = encoder
+        .encode(&source, &output, &VSLRect::new(0, 0, 640, 480))
+        .unwrap();
+    assert!(encoded.keyframe);
+    assert!(encoder.codec_config().unwrap().starts_with(&[0, 0, 0, 1]));
+}
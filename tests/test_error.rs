@@ -0,0 +1,63 @@
+use std::{error::Error as _, io, path::Path, ptr};
+use videostream::{
+    client::ClientError,
+    frame::{Frame, FrameError},
+    host::Host,
+    Error, Result, VslError,
+};
+
+/// Reads a row beyond a frame, mixing the VslError of creating the frame with
+/// the FrameError of reading it behind `?`.
+fn read_past_end() -> Result<usize> {
+    let frame = Frame::new(4, 4, 0, "GREY")?;
+    frame.alloc(None)?;
+    return Ok(frame.row(0, 4)?.len());
+}
+
+#[test]
+fn test_error_conversions() {
+    assert!(matches!(
+        read_past_end(),
+        Err(Error::Frame(FrameError::OutOfBounds))
+    ));
+
+    // Boxed module errors are recovered, others keep their message.
+    let boxed: Box<dyn std::error::Error> = Box::new(ClientError::AuthFailed);
+    assert!(matches!(
+        Error::from(boxed),
+        Error::Client(ClientError::AuthFailed)
+    ));
+    let boxed: Box<dyn std::error::Error> = "no such stream".into();
+    let err = Error::from(boxed);
+    assert!(matches!(&err, Error::Os(inner) if inner.kind() == io::ErrorKind::Other));
+    assert_eq!(err.to_string(), "no such stream");
+
+    let err = Error::from(ClientError::Timeout);
+    assert_eq!(err.to_string(), ClientError::Timeout.to_string());
+    assert!(err.source().is_some());
+}
+
+#[test]
+fn test_vsl_error() {
+    assert!(matches!(
+        Frame::new(4, 4, 0, "GREYS"),
+        Err(VslError::InvalidFourcc(_))
+    ));
+    let frame = Frame::new(4, 4, 0, "GREY").unwrap();
+    assert!(matches!(
+        frame.alloc(Some(Path::new("/vsl-test\0error"))),
+        Err(VslError::InvalidString)
+    ));
+    assert!(matches!(
+        frame.unlock(),
+        Err(VslError::Frame(FrameError::InvalidState { .. }))
+    ));
+    assert!(matches!(
+        Host::new("/tmp/test_vsl\0error.vsl"),
+        Err(VslError::InvalidString)
+    ));
+    assert!(matches!(
+        Frame::wrap(ptr::null_mut()),
+        Err(VslError::NullPointer)
+    ));
+}
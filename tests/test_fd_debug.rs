@@ -0,0 +1,44 @@
+#![cfg(feature = "fd-debug")]
+
+use std::sync::Mutex;
+use videostream::{
+    fd_debug::{open_fd_count, open_mapping_count},
+    frame::Frame,
+};
+
+/// The counts are process wide, so tests opening descriptors or mappings must
+/// not run concurrently.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_no_fd_leak() {
+    let _serial = SERIAL.lock().unwrap();
+    let baseline = open_fd_count();
+
+    for _ in 0..1000 {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        assert!(frame.handle().is_some());
+        let shared = Frame::new(64, 64, 0, "RGB3").unwrap();
+        shared
+            .attach(frame.handle().unwrap(), frame.size() as usize, 0)
+            .unwrap();
+    }
+
+    assert_eq!(open_fd_count(), baseline);
+}
+
+#[test]
+fn test_no_mapping_leak() {
+    let _serial = SERIAL.lock().unwrap();
+    let baseline = open_mapping_count();
+
+    for _ in 0..100 {
+        let mut frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap().fill(1);
+        assert!(open_mapping_count() > baseline);
+    }
+
+    assert_eq!(open_mapping_count(), baseline);
+}
@@ -0,0 +1,106 @@
+use videostream::{
+    format::{self, default_stride, format_info, layout_fits, PixelFormatInfo, Subsampling},
+    fourcc,
+    frame::Frame,
+};
+
+#[test]
+fn test_format_info() {
+    assert_eq!(
+        format_info(format::NV12),
+        Some(PixelFormatInfo {
+            planes: 2,
+            bits_per_pixel: 12,
+            subsampling: Subsampling::Yuv420,
+            is_planar: true,
+            channel_order: "Y/UV",
+        })
+    );
+    assert_eq!(
+        format_info(fourcc("BGR3").unwrap()).unwrap().channel_order,
+        "BGR"
+    );
+    assert_eq!(format_info(fourcc("H264").unwrap()), None);
+}
+
+#[test]
+fn test_format_info_planes() {
+    // The table must agree with the plane layouts used by the frame helpers.
+    let formats = [
+        "RGB3", "BGR3", "RGBA", "BGRA", "RGBX", "BGRX", "YUYV", "YUY2", "UYVY", "GREY", "Y16 ",
+        "NV12", "NV21", "NV16", "I420", "YV12", "P010", "P016",
+    ];
+    for name in formats {
+        let mut buf = vec![0u8; 64 * 48 * 4];
+        let frame =
+            unsafe { Frame::from_raw_buffer(buf.as_mut_ptr(), buf.len(), 64, 48, 64, name) }
+                .unwrap();
+        let info = frame.format_info().unwrap();
+        assert_eq!(info.planes as usize, info.channel_order.split('/').count());
+        assert!(
+            frame.plane_offset(info.planes as usize - 1).is_ok(),
+            "{}",
+            name
+        );
+        assert!(
+            frame.plane_offset(info.planes as usize).is_err(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_layout_fits() {
+    assert!(layout_fits(format::NV12, 64, 48, 64, 64 * 72));
+    assert!(!layout_fits(format::NV12, 64, 48, 64, 64 * 72 - 1));
+    assert!(!layout_fits(
+        format::RGB3,
+        u32::MAX,
+        u32::MAX,
+        u32::MAX,
+        usize::MAX
+    ));
+    assert!(layout_fits(fourcc("H264").unwrap(), 1920, 1080, 0, 1));
+    assert!(!layout_fits(fourcc("H264").unwrap(), 1920, 1080, 0, 0));
+
+    // Frames wider than 65536 pixels fit formats of every layout.
+    let (width, height) = (1 << 20, 2);
+    for (fourcc, stride, size) in [
+        (format::GREY, width, width * 2),
+        (format::RGBA, width * 4, width * 8),
+        (format::NV12, width, width * 3),
+        (format::I420, width, width * 3),
+        (format::P010, width * 2, width * 6),
+    ] {
+        assert!(layout_fits(fourcc, width, height, stride, size as usize));
+        assert!(!layout_fits(
+            fourcc,
+            width,
+            height,
+            stride,
+            size as usize - 1
+        ));
+    }
+    assert!(!layout_fits(
+        format::GREY,
+        width + 1,
+        1,
+        width + 1,
+        usize::MAX
+    ));
+}
+
+#[test]
+fn test_default_stride() {
+    assert_eq!(default_stride(format::RGB3, 640, 0), 1920);
+    assert_eq!(default_stride(format::RGB3, 641, 1), 1923);
+    assert_eq!(default_stride(format::RGB3, 641, 64), 1984);
+    assert_eq!(default_stride(format::NV12, 1920, 16), 1920);
+    assert_eq!(default_stride(format::NV12, 1921, 16), 1936);
+    // The pixels of an odd width still take a whole pair of YUYV.
+    assert_eq!(default_stride(format::YUYV, 1280, 32), 2560);
+    assert_eq!(default_stride(format::YUYV, 1, 0), 4);
+    assert_eq!(default_stride(format::YUYV, 33, 128), 128);
+    assert_eq!(default_stride(fourcc("H264").unwrap(), 1920, 64), 0);
+}
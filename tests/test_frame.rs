@@ -4,13 +4,24 @@ mod frame {
     use std::{
         fs::{self, File},
         io::Write,
-        os::fd::AsRawFd,
+        os::fd::{AsRawFd, OwnedFd},
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+    use videostream::{
+        color::{ColorRange, ColorSpace, HdrMetadata, MasteringDisplay, Rgb, Rgba},
+        encoder::VSLRect,
+        format, frame, VslError,
     };
-    use videostream::frame;
     #[test]
     fn frame() {
         //let fourcc = 0x33424752 as u32; //Hex for RGB3
-        let frame = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
+        let mut frame = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
 
         assert_eq!(frame.width(), 640);
         assert_eq!(frame.height(), 480);
@@ -22,18 +33,18 @@ mod frame {
 
         assert_eq!(frame.size(), 640 * 480 * 3);
 
-        let mem: &mut [u8] = frame.mmap_mut().unwrap();
         let mut rng = rand::thread_rng();
-        for elem in &mut *mem {
+        for elem in frame.mmap_mut().unwrap() {
             let num: u8 = rng.gen();
             *elem = num;
         }
+        let mem = frame.mmap().unwrap();
         let mem2 = frame.mmap().unwrap();
         for i in 0..mem.len() {
             assert_eq!(mem[i], mem2[i]);
         }
 
-        let frame2 = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
+        let mut frame2 = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
         frame2
             .attach(frame.handle().unwrap(), frame.size() as usize, 0)
             .unwrap();
@@ -85,6 +96,678 @@ mod frame {
         }
     }
 
+    #[test]
+    fn attach_owned() {
+        let frame = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
+
+        let mut expect = Vec::new();
+        let mut rng = rand::thread_rng();
+        for _ in 0..(frame.height() * frame.width() * 3) {
+            expect.push(rng.gen::<u8>());
+        }
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open("./temp_owned.txt")
+            .unwrap();
+        file.write_all(&expect).unwrap();
+        fs::remove_file("./temp_owned.txt").unwrap();
+
+        frame
+            .attach_owned(
+                OwnedFd::from(file),
+                (frame.height() * frame.width() * 3) as usize,
+                0,
+            )
+            .unwrap();
+
+        let mem = frame.mmap().unwrap();
+        assert_eq!(mem, &expect[..]);
+    }
+
+    #[test]
+    fn age() {
+        let frame = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
+        thread::sleep(Duration::from_millis(10));
+
+        assert!(frame.age() >= Duration::from_millis(10));
+        assert!(frame.is_stale(Duration::from_millis(5)));
+        assert!(!frame.is_stale(frame.age() + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn from_raw_buffer() {
+        let mut buffer = vec![0u8; 64 * 48 * 3];
+        buffer[0] = 42;
+
+        let mut frame = unsafe {
+            frame::Frame::from_raw_buffer(buffer.as_mut_ptr(), buffer.len(), 64, 48, 64 * 3, "RGB3")
+        }
+        .unwrap();
+        assert_eq!(frame.width(), 64);
+        assert_eq!(frame.handle(), None);
+        assert_eq!(frame.mmap().unwrap()[0], 42);
+        frame.mmap_mut().unwrap()[1] = 7;
+        drop(frame);
+        assert_eq!(buffer[1], 7);
+
+        let small = unsafe {
+            frame::Frame::from_raw_buffer(buffer.as_mut_ptr(), 16, 64, 48, 64 * 3, "RGB3")
+        };
+        assert!(matches!(
+            small,
+            Err(VslError::Frame(frame::FrameError::BufferTooSmall {
+                len: 16,
+                ..
+            }))
+        ));
+
+        // The buffer must also hold the chroma planes.
+        let luma = unsafe {
+            frame::Frame::from_raw_buffer(buffer.as_mut_ptr(), 64 * 48, 64, 48, 64, "NV12")
+        };
+        assert!(matches!(
+            luma,
+            Err(VslError::Frame(frame::FrameError::BufferTooSmall {
+                required: 4608,
+                len: 3072
+            }))
+        ));
+    }
+
+    #[test]
+    fn new_with_release() {
+        let released = Arc::new(AtomicBool::new(false));
+        let flag = released.clone();
+        let frame = frame::Frame::new_with_release(
+            640,
+            480,
+            0,
+            "RGB3",
+            Box::new(move || flag.store(true, Ordering::SeqCst)),
+        )
+        .unwrap();
+        frame.alloc(None).unwrap();
+
+        assert!(!released.load(Ordering::SeqCst));
+        drop(frame);
+        assert!(released.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn row() {
+        // Rows are padded from 30 to 32 bytes.
+        let mut frame = frame::Frame::new(10, 4, 32, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        let mem = frame.mmap_mut().unwrap();
+        for (i, elem) in mem.iter_mut().enumerate() {
+            *elem = (i / 32) as u8;
+        }
+
+        for y in 0..4 {
+            let row = frame.row(0, y).unwrap();
+            assert_eq!(row.len(), 30);
+            assert!(row.iter().all(|&v| v == y as u8));
+        }
+        assert!(matches!(
+            frame.row(0, 4),
+            Err(VslError::Frame(frame::FrameError::OutOfBounds))
+        ));
+        assert!(matches!(
+            frame.row(1, 0),
+            Err(VslError::Frame(frame::FrameError::InvalidPlane {
+                plane: 1,
+                planes: 1
+            }))
+        ));
+
+        let nv12 = frame::Frame::new(64, 48, 64, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        assert_eq!(nv12.row(0, 47).unwrap().len(), 64);
+        assert_eq!(nv12.row(1, 23).unwrap().len(), 64);
+        assert!(matches!(
+            nv12.row(1, 24),
+            Err(VslError::Frame(frame::FrameError::OutOfBounds))
+        ));
+    }
+
+    #[test]
+    fn crop() {
+        // Each byte of the NV12 frame holds its row within the plane.
+        let mut frame = frame::Frame::new(16, 8, 16, "NV12").unwrap();
+        frame.alloc(None).unwrap();
+        let mem = frame.mmap_mut().unwrap();
+        for (i, elem) in mem.iter_mut().enumerate() {
+            *elem = (i / 16 % 8) as u8 + if i < 128 { 0 } else { 100 };
+        }
+
+        let rect = VSLRect::new(4, 2, 6, 4);
+        let view = frame.crop_view(&rect).unwrap();
+        assert_eq!((view.width(), view.height()), (6, 4));
+        let parent_row = frame.row(0, 3).unwrap();
+        let row = view.row(0, 1).unwrap();
+        assert_eq!(row, &parent_row[4..10]);
+        assert_eq!(row.as_ptr(), parent_row[4..].as_ptr());
+        assert_eq!(view.row(1, 0).unwrap(), [101; 6]);
+        assert!(matches!(
+            view.row(1, 2),
+            Err(VslError::Frame(frame::FrameError::OutOfBounds))
+        ));
+
+        let copy = frame.crop_copy(&rect).unwrap();
+        assert_eq!((copy.width(), copy.height()), (6, 4));
+        assert_eq!(copy.to_vec().unwrap(), view.to_vec().unwrap());
+
+        assert!(matches!(
+            frame.crop_view(&VSLRect::new(3, 2, 4, 4)),
+            Err(VslError::Frame(frame::FrameError::MisalignedRegion {
+                x: 3,
+                y: 2
+            }))
+        ));
+        assert!(matches!(
+            frame.crop_view(&VSLRect::new(12, 0, 6, 4)),
+            Err(VslError::Frame(frame::FrameError::OutOfBounds))
+        ));
+
+        // Packed formats without subsampling crop at any pixel.
+        let rgb = frame::Frame::new(8, 8, 0, "RGB3").unwrap();
+        rgb.alloc(None).unwrap();
+        let view = rgb.crop_view(&VSLRect::new(3, 5, 5, 3)).unwrap();
+        assert_eq!(view.row(0, 2).unwrap().len(), 15);
+    }
+
+    #[test]
+    fn new_planar() {
+        // The chroma plane of the hardware buffer starts on its own page, and
+        // both planes are padded to 32 bytes per row.
+        let frame = frame::Frame::new_planar(16, 4, "NV12", &[32, 32], &[0, 4096]).unwrap();
+        let mut data = vec![0u8; 4096 + 64];
+        data[32..48].fill(1);
+        data[4096 + 32..4096 + 48].fill(2);
+        let path = "./temp_planar.bin";
+        fs::write(path, &data).unwrap();
+        let file = File::options().read(true).write(true).open(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert!(matches!(
+            frame.attach(file.as_raw_fd(), 4096, 0).unwrap_err(),
+            VslError::Frame(frame::FrameError::BufferTooSmall { .. })
+        ));
+        frame.attach(file.as_raw_fd(), data.len(), 0).unwrap();
+        assert_eq!(frame.row(0, 1).unwrap(), [1; 16]);
+        assert_eq!(frame.row(1, 1).unwrap(), [2; 16]);
+
+        assert!(matches!(
+            frame::Frame::new_planar(16, 4, "NV12", &[16], &[0])
+                .err()
+                .unwrap(),
+            VslError::Frame(frame::FrameError::PlaneCountMismatch {
+                planes: 2,
+                strides: 1,
+                offsets: 1
+            })
+        ));
+        assert!(matches!(
+            frame::Frame::new_planar(16, 4, "I420", &[16, 4, 8], &[0, 64, 96])
+                .err()
+                .unwrap(),
+            VslError::Frame(frame::FrameError::StrideTooSmall { plane: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn gray() {
+        let info = format::format_info(format::Y16).unwrap();
+        assert_eq!((info.planes, info.bits_per_pixel), (1, 16));
+        assert_eq!(
+            format::format_info(format::Y8),
+            format::format_info(format::GREY)
+        );
+
+        let mut depth = frame::Frame::new(8, 4, 0, "Y16 ").unwrap();
+        depth.alloc(None).unwrap();
+        depth.set_color_range(ColorRange::Full);
+        depth.fill_color(Rgba::new(255, 255, 255, 255)).unwrap();
+        depth
+            .draw_rect(&VSLRect::new(0, 0, 2, 2), Rgb::new(0, 0, 0), 1)
+            .unwrap();
+        assert_eq!(depth.row(0, 3).unwrap(), [0xff; 16]);
+        assert_eq!(depth.row(0, 0).unwrap()[..6], [0, 0, 0, 0, 0xff, 0xff]);
+        assert_eq!(depth.to_vec().unwrap().len(), 8 * 4 * 2);
+
+        let mut ir = frame::Frame::new(8, 4, 0, "GREY").unwrap();
+        ir.alloc(None).unwrap();
+        ir.fill_color(Rgba::new(255, 255, 255, 255)).unwrap();
+        // Limited range by default.
+        assert_eq!(ir.row(0, 0).unwrap(), [235; 8]);
+
+        let mut rgb = frame::Frame::new(8, 4, 0, "RGB3").unwrap();
+        rgb.alloc(None).unwrap();
+        rgb.set_color_space(ColorSpace::Bt601);
+        rgb.fill_color(Rgba::new(255, 0, 0, 255)).unwrap();
+        let gray = rgb.to_gray().unwrap();
+        assert_eq!(gray.fourcc(), format::GREY);
+        assert_eq!(gray.color_range(), ColorRange::Full);
+        assert_eq!(gray.to_vec().unwrap(), [76; 32]);
+
+        let gray = ir.to_gray().unwrap();
+        assert_eq!(gray.to_vec().unwrap(), [235; 32]);
+        assert_eq!(gray.color_range(), ColorRange::Limited);
+    }
+
+    #[test]
+    fn backing_copies() {
+        let mut frame = frame::Frame::new(16, 8, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap().fill(42);
+
+        let shm = frame.to_shm_copy().unwrap();
+        assert!(shm.path().is_some());
+        assert_eq!(shm.format(), frame.format());
+        assert_eq!(shm.to_vec().unwrap(), frame.to_vec().unwrap());
+
+        // Only systems with a dmabuf heap can allocate dmabufs.
+        match frame.to_dmabuf_copy() {
+            Ok(dmabuf) => assert_eq!(dmabuf.to_vec().unwrap(), frame.to_vec().unwrap()),
+            Err(err) => assert!(matches!(
+                err,
+                VslError::Frame(frame::FrameError::DmabufUnavailable)
+            )),
+        }
+    }
+
+    #[test]
+    fn reinterpret_fourcc() {
+        let mut frame = frame::Frame::new(16, 8, 0, "YUYV").unwrap();
+        frame.alloc(None).unwrap();
+        frame.reinterpret_fourcc(format::YUY2).unwrap();
+        assert_eq!(frame.fourcc(), format::YUY2);
+        assert_eq!(frame.row(0, 7).unwrap().len(), 32);
+
+        // NV12 adds a second plane while RGB3 packs fewer bytes per pixel.
+        assert!(matches!(
+            frame.reinterpret_fourcc(format::NV12),
+            Err(VslError::Frame(
+                frame::FrameError::IncompatibleFourcc { .. }
+            ))
+        ));
+        let mut rgb = frame::Frame::new(16, 8, 0, "RGBA").unwrap();
+        assert!(matches!(
+            rgb.reinterpret_fourcc(format::RGB3),
+            Err(VslError::Frame(
+                frame::FrameError::IncompatibleFourcc { .. }
+            ))
+        ));
+        rgb.reinterpret_fourcc(format::BGRX).unwrap();
+        assert_eq!(rgb.fourcc(), format::BGRX);
+    }
+
+    #[test]
+    fn raw_transfer() {
+        let released = Arc::new(AtomicBool::new(false));
+        let flag = released.clone();
+        let mut frame = frame::Frame::new_with_release(
+            64,
+            48,
+            0,
+            "RGB3",
+            Box::new(move || flag.store(true, Ordering::SeqCst)),
+        )
+        .unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap()[0] = 42;
+
+        let ptr = frame.into_raw();
+        assert!(!released.load(Ordering::SeqCst));
+
+        let frame = unsafe { frame::Frame::from_raw(ptr) };
+        assert_eq!(frame.get_ptr(), ptr);
+        assert_eq!(frame.mmap().unwrap()[0], 42);
+        drop(frame);
+        assert!(released.load(Ordering::SeqCst));
+
+        // An owned descriptor stays open until the library releases the frame.
+        let path = "./temp_raw_transfer.bin";
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)
+            .unwrap();
+        file.set_len(64 * 48 * 3).unwrap();
+        fs::remove_file(path).unwrap();
+        let fd = OwnedFd::from(file);
+        let raw_fd = fd.as_raw_fd();
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.attach_owned(fd, 64 * 48 * 3, 0).unwrap();
+        let ptr = frame.into_raw();
+        assert!(is_open(raw_fd));
+        drop(unsafe { frame::Frame::from_raw(ptr) });
+        assert!(!is_open(raw_fd));
+    }
+
+    /// Returns true if the descriptor is open in this process.
+    fn is_open(fd: i32) -> bool {
+        return unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1;
+    }
+
+    #[test]
+    fn fill_color() {
+        let mut rgba = frame::Frame::new(8, 4, 0, "RGBA").unwrap();
+        rgba.alloc(None).unwrap();
+        rgba.fill_color(Rgba::new(1, 2, 3, 4)).unwrap();
+        assert!(rgba.mmap().unwrap()[..128]
+            .chunks_exact(4)
+            .all(|px| px == [1, 2, 3, 4]));
+
+        let mut bgr = frame::Frame::new(8, 4, 0, "BGR3").unwrap();
+        bgr.alloc(None).unwrap();
+        bgr.fill_color(Rgba::new(1, 2, 3, 4)).unwrap();
+        assert!(bgr.mmap().unwrap()[..96]
+            .chunks_exact(3)
+            .all(|px| px == [3, 2, 1]));
+
+        let mut nv12 = frame::Frame::new(8, 4, 0, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        assert!(matches!(
+            nv12.fill_color(Rgba::new(0, 0, 0, 255)),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(_)))
+        ));
+    }
+
+    #[test]
+    fn blend_over() {
+        let mut frame = frame::Frame::new(4, 4, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.fill_color(Rgba::new(0, 0, 200, 255)).unwrap();
+
+        let mut overlay = frame::Frame::new(2, 2, 0, "RGBA").unwrap();
+        overlay.alloc(None).unwrap();
+        overlay.fill_color(Rgba::new(255, 0, 0, 51)).unwrap();
+
+        // Placed at the bottom right corner the overlay is clipped to 1x1.
+        frame.blend_over(&overlay, 3, 3).unwrap();
+        let mem = frame.mmap().unwrap();
+        assert_eq!(&mem[45..48], [51, 0, 160]);
+        assert!(mem[..45].chunks_exact(3).all(|px| px == [0, 0, 200]));
+    }
+
+    #[test]
+    fn draw_rect() {
+        let mut frame = frame::Frame::new(8, 8, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.fill_color(Rgba::new(0, 0, 0, 255)).unwrap();
+
+        // The rectangle hangs off the right edge and is clipped.
+        let red = Rgb::new(255, 0, 0);
+        frame.draw_rect(&VSLRect::new(2, 2, 10, 4), red, 1).unwrap();
+
+        let mem = frame.mmap().unwrap();
+        let pixel = |x: usize, y: usize| &mem[(y * 8 + x) * 3..(y * 8 + x + 1) * 3];
+        for x in 2..8 {
+            assert_eq!(pixel(x, 2), [255, 0, 0]);
+            assert_eq!(pixel(x, 5), [255, 0, 0]);
+        }
+        for y in 3..5 {
+            assert_eq!(pixel(2, y), [255, 0, 0]);
+            assert_eq!(pixel(3, y), [0, 0, 0]);
+            assert_eq!(pixel(7, y), [0, 0, 0]);
+        }
+        assert_eq!(pixel(1, 2), [0, 0, 0]);
+        assert_eq!(pixel(2, 6), [0, 0, 0]);
+
+        let green = Rgb::new(0, 255, 0);
+        frame
+            .draw_rects(&[(VSLRect::new(-1, -1, 2, 2), green)], 1)
+            .unwrap();
+        assert_eq!(frame.mmap().unwrap()[..3], [0, 255, 0]);
+
+        // Lines thicker than half the rectangle fill it without crossing out.
+        frame.fill_color(Rgba::new(0, 0, 0, 255)).unwrap();
+        frame.draw_rect(&VSLRect::new(2, 2, 3, 4), red, 10).unwrap();
+        let mem = frame.mmap().unwrap();
+        let pixel = |x: usize, y: usize| &mem[(y * 8 + x) * 3..(y * 8 + x + 1) * 3];
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = (2..5).contains(&x) && (2..6).contains(&y);
+                let expected = if inside { [255, 0, 0] } else { [0, 0, 0] };
+                assert_eq!(pixel(x, y), expected, "{},{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn alloc_aligned() {
+        let frame = frame::Frame::new(100, 10, 0, "RGB3").unwrap();
+        frame.alloc_aligned(None, 4096).unwrap();
+        assert_eq!(frame.size(), 4096);
+        assert!(frame.mmap().unwrap().len() >= 3000);
+
+        // The chroma plane is covered along with the luma.
+        let nv12 = frame::Frame::new(640, 480, 0, "NV12").unwrap();
+        nv12.alloc_aligned(None, 4096).unwrap();
+        assert_eq!(nv12.size(), 462848);
+        assert_eq!(nv12.row(1, 239).unwrap().len(), 640);
+
+        let frame = frame::Frame::new(100, 10, 0, "RGB3").unwrap();
+        assert!(matches!(
+            frame.alloc_aligned(None, 3000),
+            Err(VslError::Frame(frame::FrameError::InvalidAlignment(3000)))
+        ));
+    }
+
+    #[test]
+    fn alloc_with() {
+        let backings = [
+            frame::AllocBacking::Auto,
+            frame::AllocBacking::SharedMemory(None),
+            frame::AllocBacking::SharedMemory(Some("/vsl-test-alloc-with".into())),
+        ];
+        for backing in backings {
+            let mut frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+            frame.alloc_with(backing).unwrap();
+            assert!(frame.handle().is_some());
+            let err = frame.alloc_with(frame::AllocBacking::Auto).unwrap_err();
+            assert!(invalid_state(&err, "allocate", "allocated"));
+            frame.mmap_mut().unwrap()[0] = 42;
+        }
+
+        // Dmabufs never fall back to shared memory.
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        for heap in ["/dev/dma_heap/unknown", "/vsl-test-not-a-heap"] {
+            let backing = frame::AllocBacking::DmaBuf(Some(heap.into()));
+            assert!(matches!(
+                frame.alloc_with(backing),
+                Err(VslError::Frame(frame::FrameError::DmabufUnavailable))
+            ));
+        }
+        match frame.alloc_with(frame::AllocBacking::DmaBuf(None)) {
+            Ok(()) => assert!(frame.handle().is_some()),
+            Err(err) => assert!(matches!(
+                err,
+                VslError::Frame(frame::FrameError::DmabufUnavailable)
+            )),
+        }
+        assert_eq!(frame::AllocBacking::default(), frame::AllocBacking::Auto);
+    }
+
+    #[test]
+    fn export_import() {
+        let mut frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        assert!(matches!(
+            frame.export(),
+            Err(VslError::Frame(frame::FrameError::NoBuffer))
+        ));
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap()[7] = 42;
+
+        let export = frame.export().unwrap();
+        assert_eq!(export.width, 64);
+        assert_eq!(export.height, 48);
+        assert_eq!(export.stride, 64 * 3);
+        assert_eq!(export.offset, 0);
+        drop(frame);
+
+        let frame = frame::Frame::import(export).unwrap();
+        assert_eq!(frame.width(), 64);
+        assert_eq!(frame.fourcc(), videostream::fourcc("RGB3").unwrap());
+        assert_eq!(frame.mmap().unwrap()[7], 42);
+    }
+
+    #[test]
+    fn odd_dimensions() {
+        // Chroma of 641x481 rounds up to 321x241, each case lists the plane
+        // offsets and the length of the unpadded copy.
+        let cases = [
+            ("I420", 641, vec![0, 308321, 385682], 308321 + 2 * 321 * 241),
+            ("NV12", 642, vec![0, 308802], 308321 + 642 * 241),
+            ("NV16", 642, vec![0, 308802], 308321 + 642 * 481),
+            ("YUYV", 1284, vec![0], 1284 * 481),
+        ];
+        for (fourcc, stride, offsets, len) in cases {
+            let mut buf = vec![1u8; 2 * 642 * 481];
+            let frame = unsafe {
+                frame::Frame::from_raw_buffer(buf.as_mut_ptr(), buf.len(), 641, 481, stride, fourcc)
+            }
+            .unwrap();
+            for (plane, offset) in offsets.iter().enumerate() {
+                assert_eq!(frame.plane_offset(plane).unwrap(), *offset, "{}", fourcc);
+                assert!(frame.plane_slice(plane).is_ok(), "{}", fourcc);
+            }
+            assert!(frame.plane_offset(offsets.len()).is_err());
+            assert_eq!(frame.to_vec().unwrap().len(), len, "{}", fourcc);
+        }
+    }
+
+    #[test]
+    fn interlaced() {
+        let mut frame = frame::Frame::new(720, 576, 0, "UYVY").unwrap();
+        assert_eq!(frame.interlaced(), frame::Interlace::Progressive);
+        frame.set_interlaced(frame::Interlace::TopFieldFirst);
+        assert_eq!(frame.interlaced(), frame::Interlace::TopFieldFirst);
+    }
+
+    #[test]
+    fn color_space() {
+        let mut hd = frame::Frame::new(1280, 720, 0, "NV12").unwrap();
+        assert_eq!(hd.color_space(), ColorSpace::Bt709);
+        assert_eq!(hd.color_range(), ColorRange::Limited);
+        hd.set_color_space(ColorSpace::Bt2020);
+        hd.set_color_range(ColorRange::Full);
+        assert_eq!(hd.color_space(), ColorSpace::Bt2020);
+        assert_eq!(hd.color_range(), ColorRange::Full);
+
+        let sd = frame::Frame::new(720, 576, 0, "NV12").unwrap();
+        assert_eq!(sd.color_space(), ColorSpace::Bt601);
+    }
+
+    #[test]
+    fn convert_to() {
+        // Limited range BT.601 red is Y 81, U 90, V 240.
+        let mut nv12 = frame::Frame::new(4, 4, 4, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        let mem = nv12.mmap_mut().unwrap();
+        mem[..16].fill(81);
+        for uv in mem[16..24].chunks_exact_mut(2) {
+            uv.copy_from_slice(&[90, 240]);
+        }
+
+        let mut rgb = frame::Frame::new(4, 4, 0, "RGB3").unwrap();
+        rgb.alloc(None).unwrap();
+        nv12.convert_to(&mut rgb).unwrap();
+        let close = |px: &[u8], expected: [u8; 3]| {
+            px.iter().zip(expected).all(|(&a, b)| a.abs_diff(b) <= 2)
+        };
+        assert!(rgb.mmap().unwrap()[..48]
+            .chunks_exact(3)
+            .all(|px| close(px, [255, 0, 0])));
+
+        // The same samples decode to a different color under BT.709.
+        nv12.set_color_space(ColorSpace::Bt709);
+        nv12.convert_to(&mut rgb).unwrap();
+        assert!(!close(&rgb.mmap().unwrap()[..3], [255, 0, 0]));
+
+        let mut small = frame::Frame::new(2, 2, 0, "RGB3").unwrap();
+        assert!(matches!(
+            nv12.convert_to(&mut small),
+            Err(VslError::Frame(frame::FrameError::DimensionMismatch))
+        ));
+    }
+
+    #[test]
+    fn copy_to() {
+        let mut rgb = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        rgb.alloc(None).unwrap();
+        for px in rgb.mmap_mut().unwrap().chunks_exact_mut(3) {
+            px.copy_from_slice(&[10, 20, 30]);
+        }
+        rgb.munmap();
+
+        // The copy scales the frame down to the destination.
+        let mut small = frame::Frame::new(32, 24, 0, "RGB3").unwrap();
+        small.alloc(None).unwrap();
+        rgb.copy_to(&mut small).unwrap();
+        assert_eq!((small.width(), small.height()), (32, 24));
+        assert_eq!((rgb.width(), rgb.height()), (64, 48));
+        assert!(small.mmap().unwrap()[..32 * 24 * 3]
+            .chunks_exact(3)
+            .all(|px| px == [10, 20, 30]));
+
+        let mut unallocated = frame::Frame::new(32, 24, 0, "RGB3").unwrap();
+        assert!(invalid_state(
+            &rgb.copy_to(&mut unallocated).unwrap_err(),
+            "copy into",
+            "unallocated"
+        ));
+    }
+
+    #[test]
+    fn convert_simd_matches_scalar() {
+        // NV12 is converted with the SIMD kernels, where supported, while I420
+        // holding the same samples takes the scalar path.  The width leaves a
+        // partial block for the scalar tail of each NV12 row.
+        let (width, height) = (66, 10);
+        let mut rng = rand::thread_rng();
+        let luma: Vec<u8> = (0..width * height).map(|_| rng.gen()).collect();
+        let chroma: Vec<(u8, u8)> = (0..width * height / 4).map(|_| rng.gen()).collect();
+
+        let mut nv12 = frame::Frame::new(width as u32, height as u32, 0, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        let offset = nv12.plane_offset(1).unwrap();
+        let mem = nv12.mmap_mut().unwrap();
+        mem[..luma.len()].copy_from_slice(&luma);
+        for (i, (u, v)) in chroma.iter().enumerate() {
+            mem[offset + i * 2] = *u;
+            mem[offset + i * 2 + 1] = *v;
+        }
+
+        let mut i420 = frame::Frame::new(width as u32, height as u32, 0, "I420").unwrap();
+        i420.alloc(None).unwrap();
+        let (u_offset, v_offset) = (i420.plane_offset(1).unwrap(), i420.plane_offset(2).unwrap());
+        let mem = i420.mmap_mut().unwrap();
+        mem[..luma.len()].copy_from_slice(&luma);
+        for (i, (u, v)) in chroma.iter().enumerate() {
+            mem[u_offset + i] = *u;
+            mem[v_offset + i] = *v;
+        }
+
+        for range in [ColorRange::Limited, ColorRange::Full] {
+            nv12.set_color_range(range);
+            i420.set_color_range(range);
+            for fourcc in ["RGB3", "BGRA"] {
+                let mut fast = frame::Frame::new(width as u32, height as u32, 0, fourcc).unwrap();
+                fast.alloc(None).unwrap();
+                let mut scalar = frame::Frame::new(width as u32, height as u32, 0, fourcc).unwrap();
+                scalar.alloc(None).unwrap();
+                nv12.convert_to(&mut fast).unwrap();
+                i420.convert_to(&mut scalar).unwrap();
+                assert_eq!(fast.mmap().unwrap(), scalar.mmap().unwrap(), "{}", fourcc);
+            }
+        }
+    }
+
     #[test]
     fn bad_attach() {
         let frame = frame::Frame::new(640, 480, 0, "RGB3").unwrap();
@@ -104,9 +787,783 @@ mod frame {
         };
     }
 
+    fn invalid_state(err: &VslError, operation: &str, state: &str) -> bool {
+        return matches!(err, VslError::Frame(frame::FrameError::InvalidState { operation: o, state: s })
+            if *o == operation && *s == state);
+    }
+
+    #[test]
+    fn mmap_before_alloc() {
+        let mut frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        assert!(invalid_state(
+            &frame.mmap().unwrap_err(),
+            "map",
+            "unallocated"
+        ));
+        assert!(invalid_state(
+            &frame.mmap_mut().unwrap_err(),
+            "map",
+            "unallocated"
+        ));
+    }
+
+    #[test]
+    fn alloc_twice() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        let err = frame.alloc(None).unwrap_err();
+        assert!(invalid_state(&err, "allocate", "allocated"));
+        assert!(invalid_state(
+            &frame.alloc_aligned(None, 4096).unwrap_err(),
+            "allocate",
+            "allocated"
+        ));
+    }
+
+    #[test]
+    fn attach_while_mapped() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        let other = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        other.alloc(None).unwrap();
+
+        frame.mmap().unwrap();
+        let fd = other.handle().unwrap();
+        let err = frame.attach(fd, other.size() as usize, 0).unwrap_err();
+        assert!(invalid_state(&err, "attach", "mapped"));
+
+        frame.munmap();
+        frame.attach(fd, other.size() as usize, 0).unwrap();
+    }
+
+    #[test]
+    fn lock_before_alloc() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        let err = frame.trylock().unwrap_err();
+        assert!(invalid_state(&err, "lock", "unallocated"));
+    }
+
+    #[test]
+    fn lock_twice() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.trylock().unwrap();
+        let err = frame.trylock().unwrap_err();
+        assert!(invalid_state(&err, "lock", "locked"));
+
+        // A locked frame can still be read but its buffer cannot be replaced.
+        frame.mmap().unwrap();
+        let err = frame.attach(frame.handle().unwrap(), 1, 0).unwrap_err();
+        assert!(invalid_state(&err, "attach", "locked"));
+        frame.unlock().unwrap();
+    }
+
+    #[test]
+    fn lock_keeps_mapping() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+
+        // A mapping made before or during the lock outlives it, so it can
+        // still be unmapped once unlocked.
+        frame.mmap().unwrap();
+        frame.trylock().unwrap();
+        frame.unlock().unwrap();
+        assert!(is_mapped(&frame));
+        frame.munmap();
+        assert!(invalid_state(
+            &frame.unlock().unwrap_err(),
+            "unlock",
+            "allocated"
+        ));
+
+        frame.trylock().unwrap();
+        frame.mmap().unwrap();
+        frame.unlock().unwrap();
+        assert!(is_mapped(&frame));
+        frame.munmap();
+
+        // Unmapping during the lock, or a guard mapping it, leaves it unmapped.
+        frame.mmap().unwrap();
+        frame.trylock().unwrap();
+        frame.munmap();
+        frame.unlock().unwrap();
+        assert!(invalid_state(
+            &frame.unlock().unwrap_err(),
+            "unlock",
+            "allocated"
+        ));
+        frame.trylock().unwrap();
+        drop(frame.map().unwrap());
+        frame.unlock().unwrap();
+        assert!(invalid_state(
+            &frame.unlock().unwrap_err(),
+            "unlock",
+            "allocated"
+        ));
+    }
+
+    #[test]
+    fn lock_guard() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        {
+            let guard = frame.locked().unwrap();
+            assert_eq!(guard.width(), 64);
+            assert!(invalid_state(
+                &frame.locked().err().unwrap(),
+                "lock",
+                "locked"
+            ));
+        }
+        frame.trylock().unwrap();
+        frame.unlock().unwrap();
+    }
+
+    #[test]
+    fn wait_ready() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        let err = frame.wait_ready(Duration::from_millis(10)).unwrap_err();
+        assert!(invalid_state(&err, "wait for", "unallocated"));
+
+        frame.alloc(None).unwrap();
+        frame.wait_ready(Duration::from_millis(10)).unwrap();
+        frame.mmap().unwrap();
+        frame.wait_ready(Duration::ZERO).unwrap();
+        // The frame is still mapped, so it cannot be attached elsewhere.
+        let err = frame.attach(frame.handle().unwrap(), 1, 0).unwrap_err();
+        assert!(invalid_state(&err, "attach", "mapped"));
+
+        // The frame's own lock is never released while waiting.
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.trylock().unwrap();
+        let err = frame.wait_ready(Duration::from_millis(10)).unwrap_err();
+        assert!(invalid_state(&err, "wait for", "locked"));
+    }
+
+    /// Returns true if the frame reports being mapped when unlocked without
+    /// a lock.
+    fn is_mapped(frame: &frame::Frame) -> bool {
+        return invalid_state(&frame.unlock().unwrap_err(), "unlock", "mapped");
+    }
+
+    #[test]
+    fn map_guard() {
+        let mut frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        assert!(invalid_state(
+            &frame.map().err().unwrap(),
+            "map",
+            "unallocated"
+        ));
+        frame.alloc(None).unwrap();
+        {
+            let mut guard = frame.map_mut().unwrap();
+            assert_eq!(guard.len(), 64 * 48 * 3);
+            guard[0] = 7;
+        }
+        assert!(!is_mapped(&frame));
+
+        // The mapping remains until the last guard is dropped.
+        let first = frame.map().unwrap();
+        let second = frame.map().unwrap();
+        drop(first);
+        frame.munmap();
+        assert!(is_mapped(&frame));
+        assert_eq!(second[0], 7);
+        drop(second);
+        assert!(!is_mapped(&frame));
+
+        // Guards leave the mapping of mmap in place.
+        let mem = frame.mmap().unwrap();
+        drop(frame.map().unwrap());
+        assert!(is_mapped(&frame));
+        assert_eq!(mem[0], 7);
+        frame.munmap();
+        assert!(!is_mapped(&frame));
+
+        // Nor do they unmap slices of mmap taken while they are held.
+        let guard = frame.map().unwrap();
+        let mem = frame.mmap().unwrap();
+        drop(guard);
+        assert!(is_mapped(&frame));
+        assert_eq!(mem[0], 7);
+        frame.munmap();
+        assert!(!is_mapped(&frame));
+
+        // Once munmap is called the last guard unmaps the frame.
+        let guard = frame.map().unwrap();
+        frame.mmap().unwrap();
+        frame.munmap();
+        assert!(is_mapped(&frame));
+        drop(guard);
+        assert!(!is_mapped(&frame));
+    }
+
+    #[test]
+    fn unlock_without_lock() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        let err = frame.unlock().unwrap_err();
+        assert!(invalid_state(&err, "unlock", "unallocated"));
+
+        frame.alloc(None).unwrap();
+        let err = frame.unlock().unwrap_err();
+        assert!(invalid_state(&err, "unlock", "allocated"));
+    }
+
+    #[test]
+    fn use_after_release() {
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        frame.release();
+        frame.release();
+
+        assert!(invalid_state(&frame.mmap().unwrap_err(), "map", "released"));
+        let err = frame.alloc(None).unwrap_err();
+        assert!(invalid_state(&err, "allocate", "released"));
+        let err = frame.trylock().unwrap_err();
+        assert!(invalid_state(&err, "lock", "released"));
+    }
+
     #[test]
     fn fourcc() {}
 
+    #[test]
+    fn stride() {
+        // Rows aligned to 64 bytes.
+        let frame = frame::Frame::new(100, 4, 320, "RGB3").unwrap();
+        assert_eq!(frame.stride(), 320);
+        assert_eq!(frame.stride_plane(0), Some(320));
+        assert_eq!(frame.stride_plane(1), None);
+
+        let i420 = frame::Frame::new(16, 4, 32, "I420").unwrap();
+        let strides: Vec<Option<u32>> = (0..4)
+            .map(|plane| return i420.stride_plane(plane))
+            .collect();
+        assert_eq!(strides, [Some(32), Some(16), Some(16), None]);
+
+        let planar = frame::Frame::new_planar(16, 4, "NV12", &[32, 64], &[0, 4096]).unwrap();
+        assert_eq!(planar.stride(), 32);
+        assert_eq!(planar.stride_plane(1), Some(64));
+
+        let jpeg = frame::Frame::new(16, 4, 64, "MJPG").unwrap();
+        assert_eq!(jpeg.stride(), 64);
+        assert_eq!(jpeg.stride_plane(0), None);
+    }
+
+    #[test]
+    fn planes_layout() {
+        let frame = frame::Frame::new(16, 4, 16, "NV12").unwrap();
+        assert!(frame.planes_layout().iter().all(|plane| plane.fd.is_none()));
+        frame.alloc(None).unwrap();
+        let fd = frame.handle();
+        assert_eq!(
+            frame.planes_layout(),
+            [
+                frame::PlaneLayout {
+                    fd,
+                    offset: 0,
+                    stride: 16,
+                    size: 64
+                },
+                frame::PlaneLayout {
+                    fd,
+                    offset: 64,
+                    stride: 16,
+                    size: 32
+                },
+            ]
+        );
+
+        // Offsets include where the buffer was attached within the descriptor,
+        // which is never mapped here so it need not be large enough.
+        let planar = frame::Frame::new_planar(16, 4, "NV12", &[32, 32], &[0, 4096]).unwrap();
+        planar.attach(fd.unwrap(), 4096 + 64, 128).unwrap();
+        let layout = planar.planes_layout();
+        assert_eq!(
+            layout.iter().map(|plane| plane.offset).collect::<Vec<_>>(),
+            [128, 4096 + 128]
+        );
+        assert_eq!(layout[1].size, 64);
+
+        let encoded = frame::Frame::new(16, 4, 16, "H264").unwrap();
+        encoded.alloc(None).unwrap();
+        assert_eq!(encoded.planes_layout().len(), 1);
+        assert_eq!(encoded.planes_layout()[0].size, encoded.size() as usize);
+    }
+
+    #[test]
+    fn histogram() {
+        // Rows are padded to 16 bytes, the padding is not counted.
+        let mut rgb = frame::Frame::new(4, 2, 16, "RGB3").unwrap();
+        rgb.alloc(None).unwrap();
+        let mem = rgb.mmap_mut().unwrap();
+        mem.fill(7);
+        for x in 0..4 {
+            mem[x * 3..x * 3 + 3].copy_from_slice(&[255, 0, 0]);
+            mem[16 + x * 3..16 + x * 3 + 3].copy_from_slice(&[0, 0, 255]);
+        }
+        let histogram = rgb.histogram(2).unwrap();
+        assert_eq!(histogram.bins(), 2);
+        assert_eq!(histogram.channels, [[4, 4], [8, 0], [4, 4]]);
+        assert_eq!(rgb.histogram(0).unwrap().bins(), 1);
+        assert_eq!(rgb.histogram(1000).unwrap().bins(), 256);
+
+        let mut nv12 = frame::Frame::new(4, 2, 4, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        let mem = nv12.mmap_mut().unwrap();
+        mem[..4].fill(10);
+        mem[4..8].fill(30);
+        mem[8..12].fill(128);
+        assert_eq!(nv12.luma_mean().unwrap(), 20.0);
+        let histogram = nv12.histogram(4).unwrap();
+        assert_eq!(histogram.channels[0], [8, 0, 0, 0]);
+        assert_eq!(histogram.channels[1], [0, 0, 8, 0]);
+
+        let encoded = frame::Frame::new(16, 1, 16, "H264").unwrap();
+        encoded.alloc(None).unwrap();
+        assert!(matches!(
+            encoded.luma_mean(),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(_)))
+        ));
+    }
+
+    #[test]
+    fn from_file_mmap() {
+        let path = std::env::temp_dir().join(format!("vsl-mmap-{}.raw", std::process::id()));
+        let mut raw = vec![0u8; 8 * 4];
+        for (row, line) in raw.chunks_mut(8).enumerate() {
+            line.fill(row as u8 + 1);
+        }
+        fs::write(&path, &raw).unwrap();
+
+        let mut frame = frame::Frame::from_file_mmap(&path, 6, 4, 8, "GREY").unwrap();
+        assert_eq!(frame.handle(), None);
+        let mem = frame.mmap_mut().unwrap();
+        assert_eq!(&mem[..], &raw[..]);
+        // The mapping is private, writes leave the file as it was.
+        mem.fill(0);
+        drop(frame);
+        assert_eq!(fs::read(&path).unwrap(), raw);
+
+        assert!(matches!(
+            frame::Frame::from_file_mmap(&path, 6, 5, 8, "GREY"),
+            Err(VslError::Frame(frame::FrameError::BufferTooSmall { .. }))
+        ));
+        assert!(matches!(
+            frame::Frame::from_file_mmap(path.with_extension("missing"), 6, 4, 8, "GREY"),
+            Err(VslError::Os(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn orientation() {
+        let mut gray = frame::Frame::new(3, 2, 4, "GREY").unwrap();
+        gray.alloc(None).unwrap();
+        assert_eq!(gray.orientation(), frame::Rotation::None);
+        let mem = gray.mmap_mut().unwrap();
+        mem[..3].copy_from_slice(&[1, 2, 3]);
+        mem[4..7].copy_from_slice(&[4, 5, 6]);
+
+        let expected: [(frame::Rotation, &[&[u8]]); 6] = [
+            (frame::Rotation::None, &[&[1, 2, 3], &[4, 5, 6]]),
+            (frame::Rotation::Rot90, &[&[4, 1], &[5, 2], &[6, 3]]),
+            (frame::Rotation::Rot180, &[&[6, 5, 4], &[3, 2, 1]]),
+            (frame::Rotation::Rot270, &[&[3, 6], &[2, 5], &[1, 4]]),
+            (frame::Rotation::FlipH, &[&[3, 2, 1], &[6, 5, 4]]),
+            (frame::Rotation::FlipV, &[&[4, 5, 6], &[1, 2, 3]]),
+        ];
+        for (rot, rows) in expected {
+            gray.set_orientation(rot);
+            assert_eq!(gray.orientation(), rot);
+            let upright = gray.apply_orientation().unwrap();
+            assert_eq!(upright.orientation(), frame::Rotation::None);
+            assert_eq!(upright.height() as usize, rows.len());
+            assert_eq!(upright.width() as usize, rows[0].len());
+            for (y, row) in rows.iter().enumerate() {
+                assert_eq!(upright.row(0, y as u32).unwrap(), *row, "{:?}", rot);
+            }
+        }
+
+        // The interleaved chroma moves as a pair.
+        let mut nv12 = frame::Frame::new(4, 2, 4, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        let mem = nv12.mmap_mut().unwrap();
+        mem[..8].copy_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        mem[8..12].copy_from_slice(&[10, 11, 20, 21]);
+        nv12.set_orientation(frame::Rotation::Rot180);
+        let upright = nv12.apply_orientation().unwrap();
+        assert_eq!(upright.row(0, 0).unwrap(), [7, 6, 5, 4]);
+        assert_eq!(upright.row(1, 0).unwrap(), [20, 21, 10, 11]);
+
+        let mut nv16 = frame::Frame::new(4, 2, 4, "NV16").unwrap();
+        nv16.alloc(None).unwrap();
+        nv16.set_orientation(frame::Rotation::Rot90);
+        assert!(matches!(
+            nv16.apply_orientation(),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(_)))
+        ));
+        let yuyv = frame::Frame::new(4, 2, 0, "YUYV").unwrap();
+        yuyv.alloc(None).unwrap();
+        assert!(matches!(
+            yuyv.apply_orientation(),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(_)))
+        ));
+    }
+
+    #[test]
+    fn physical_address() {
+        // Shared memory is not physically contiguous.
+        let frame = frame::Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        assert_eq!(frame.paddr(), None);
+        assert_eq!(frame.physical_address(), None);
+    }
+
+    #[test]
+    fn hdr_metadata() {
+        // The BT.2020 primaries and D65 white point of a 1000 nit display.
+        let meta = HdrMetadata {
+            mastering_display: MasteringDisplay {
+                primaries: [(35400, 14600), (8500, 39850), (6550, 2300)],
+                white_point: (15635, 16450),
+                max_luminance: 10_000_000,
+                min_luminance: 50,
+            },
+            max_cll: 1000,
+            max_fall: 400,
+        };
+        let bytes = meta.to_bytes();
+        assert_eq!(&bytes[..4], &[0x21, 0x34, 0x9b, 0xaa]);
+        assert_eq!(&bytes[24..], &[0x03, 0xe8, 0x01, 0x90]);
+        assert_eq!(HdrMetadata::from_bytes(&bytes), Some(meta));
+        assert_eq!(HdrMetadata::from_bytes(&bytes[1..]), None);
+
+        let mut frame = frame::Frame::new(16, 16, 0, "NV12").unwrap();
+        frame.alloc(None).unwrap();
+        assert_eq!(frame.hdr_metadata(), None);
+        frame.set_hdr_metadata(meta);
+        assert_eq!(frame.hdr_metadata(), Some(meta));
+        assert_eq!(frame.to_shm_copy().unwrap().hdr_metadata(), Some(meta));
+    }
+
+    #[test]
+    fn map_range() {
+        let mut frame = frame::Frame::new(1024, 16, 0, "RGBA").unwrap();
+        assert!(matches!(
+            frame.map_range(0, 16),
+            Err(VslError::Frame(frame::FrameError::InvalidState { .. }))
+        ));
+        frame.alloc(None).unwrap();
+        for (index, value) in frame.mmap_mut().unwrap().iter_mut().enumerate() {
+            *value = (index % 251) as u8;
+        }
+        let mem = frame.mmap().unwrap();
+        let size = frame.size() as usize;
+
+        // The range starts part way into a page and crosses into the next.
+        let range = frame.map_range(5000, 4000).unwrap();
+        assert_eq!(&range[..], &mem[5000..9000]);
+        assert_eq!(frame.map_range(size - 1, 1).unwrap()[0], mem[size - 1]);
+        assert!(frame.map_range(0, 0).unwrap().is_empty());
+        for (offset, len) in [(size, 1), (0, size + 1), (usize::MAX, 2)] {
+            assert!(matches!(
+                frame.map_range(offset, len),
+                Err(VslError::Frame(frame::FrameError::OutOfBounds))
+            ));
+        }
+
+        let mut buf = vec![7u8; 64];
+        let external =
+            unsafe { frame::Frame::from_raw_buffer(buf.as_mut_ptr(), 64, 4, 4, 16, "RGBA") }
+                .unwrap();
+        assert_eq!(&external.map_range(8, 8).unwrap()[..], &[7; 8]);
+    }
+
+    #[test]
+    fn subsample() {
+        let mut gray = frame::Frame::new(4, 4, 4, "GREY").unwrap();
+        gray.alloc(None).unwrap();
+        let mem = gray.mmap_mut().unwrap();
+        for (index, value) in mem.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+        gray.set_orientation(frame::Rotation::Rot90);
+        let half = gray.subsample(2).unwrap();
+        assert_eq!((half.width(), half.height()), (2, 2));
+        assert_eq!(half.row(0, 0).unwrap(), [0, 2]);
+        assert_eq!(half.row(0, 1).unwrap(), [8, 10]);
+        assert_eq!(half.orientation(), frame::Rotation::Rot90);
+        assert_eq!(
+            gray.subsample(1).unwrap().row(0, 3).unwrap(),
+            [12, 13, 14, 15]
+        );
+
+        for factor in [0, 3, 5] {
+            assert!(matches!(
+                gray.subsample(factor),
+                Err(VslError::Frame(frame::FrameError::InvalidFactor(_)))
+            ));
+        }
+        let third = gray.subsample_floor(3).unwrap();
+        assert_eq!((third.width(), third.height()), (1, 1));
+        assert_eq!(third.row(0, 0).unwrap(), [0]);
+
+        // The interleaved chroma is sampled as a pair.
+        let mut nv12 = frame::Frame::new(4, 4, 4, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        let mem = nv12.mmap_mut().unwrap();
+        mem[16..24].copy_from_slice(&[10, 11, 20, 21, 30, 31, 40, 41]);
+        let half = nv12.subsample(2).unwrap();
+        assert_eq!(half.row(1, 0).unwrap(), [10, 11]);
+
+        let yuyv = frame::Frame::new(4, 2, 0, "YUYV").unwrap();
+        yuyv.alloc(None).unwrap();
+        assert!(matches!(
+            yuyv.subsample(2),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(_)))
+        ));
+    }
+
+    #[test]
+    fn same_buffer() {
+        let frame = frame::Frame::new(16, 16, 0, "RGB3").unwrap();
+        let other = frame::Frame::new(16, 16, 0, "RGB3").unwrap();
+        assert!(frame.same_buffer(&frame));
+        assert!(!frame.same_buffer(&other));
+        frame.alloc(None).unwrap();
+        other.alloc(None).unwrap();
+        assert!(!frame.same_buffer(&other));
+
+        // The imported frame holds its own descriptor of the same buffer.
+        let imported = frame::Frame::import(frame.export().unwrap()).unwrap();
+        assert!(frame.same_buffer(&imported));
+        assert!(imported.same_buffer(&frame));
+        assert!(!other.same_buffer(&imported));
+
+        let mut buf = vec![0u8; 64];
+        let external =
+            unsafe { frame::Frame::from_raw_buffer(buf.as_mut_ptr(), 64, 4, 4, 16, "RGBA") }
+                .unwrap();
+        assert!(!external.same_buffer(&frame));
+    }
+
+    #[test]
+    fn available_buffer_memory() {
+        // Shared memory is available on every system the library supports.
+        let shm = frame::available_buffer_memory(Some(Path::new("/vsl-test"))).unwrap();
+        assert!(shm > 0);
+        assert!(frame::available_buffer_memory(None).is_some());
+        assert_eq!(
+            frame::available_buffer_memory(Some(Path::new("/dev/dma_heap/unknown"))),
+            None
+        );
+    }
+
+    /// Sums every `step`th byte of the buffer, checking a large frame without
+    /// reading all of it.
+    fn sampled_checksum(mem: &[u8], step: usize) -> u64 {
+        return mem
+            .iter()
+            .step_by(step)
+            .map(|&byte| return byte as u64)
+            .sum();
+    }
+
+    #[test]
+    fn large_frame() {
+        // A 2.5 GiB frame, beyond the library's int sizes.  Only sampled bytes
+        // are written so most of the buffer is never backed, but the test is
+        // skipped on systems which could not back all of it.
+        let (width, height) = (65536, 40960);
+        let size = width as usize * height as usize;
+        let step = 1 << 24;
+        match frame::available_buffer_memory(Some(Path::new("/vsl-test"))) {
+            Some(available) if available >= size as u64 => {}
+            _ => return,
+        }
+
+        let mut frame = frame::Frame::new(width, height, 0, "GREY").unwrap();
+        frame
+            .alloc_with(frame::AllocBacking::SharedMemory(None))
+            .unwrap();
+        assert_eq!(frame.buffer_size(), size);
+        assert_eq!(frame.size(), i32::MAX);
+        let mem = frame.mmap_mut().unwrap();
+        assert_eq!(mem.len(), size);
+        for (index, byte) in mem.iter_mut().step_by(step).enumerate() {
+            *byte = index as u8 ^ 0x5a;
+        }
+        mem[size - 1] = 0xa5;
+        let checksum = sampled_checksum(mem, step);
+        frame.munmap();
+
+        let import = frame::Frame::import(frame.export().unwrap()).unwrap();
+        assert_eq!(import.buffer_size(), size);
+        let mem = import.mmap().unwrap();
+        assert_eq!(sampled_checksum(mem, step), checksum);
+        assert_eq!(mem[size - 1], 0xa5);
+        import.munmap();
+        let tail = import.map_range(size - 4096, 4096).unwrap();
+        assert_eq!(tail[4095], 0xa5);
+    }
+
+    #[test]
+    fn large_layouts() {
+        // Beyond 2 GiB the size covers every plane, not only the luma rows.
+        let frame = frame::Frame::new(65536, 40960, 0, "NV12").unwrap();
+        assert_eq!(frame.buffer_size(), 65536 * 40960 * 3 / 2);
+        let frame = frame::Frame::new(65536, 32768, 0, "I420").unwrap();
+        assert_eq!(frame.buffer_size(), 65536 * 32768 * 3 / 2);
+
+        // Frames wider than 65536 pixels have a layout.
+        let frame = frame::Frame::new(131072, 4, 0, "RGBA").unwrap();
+        frame.alloc(None).unwrap();
+        assert_eq!(frame.row(0, 3).unwrap().len(), 131072 * 4);
+        let frame = frame::Frame::new(131072, 4, 0, "NV12").unwrap();
+        frame.alloc(None).unwrap();
+        assert_eq!(frame.row(1, 1).unwrap().len(), 131072);
+    }
+
+    /// Returns the 16 bit words of the frame's buffer.
+    fn words(frame: &frame::Frame, count: usize) -> Vec<u16> {
+        let mem = frame.mmap().unwrap();
+        return mem[..count * 2]
+            .chunks_exact(2)
+            .map(|word| return u16::from_le_bytes([word[0], word[1]]))
+            .collect();
+    }
+
+    #[test]
+    fn bit_depth() {
+        let mut p010 = frame::Frame::new(4, 2, 8, "P010").unwrap();
+        // Unpadded, so the buffer holds exactly the luma and chroma planes.
+        p010.alloc_aligned(None, 1).unwrap();
+        assert_eq!(p010.size(), 24);
+        let samples: [u16; 12] = [
+            0x3ff, 0x200, 1, 0, 0, 1, 0x200, 0x3ff, 0x40, 0x80, 0x100, 0x3c0,
+        ];
+        for (word, sample) in p010.mmap_mut().unwrap().chunks_exact_mut(2).zip(samples) {
+            word.copy_from_slice(&(sample << 6).to_le_bytes());
+        }
+        assert_eq!(p010.bit_layout(), frame::BitLayout::Msb);
+
+        let lsb = p010.repack_10bit(frame::BitLayout::Lsb).unwrap();
+        assert_eq!(lsb.fourcc(), p010.fourcc());
+        assert_eq!(lsb.bit_layout(), frame::BitLayout::Lsb);
+        assert_eq!(words(&lsb, 12), samples);
+        let msb = lsb.repack_10bit(frame::BitLayout::Msb).unwrap();
+        assert_eq!(words(&msb, 12), words(&p010, 12));
+
+        // The samples are replicated into the low bits, from either layout.
+        let p016 = p010.to_16bit().unwrap();
+        assert_eq!(p016.fourcc(), format::P016);
+        assert_eq!(words(&p016, 4), [0xffff, 0x8020, 0x0040, 0x0000]);
+        assert_eq!(words(&lsb.to_16bit().unwrap(), 12), words(&p016, 12));
+        let back = p016.to_10bit().unwrap();
+        assert_eq!(back.fourcc(), format::P010);
+        assert_eq!(words(&back, 12), words(&p010, 12));
+
+        let nv12 = frame::Frame::new(4, 2, 0, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        assert!(matches!(
+            nv12.repack_10bit(frame::BitLayout::Lsb),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(
+                format::NV12
+            )))
+        ));
+        assert!(matches!(
+            p010.to_10bit(),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(
+                format::P010
+            )))
+        ));
+        assert!(matches!(
+            p016.to_16bit(),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(
+                format::P016
+            )))
+        ));
+    }
+
+    #[test]
+    fn resize_yuv() {
+        let mut nv12 = frame::Frame::new(64, 48, 0, "NV12").unwrap();
+        nv12.alloc(None).unwrap();
+        let mem = nv12.mmap_mut().unwrap();
+        // Luma alternates between columns and the chroma pairs are constant.
+        for (index, value) in mem[..64 * 48].iter_mut().enumerate() {
+            *value = if index % 2 == 0 { 100 } else { 200 };
+        }
+        for pair in mem[64 * 48..].chunks_mut(2) {
+            pair.copy_from_slice(&[50, 220]);
+        }
+
+        let small = nv12.resize_yuv(32, 24).unwrap();
+        assert_eq!((small.width(), small.height()), (32, 24));
+        assert_eq!(small.fourcc(), nv12.fourcc());
+        let layout: Vec<(usize, u32, usize)> = small
+            .planes_layout()
+            .iter()
+            .map(|plane| return (plane.offset, plane.stride, plane.size))
+            .collect();
+        assert_eq!(layout, [(0, 32, 32 * 24), (32 * 24, 32, 32 * 12)]);
+        assert!(small.row(0, 5).unwrap().iter().all(|&value| value == 150));
+        let chroma = small.row(1, 3).unwrap();
+        assert!(chroma.chunks(2).all(|pair| pair == [50, 220]));
+
+        // Enlarging repeats the nearest samples.
+        let large = small.resize_yuv(64, 48).unwrap();
+        assert_eq!(large.planes_layout()[1].offset, 64 * 48);
+        assert!(large.row(0, 0).unwrap().iter().all(|&value| value == 150));
+
+        let i420 = frame::Frame::new(14, 10, 0, "I420").unwrap();
+        i420.alloc(None).unwrap();
+        // The chroma planes of 7x5 are scaled to 3x3.
+        let scaled = i420.resize_yuv(6, 6).unwrap();
+        assert_eq!(scaled.planes_layout().len(), 3);
+        assert_eq!(scaled.row(1, 2).unwrap().len(), 3);
+
+        let rgb = frame::Frame::new(4, 4, 0, "RGB3").unwrap();
+        rgb.alloc(None).unwrap();
+        assert!(matches!(
+            rgb.resize_yuv(2, 2),
+            Err(VslError::Frame(frame::FrameError::UnsupportedFourcc(_)))
+        ));
+    }
+
+    #[test]
+    fn probe() {
+        let probe = frame::Frame::probe("NV12", 1920, 1080).unwrap();
+        assert!(probe.is_probe());
+        assert_eq!((probe.width(), probe.height()), (1920, 1080));
+        assert_eq!(probe.fourcc(), format::fourcc_code(b"NV12"));
+        assert!(invalid_state(
+            &probe.alloc(None).unwrap_err(),
+            "allocate",
+            "metadata only"
+        ));
+        assert!(invalid_state(
+            &probe.alloc_aligned(None, 4096).unwrap_err(),
+            "allocate",
+            "metadata only"
+        ));
+        assert!(matches!(
+            probe.mmap(),
+            Err(VslError::Frame(frame::FrameError::InvalidState { .. }))
+        ));
+
+        // Frames without pixels are metadata only as well.
+        let empty = frame::Frame::new(0, 0, 0, "RGB3").unwrap();
+        assert!(empty.is_probe());
+        assert!(invalid_state(
+            &empty.alloc(None).unwrap_err(),
+            "allocate",
+            "metadata only"
+        ));
+        assert!(!frame::Frame::new(1, 1, 0, "RGB3").unwrap().is_probe());
+    }
+
     #[test]
     fn bad_fourcc() {}
 
@@ -1,5 +1,28 @@
-use std::path::PathBuf;
-use videostream::host::Host;
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    io::Write,
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use videostream::{
+    client::{Client, ClientOptions, RecvMode},
+    encoder::Encoder,
+    frame::Frame,
+    host::{Host, HostBuilder, HostError, PostReceipt, RecordFormat, ShutdownReason},
+    timestamp, Endpoint, VslError,
+};
+
+mod common;
+
+use common::TIMEOUT;
 
 #[test]
 fn test_host() {
@@ -21,3 +44,299 @@ fn test_host() {
     // let host2 = Host::new(&path);
     // assert!(host2.is_err());
 }
+
+#[test]
+fn test_keep_last_frame() {
+    let path = common::socket_path("keep_last_frame");
+    let host = HostBuilder::new(&path)
+        .keep_last_frame(true)
+        .build()
+        .unwrap();
+
+    // The frame is posted before any client has connected.
+    let frame = Frame::new(640, 480, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    let expires = timestamp() + 10_000_000_000;
+    host.post_frame(frame, expires, 0, 0, 0).unwrap();
+
+    let client = thread::spawn(move || {
+        let client = Client::new(&path, false).unwrap();
+        client.set_timeout(5.0);
+        let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+        return (frame.width(), frame.height());
+    });
+
+    while !client.is_finished() {
+        common::accept(&host);
+    }
+
+    assert_eq!(client.join().unwrap(), (640, 480));
+}
+
+#[test]
+fn test_control_frames_disabled() {
+    let path = common::socket_path("control_frames_disabled");
+    let host = Host::new(&path).unwrap();
+
+    assert!(matches!(
+        host.send_heartbeat(),
+        Err(VslError::Host(HostError::ControlFramesDisabled))
+    ));
+    let probe = Frame::probe("RGB3", 64, 48).unwrap();
+    assert!(matches!(
+        host.post_probe(&probe),
+        Err(VslError::Host(HostError::ControlFramesDisabled))
+    ));
+    // The host is released without sending a goodbye.
+    host.shutdown(ShutdownReason::Terminate).unwrap();
+}
+
+#[test]
+fn test_post_receipt() {
+    let path = common::socket_path("post_receipt");
+    let host = HostBuilder::new(&path)
+        .control_frames(true)
+        .build()
+        .unwrap();
+    let _clients = [
+        Client::new(&path, false).unwrap(),
+        Client::new(&path, false).unwrap(),
+    ];
+    for _ in 0..2 {
+        common::accept(&host);
+    }
+
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    let receipt = host
+        .post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0)
+        .unwrap();
+    assert_eq!(
+        receipt,
+        PostReceipt {
+            client_count: 2,
+            bytes: 64 * 48 * 3,
+        }
+    );
+
+    host.send_heartbeat().unwrap();
+    let stats = host.stats().unwrap();
+    assert_eq!(stats.clients, 2);
+    assert_eq!((stats.frames_posted, stats.bytes_posted), (1, 64 * 48 * 3));
+    assert!(stats.uptime > Duration::ZERO);
+}
+
+#[test]
+fn test_serve_with_cancel() {
+    let host = Host::new("/tmp/test_serve_with_cancel.vsl").unwrap();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let mut produced = 0;
+    let flag = cancel.clone();
+    host.serve_with_cancel(
+        || {
+            produced += 1;
+            if produced == 3 {
+                flag.store(true, Ordering::Release);
+            }
+            let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+            frame.alloc(None).unwrap();
+            return Some(frame);
+        },
+        cancel,
+    )
+    .unwrap();
+    assert_eq!(produced, 3);
+
+    let mut remaining = 2;
+    host.serve(|| {
+        if remaining == 0 {
+            return None;
+        }
+        remaining -= 1;
+        let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        return Some(frame);
+    })
+    .unwrap();
+    assert_eq!(remaining, 0);
+}
+
+#[test]
+fn test_on_client_connected() {
+    let path = common::socket_path("on_client_connected");
+    let host = Host::new(&path).unwrap();
+    let connected = Rc::new(Cell::new(0));
+    let counter = connected.clone();
+    host.on_client_connected(move || counter.set(counter.get() + 1));
+    let encoder = Arc::new(Encoder::create(0, videostream::fourcc("H264").unwrap(), 30));
+    host.attach_encoder(encoder.clone());
+
+    let _first = common::connect(&host, &path, ClientOptions::new());
+    assert_eq!(connected.get(), 1);
+
+    let _second = common::connect(&host, &path, ClientOptions::new());
+    host.process().unwrap();
+    assert_eq!(connected.get(), 2);
+    assert!(encoder.keyframe_requested());
+}
+
+#[test]
+fn test_on_frame_acked() {
+    let path = common::socket_path("on_frame_acked");
+    let host = Host::new(&path).unwrap();
+    let acked = Rc::new(RefCell::new(Vec::new()));
+    let received = acked.clone();
+    host.on_frame_acked(move |client, serial, processing| {
+        received.borrow_mut().push((client, serial, processing));
+    })
+    .unwrap();
+
+    let client = ClientOptions::new().send_acks(true).connect(&path).unwrap();
+    let silent = Client::new(&path, false).unwrap();
+    assert_eq!(silent.ack_id(), None);
+    common::accept(&host);
+
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    host.post_frame(frame, timestamp() + 1_000_000_000, 0, 0, 0)
+        .unwrap();
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    let serial = frame.serial();
+    thread::sleep(Duration::from_millis(20));
+    drop(frame);
+    drop(silent.recv(RecvMode::Blocking(TIMEOUT)).unwrap());
+
+    host.process().unwrap();
+    let acked = acked.borrow();
+    assert_eq!(acked.len(), 1);
+    assert_eq!(acked[0].0, client.ack_id().unwrap());
+    assert_eq!(acked[0].1, serial);
+    assert!(acked[0].2 >= Duration::from_millis(20));
+    let id = client.ack_id().unwrap();
+    assert_eq!(host.stats().unwrap().frames_dropped_per_client, [(id, 0)]);
+    drop(acked);
+
+    // Acknowledgements report the frames the client skipped.
+    let decimated = common::connect(
+        &host,
+        &path,
+        ClientOptions::new().send_acks(true).decimate(2),
+    );
+    for pts in 1..=2 {
+        let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 1_000_000_000, 0, pts, pts)
+            .unwrap();
+    }
+    assert_eq!(
+        decimated.recv(RecvMode::Blocking(TIMEOUT)).unwrap().pts(),
+        2
+    );
+    host.process().unwrap();
+    let mut expected = vec![(id, 0), (decimated.ack_id().unwrap(), 1)];
+    expected.sort();
+    assert_eq!(host.stats().unwrap().frames_dropped_per_client, expected);
+}
+
+#[test]
+fn test_recording() {
+    let path = common::socket_path("recording");
+    let saved = PathBuf::from("/tmp/test_recording.frames");
+    let raw = PathBuf::from("/tmp/test_recording.raw");
+    let host = Host::new(&path).unwrap();
+    assert_eq!(host.stop_recording().unwrap().frames_written, 0);
+
+    let post = |index: u8| {
+        let mut frame = Frame::new(4, 2, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap().fill(index);
+        let pts = index as i64 * 1000;
+        host.post_frame(frame, timestamp() + 1_000_000_000, 0, pts, pts)
+            .unwrap();
+    };
+    host.start_recording(&saved, RecordFormat::Saved).unwrap();
+    post(1);
+    post(2);
+    // Starting another recording finishes the first.
+    host.start_recording(&raw, RecordFormat::Raw).unwrap();
+    post(3);
+    let stats = host.stop_recording().unwrap();
+    assert_eq!((stats.frames_written, stats.frames_dropped), (1, 0));
+    post(4);
+
+    let mut file = fs::File::open(&saved).unwrap();
+    for index in [1, 2] {
+        let frame = Frame::load(&mut file).unwrap();
+        assert_eq!(frame.pts(), index as i64 * 1000);
+        assert_eq!(frame.row(0, 1).unwrap(), [index; 4]);
+    }
+    assert_eq!(fs::read(&raw).unwrap(), [3; 8]);
+
+    // Raw frames are not part of an encoded recording.
+    host.start_recording(&raw, RecordFormat::Encoded).unwrap();
+    post(5);
+    assert_eq!(host.stop_recording().unwrap().frames_written, 0);
+    assert!(fs::read(&raw).unwrap().is_empty());
+    let _ = fs::remove_file(&saved);
+    let _ = fs::remove_file(&raw);
+}
+
+#[test]
+fn test_post_frame_with_fence() {
+    let (host, client) = common::host_and_client("post_frame_with_fence");
+
+    // A socket stands in for the sync_file, signalled once it is readable.
+    // Shared memory frames cannot carry the fence so the host waits for it.
+    let (fence, mut signal) = UnixStream::pair().unwrap();
+    let signaller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        signal.write_all(&[1]).unwrap();
+    });
+
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    let start = Instant::now();
+    host.post_frame_with_fence(frame, fence.into(), timestamp() + 1_000_000_000, 0, 3, 3)
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    signaller.join().unwrap();
+
+    let frame = client.recv(RecvMode::Blocking(TIMEOUT)).unwrap();
+    assert_eq!(frame.pts(), 3);
+    assert!(frame.acquire_fence().is_none());
+}
+
+#[test]
+fn test_post_locked_frame() {
+    let path = common::socket_path("post_locked_frame");
+    let host = Host::new(&path).unwrap();
+
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    frame.trylock().unwrap();
+    assert!(frame.is_locked());
+    assert!(matches!(
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0),
+        Err(VslError::Host(HostError::FrameNotReady))
+    ));
+
+    let frame = Frame::new(64, 48, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    frame.trylock().unwrap();
+    frame.unlock().unwrap();
+    host.post_frame(frame, timestamp() + 10_000_000_000, 0, 0, 0)
+        .unwrap();
+}
+
+#[test]
+fn test_endpoint() {
+    let path = PathBuf::from("/tmp/test_endpoint.vsl");
+    let endpoint = Endpoint::from(path.clone());
+    let host = Host::bind(&endpoint).unwrap();
+    assert_eq!(path, host.path().unwrap());
+    let client = ClientOptions::new().connect_endpoint(&endpoint).unwrap();
+    common::accept(&host);
+    assert_eq!(host.sockets().unwrap().len(), 2);
+    drop(client);
+}
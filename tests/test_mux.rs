@@ -0,0 +1,132 @@
+use std::time::Duration;
+use videostream::{
+    client::{Client, RecvMode},
+    encoder::EncoderError,
+    frame::Frame,
+    host::Host,
+    mux::{Mp4Recorder, MuxError},
+    timestamp, VslError,
+};
+
+/// An SPS and PPS followed by an IDR slice.
+const KEYFRAME: [u8; 22] = [
+    0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1f, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80, 0, 0, 0, 1, 0x65, 0x88,
+    0x84,
+];
+
+/// A predicted slice.
+const PREDICTED: [u8; 7] = [0, 0, 0, 1, 0x41, 0x9a, 0x02];
+
+/// Returns an encoded frame holding the bitstream, the rest of its buffer
+/// being zeros which end the last NAL unit.
+fn encoded_frame(fourcc: &str, bitstream: &[u8]) -> Frame {
+    let mut frame = Frame::new(64, 1, 64, fourcc).unwrap();
+    frame.alloc(None).unwrap();
+    frame.mmap_mut().unwrap()[..bitstream.len()].copy_from_slice(bitstream);
+    return frame;
+}
+
+/// Returns the kind and payload of each top level box of the file.
+fn boxes(file: &[u8]) -> Vec<(String, &[u8])> {
+    let mut boxes = Vec::new();
+    let mut rest = file;
+    while rest.len() >= 8 {
+        let size = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind = String::from_utf8_lossy(&rest[4..8]).to_string();
+        boxes.push((kind, &rest[8..size]));
+        rest = &rest[size..];
+    }
+    assert!(rest.is_empty());
+    return boxes;
+}
+
+#[test]
+fn test_mp4_recorder() {
+    let mut recorder = Mp4Recorder::encoded(Vec::new());
+    // Frames before the first keyframe cannot be decoded.
+    recorder.push(&encoded_frame("H264", &PREDICTED)).unwrap();
+    recorder.push(&encoded_frame("H264", &KEYFRAME)).unwrap();
+    recorder.push(&encoded_frame("H264", &PREDICTED)).unwrap();
+    let file = recorder.finish().unwrap();
+
+    let boxes = boxes(&file);
+    let kinds: Vec<&str> = boxes.iter().map(|(kind, _)| kind.as_str()).collect();
+    assert_eq!(kinds, ["ftyp", "moov", "moof", "mdat", "moof", "mdat"]);
+    let moov = boxes[1].1;
+    let avcc = moov.windows(4).position(|kind| kind == b"avcC").unwrap();
+    assert_eq!(
+        &moov[avcc + 4..avcc + 16],
+        [1, 0x42, 0x00, 0x1f, 0xff, 0xe1, 0, 4, 0x67, 0x42, 0x00, 0x1f]
+    );
+    // The samples hold their slices with four byte lengths.
+    assert_eq!(boxes[3].1, [0, 0, 0, 3, 0x65, 0x88, 0x84]);
+    assert_eq!(boxes[5].1, [0, 0, 0, 3, 0x41, 0x9a, 0x02]);
+
+    // Without a keyframe the recording is empty.
+    let mut recorder = Mp4Recorder::encoded(Vec::new());
+    recorder.push(&encoded_frame("H264", &PREDICTED)).unwrap();
+    assert!(recorder.finish().unwrap().is_empty());
+}
+
+#[test]
+fn test_mp4_composition_offset() {
+    let path = "/tmp/test_mp4_composition_offset.vsl";
+    let host = Host::new(path).unwrap();
+    let client = Client::new(path, false).unwrap();
+    host.poll(100).unwrap();
+    host.process().unwrap();
+
+    // A keyframe presented 40ms after it is decoded, then a frame presented as
+    // it is decoded.
+    let expires = timestamp() + 10_000_000_000;
+    for (bitstream, pts, dts) in [
+        (&KEYFRAME[..], 40_000_000, 0),
+        (&PREDICTED[..], 33_000_000, 33_000_000),
+    ] {
+        let frame = encoded_frame("H264", bitstream);
+        host.post_frame(frame, expires, 33_000_000, pts, dts)
+            .unwrap();
+    }
+    let mut recorder = Mp4Recorder::encoded(Vec::new());
+    for _ in 0..2 {
+        let frame = client
+            .recv(RecvMode::Blocking(Duration::from_secs(5)))
+            .unwrap();
+        recorder.push(&frame).unwrap();
+    }
+    let file = recorder.finish().unwrap();
+
+    // Each trun gives the composition offset of its sample at 90 kHz.
+    let boxes = boxes(&file);
+    for (moof, offset) in [(boxes[2].1, 3600), (boxes[4].1, 0)] {
+        let trun = moof.windows(4).position(|kind| kind == b"trun").unwrap() + 4;
+        let word = |index: usize| {
+            let at = trun + index * 4;
+            return u32::from_be_bytes(moof[at..at + 4].try_into().unwrap());
+        };
+        assert_eq!(word(0), 0xf01);
+        assert_eq!(word(6), offset);
+    }
+}
+
+#[test]
+fn test_mp4_recorder_errors() {
+    let mut recorder = Mp4Recorder::encoded(Vec::new());
+    assert!(matches!(
+        recorder.push(&encoded_frame("MJPG", &[0xff, 0xd8])),
+        Err(VslError::Mux(MuxError::UnsupportedCodec(_)))
+    ));
+
+    let raw = Frame::new(16, 16, 0, "NV12").unwrap();
+    raw.alloc(None).unwrap();
+    assert!(matches!(
+        recorder.push(&raw),
+        Err(VslError::Mux(MuxError::Encoder(EncoderError::Unavailable)))
+    ));
+
+    recorder.push(&encoded_frame("H264", &KEYFRAME)).unwrap();
+    assert!(matches!(
+        recorder.push(&encoded_frame("HEVC", &PREDICTED)),
+        Err(VslError::Mux(MuxError::UnsupportedCodec(_)))
+    ));
+}
@@ -0,0 +1,85 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use videostream::{
+    client::{Client, RecvMode},
+    frame::Frame,
+    playback::FileHost,
+};
+
+#[test]
+fn test_save_load() {
+    let mut frame = Frame::new(32, 16, 0, "RGB3").unwrap();
+    frame.alloc(None).unwrap();
+    for (i, byte) in frame.mmap_mut().unwrap().iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    let mut saved = Vec::new();
+    frame.save(&mut saved).unwrap();
+    frame.save(&mut saved).unwrap();
+
+    let mut reader = saved.as_slice();
+    for _ in 0..2 {
+        let loaded = Frame::load(&mut reader).unwrap();
+        assert_eq!(loaded.format(), frame.format());
+        assert_eq!(loaded.to_vec().unwrap(), frame.to_vec().unwrap());
+    }
+    assert!(reader.is_empty());
+    assert!(Frame::load(&mut &saved[..20]).is_err());
+    assert!(Frame::load(&mut &b"not a frame at all, nor anything close"[..]).is_err());
+}
+
+#[test]
+fn test_file_host() {
+    let recording = Path::new("/tmp/test_file_host.frames");
+    let mut file = File::create(recording).unwrap();
+    for value in 1..=3 {
+        let mut frame = Frame::new(16, 16, 0, "GREY").unwrap();
+        frame.alloc(None).unwrap();
+        frame.mmap_mut().unwrap().fill(value);
+        frame.save(&mut file).unwrap();
+    }
+    drop(file);
+
+    let path = "/tmp/test_file_host.vsl";
+    let host = FileHost::new(path, recording)
+        .unwrap()
+        .frame_rate(100.0)
+        .looping(true);
+    assert_eq!(host.frame_count(), 3);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let stop = cancel.clone();
+    let receiver = thread::spawn(move || {
+        let client = Client::new(path, false).unwrap();
+        client.set_timeout(5.0);
+        let values: Vec<u8> = (0..6)
+            .map(|_| {
+                client
+                    .recv(RecvMode::Blocking(Duration::from_secs(5)))
+                    .unwrap()
+                    .mmap()
+                    .unwrap()[0]
+            })
+            .collect();
+        stop.store(true, Ordering::Release);
+        return values;
+    });
+    host.run_with_cancel(cancel).unwrap();
+
+    // The client may join part way through but then sees the frames in order,
+    // starting over after the last.
+    let values = receiver.join().unwrap();
+    for pair in values.windows(2) {
+        assert_eq!(pair[1], pair[0] % 3 + 1);
+    }
+    fs::remove_file(recording).unwrap();
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "async")]
+
+use futures::{executor::block_on, StreamExt};
+use std::{thread, time::Duration};
+use videostream::{
+    client::Client,
+    frame::Frame,
+    host::Host,
+    stream::{BufferPolicy, FrameStream},
+    timestamp,
+};
+
+#[test]
+fn test_into_stream() {
+    let path = "/tmp/test_into_stream.vsl";
+    let host = Host::new(path).unwrap();
+    let client = Client::new(path, false).unwrap();
+    client.set_timeout(5.0);
+
+    host.poll(100).unwrap();
+    host.process().unwrap();
+
+    for i in 0..5 {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, i, i)
+            .unwrap();
+    }
+
+    let frames: Vec<_> = block_on(client.into_stream().take(5).collect());
+    let pts: Vec<_> = frames.into_iter().map(|f| f.unwrap().pts()).collect();
+    assert_eq!(pts, vec![0, 1, 2, 3, 4]);
+}
+
+/// Posts five frames, numbered by their pts, which the stream's thread
+/// receives before any is polled.
+fn posted_stream(path: &str, policy: BufferPolicy) -> (Host, FrameStream) {
+    let host = Host::new(path).unwrap();
+    let client = Client::new(path, false).unwrap();
+    client.set_timeout(5.0);
+
+    host.poll(100).unwrap();
+    host.process().unwrap();
+
+    for i in 0..5 {
+        let frame = Frame::new(64, 64, 0, "RGB3").unwrap();
+        frame.alloc(None).unwrap();
+        host.post_frame(frame, timestamp() + 10_000_000_000, 0, i, i)
+            .unwrap();
+    }
+    let stream = client.into_stream().with_policy(policy);
+    thread::sleep(Duration::from_millis(200));
+    return (host, stream);
+}
+
+#[test]
+fn test_stream_policy() {
+    let (_host, mut stream) =
+        posted_stream("/tmp/test_stream_oldest.vsl", BufferPolicy::DropOldest);
+    assert_eq!(stream.policy(), BufferPolicy::DropOldest);
+    let frame = block_on(stream.next()).unwrap().unwrap();
+    assert_eq!(frame.pts(), 4);
+    assert_eq!(stream.dropped(), 4);
+
+    let (_host, mut stream) =
+        posted_stream("/tmp/test_stream_newest.vsl", BufferPolicy::DropNewest);
+    let frame = block_on(stream.next()).unwrap().unwrap();
+    assert_eq!(frame.pts(), 0);
+    assert_eq!(stream.dropped(), 4);
+
+    let (_host, stream) = posted_stream("/tmp/test_stream_block.vsl", BufferPolicy::Block(2));
+    let frames: Vec<_> = block_on(stream.take(5).collect());
+    let pts: Vec<_> = frames.into_iter().map(|f| f.unwrap().pts()).collect();
+    assert_eq!(pts, vec![0, 1, 2, 3, 4]);
+}
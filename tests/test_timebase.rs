@@ -0,0 +1,23 @@
+use videostream::timebase::Timebase;
+
+#[test]
+fn test_from_nanos() {
+    // One 30fps frame is 3000 ticks of the 90kHz clock.
+    assert_eq!(Timebase::MPEG.from_nanos(33_333_333), 3000);
+    assert_eq!(Timebase::MPEG.from_nanos(1_000_000_000), 90_000);
+    assert_eq!(Timebase::new(1, 1000).from_nanos(1_500_000), 2);
+    assert_eq!(Timebase::new(1, 1000).from_nanos(1_499_999), 1);
+    assert_eq!(Timebase::new(1, 1000).from_nanos(-1_500_000), -2);
+    assert_eq!(Timebase::NANOSECONDS.from_nanos(12345), 12345);
+}
+
+#[test]
+fn test_to_nanos() {
+    assert_eq!(Timebase::MPEG.to_nanos(3000), 33_333_333);
+    assert_eq!(Timebase::new(1001, 30000).to_nanos(1), 33_366_667);
+}
+
+#[test]
+fn test_no_overflow() {
+    assert_eq!(Timebase::MPEG.from_nanos(i64::MAX), 830_103_483_316_930);
+}
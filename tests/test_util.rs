@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+use videostream::util::{CpuSet, JitterMeter, Pacer, ThreadConfig, ThreadPriority};
+
+#[test]
+fn test_pacer() {
+    let mut pacer = Pacer::new(100.0);
+    assert_eq!(pacer.interval(), Duration::from_millis(10));
+
+    let start = Instant::now();
+    pacer.wait();
+    assert!(start.elapsed() < Duration::from_millis(10));
+    for _ in 0..10 {
+        pacer.wait();
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(100), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(200), "{:?}", elapsed);
+
+    // A producer which fell behind is not made to catch up with a burst.
+    std::thread::sleep(Duration::from_millis(50));
+    pacer.wait();
+    let start = Instant::now();
+    pacer.wait();
+    assert!(start.elapsed() >= Duration::from_millis(5));
+
+    pacer.reset();
+    let start = Instant::now();
+    pacer.wait();
+    assert!(start.elapsed() < Duration::from_millis(10));
+}
+
+#[test]
+fn test_jitter_meter() {
+    let mut meter = JitterMeter::new(4);
+    assert!(meter.stats().is_none());
+
+    let ms = 1_000_000;
+    for time in [0, 10 * ms, 20 * ms, 30 * ms] {
+        meter.record(time);
+    }
+    let stats = meter.stats().unwrap();
+    assert_eq!(stats.samples, 3);
+    assert_eq!(stats.mean_interval, Duration::from_millis(10));
+    assert_eq!(stats.stddev, Duration::ZERO);
+
+    // Intervals of 10, 10, 30 and 10 ms once the first falls out of the window.
+    meter.record(60 * ms);
+    meter.record(70 * ms);
+    let stats = meter.stats().unwrap();
+    assert_eq!(stats.samples, 4);
+    assert_eq!(stats.mean_interval, Duration::from_millis(15));
+    assert_eq!(stats.max_gap, Duration::from_millis(30));
+    assert_eq!(stats.stddev, Duration::from_nanos(8_660_254));
+
+    // A clock going backwards restarts the measurement.
+    meter.record(0);
+    assert!(meter.stats().is_none());
+}
+
+#[test]
+fn test_thread_config() {
+    let config = ThreadConfig {
+        name: Some("vsl-test".to_string()),
+        priority: Some(ThreadPriority::Nice(5)),
+        affinity: Some(CpuSet::new(&[0])),
+    };
+    let (name, nice, cpus) = config
+        .spawn("unused", || {
+            let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            unsafe { libc::sched_getaffinity(0, std::mem::size_of_val(&set), &mut set) };
+            let cpus = unsafe { libc::CPU_COUNT(&set) };
+            let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            let name = std::thread::current().name().map(str::to_string);
+            return (name, nice, cpus);
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(name.as_deref(), Some("vsl-test"));
+    assert_eq!(nice, 5);
+    assert_eq!(cpus, 1);
+
+    // Threads are named after their role by default.
+    let name = ThreadConfig::default()
+        .spawn("vsl-role", || {
+            return std::thread::current().name().map(str::to_string);
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(name.as_deref(), Some("vsl-role"));
+
+    // A configuration which cannot be applied is reported to the spawner and
+    // the closure never runs.
+    let config = ThreadConfig {
+        affinity: Some(CpuSet::new(&[libc::CPU_SETSIZE as usize])),
+        ..ThreadConfig::default()
+    };
+    let err = config
+        .spawn("vsl-invalid", || panic!("ran unconfigured"))
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+}
@@ -1,3 +1,28 @@
+use std::env;
+
 fn main() {
-    println!("cargo:rustc-link-lib=videostream");
+    println!("cargo:rerun-if-env-changed=VIDEOSTREAM_LIB_DIR");
+    if let Ok(dir) = env::var("VIDEOSTREAM_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir);
+    }
+
+    #[cfg(feature = "system")]
+    {
+        let probe = pkg_config::Config::new()
+            .atleast_version("1.3.0")
+            .statik(cfg!(feature = "static"))
+            .probe("videostream");
+        if probe.is_ok() {
+            return;
+        }
+        println!(
+            "cargo:warning=pkg-config could not find videostream, falling back to default search"
+        );
+    }
+
+    if cfg!(feature = "static") {
+        println!("cargo:rustc-link-lib=static=videostream");
+    } else {
+        println!("cargo:rustc-link-lib=videostream");
+    }
 }
@@ -317,6 +317,10 @@ extern "C" {
 }
 extern "C" {
     #[doc = " Returns the stride in bytes of the video frame, to go from one row to the\n next.\n\n @since 1.3\n @memberof VSLFrame"]
+    pub fn vsl_frame_stride(frame: *const VSLFrame) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Returns the size in bytes of the video frame.\n\n @memberof VSLFrame"]
     pub fn vsl_frame_size(frame: *const VSLFrame) -> ::std::os::raw::c_int;
 }
 extern "C" {
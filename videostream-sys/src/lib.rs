@@ -2,4 +2,7 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+/// The library version of the header the bindings were generated from.
+pub const BUILT_AGAINST: &str = "1.3.0";
+
 include!("ffi.rs");
@@ -802,7 +802,7 @@ vsl_frame_height(const VSLFrame* frame);
 VSL_AVAILABLE_SINCE_1_3
 VSL_API
 int
-vsl_frame_size(const VSLFrame* frame);
+vsl_frame_stride(const VSLFrame* frame);
 
 /**
  * Returns the size in bytes of the video frame.